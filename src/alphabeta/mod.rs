@@ -1,2 +1,4 @@
 pub mod search;
-pub mod pvtable;
\ No newline at end of file
+pub mod pvtable;
+pub mod bench;
+pub mod transposition;
@@ -1,4 +1,36 @@
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use crate::alphabeta::pvtable::PVTable;
+use crate::alphabeta::transposition::{Bound, TranspositionTable};
+use crate::constants::pieces::{BLACK, EMPTY, WHITE};
+use crate::evaluation::evaluate::evaluate_position;
 use crate::game_board::board::Board;
+use crate::game_board::outcome::{GameResult, Outcome};
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::{generate_all_moves, CAPTURE_SCORE, PV_MOVE_SCORE, SECOND_KILLER_SCORE};
+use crate::moves::see::see;
+use crate::utils::io::move_to_uci;
+use crate::utils::time::time_ms;
+use crate::variants::variant::{no_moves_outcome, side_in_check, variant_outcome, Variant};
+
+pub const MAX_DEPTH: usize = 64; // Deepest ply the search tables have room for
+pub const INFINITE: i32 = 30000;
+pub const MATE: i32 = 29000; // Score of a mate on the board, mates further away score less
+// Scores beyond this are mates
+const IS_MATE: i32 = MATE - MAX_DEPTH as i32;
+
+// Moves searched at full depth before late move reductions start
+const LMR_FULL_DEPTH_MOVES: u32 = 3;
+const LMR_MIN_DEPTH: i32 = 3;
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+// Half width of the first aspiration window, doubled after each failed search
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_MIN_DEPTH: u64 = 4;
+
+/// A line found by the search: its first move, its score and the whole principal variation
+pub type PvLine = (GameMove, i32, Vec<GameMove>);
 
 #[derive(Debug, Clone)]
 pub struct SearhInfo {
@@ -6,14 +38,79 @@ pub struct SearhInfo {
     pub end_time: u64,
     pub depth: u64,
     pub depthset: u64,
-    pub timeset: u64,
+    pub timeset: bool,
     pub moves_to_go: u64,
+    pub node_limit: u64, // Nodes the main thread searches before stopping
+    pub mate_limit: Option<u64>, // Stop once a mate in this many moves or fewer is found
+    pub search_moves: Vec<GameMove>, // Root moves to search, all of them when empty
     pub infinite: bool,
 
     pub nodes: u64,
+    pub score: i32, // Score of the deepest completed iteration
+    pub pv: Vec<GameMove>, // Principal variation of the deepest completed iteration
+    pub multi_pv: usize, // Lines to search, each line's move excluded from the root for the next
+    pub lines: Vec<PvLine>, // The lines of the deepest completed iteration, best first
+    pub excluded: Vec<GameMove>, // Root moves not searched
 
     pub quit: bool,
     pub stopped: bool,
+
+    pub fail_high: u64,
+    pub fail_high_first: u64, // Fail highs on the first move searched, a measure of move ordering
+
+    pub fail_high_researches: u64, // Searches repeated after failing high of the aspiration window
+    pub fail_low_researches: u64,
+
+    pub null_move_pruning: bool,
+    pub late_move_reductions: bool,
+    pub principal_variation_search: bool,
+    pub aspiration_windows: bool,
+    pub post_thinking: bool, // Print the search information after each iteration
+
+    pub threads: usize, // Threads searching together, each helper thread on its own copy of the board
+    pub hash: Arc<TranspositionTable>, // Shared by every thread, and by clones of the info
+    pub stop: Arc<AtomicBool>, // Set to stop every thread of the search
+    pub pondering: Arc<AtomicBool>, // Set while searching on the opponent's time, the time limit waits until it is cleared
+    pub ponder_end_time: Arc<AtomicU64>, // End time set on a ponder hit, replacing end_time when not 0
+}
+
+impl Default for SearhInfo {
+    fn default() -> Self {
+        SearhInfo {
+            start_time: 0,
+            end_time: 0,
+            depth: 0,
+            depthset: MAX_DEPTH as u64,
+            timeset: false,
+            moves_to_go: 0,
+            node_limit: u64::MAX,
+            mate_limit: None,
+            search_moves: Vec::new(),
+            infinite: false,
+            nodes: 0,
+            score: 0,
+            pv: Vec::new(),
+            multi_pv: 1,
+            lines: Vec::new(),
+            excluded: Vec::new(),
+            quit: false,
+            stopped: false,
+            fail_high: 0,
+            fail_high_first: 0,
+            fail_high_researches: 0,
+            fail_low_researches: 0,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            principal_variation_search: true,
+            aspiration_windows: true,
+            post_thinking: true,
+            threads: 1,
+            hash: Arc::new(TranspositionTable::default()),
+            stop: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
+            ponder_end_time: Arc::new(AtomicU64::new(0)),
+        }
+    }
 }
 
 /// Checks if the current position is a repetition of a previous position
@@ -26,7 +123,7 @@ pub struct SearhInfo {
 
 #[inline(always)]
 pub fn is_repetition(pos: &Board) -> bool {
-    for i in pos.history_ply - pos.fifty_move..pos.history_ply - 1 { //Only have to start looking after most recent capture
+    for i in pos.history_ply.saturating_sub(pos.fifty_move)..pos.history_ply.saturating_sub(1) { //Only have to start looking after most recent capture
         if pos.pos_key == pos.history[i as usize].pos_key {
             return true;
         }
@@ -34,9 +131,637 @@ pub fn is_repetition(pos: &Board) -> bool {
     false
 }
 
+/// Stops the search once its time or nodes are used up or another thread has stopped it.
+/// While pondering there is no time limit. Nothing stops the first iteration, so even a stop
+/// sent straight away or a tiny time or node limit gives a move.
+fn check_up(info: &mut SearhInfo) {
+    if info.depth == 0 {
+        return;
+    }
+    if info.nodes >= info.node_limit {
+        info.stop.store(true, Ordering::Relaxed);
+    }
+    if !info.pondering.load(Ordering::Relaxed) {
+        // A ponder hit can bring a time limit to a search started without one
+        let end_time = match info.ponder_end_time.load(Ordering::Relaxed) {
+            0 => info.timeset.then_some(info.end_time),
+            end_time => Some(end_time),
+        };
+        if end_time.is_some_and(|end_time| time_ms() > end_time) {
+            info.stop.store(true, Ordering::Relaxed);
+        }
+    }
+    if info.stop.load(Ordering::Relaxed) {
+        info.stopped = true;
+    }
+}
+
+/// Resets the search tables and counters before a new search
+fn clear_for_search(pos: &mut Board, info: &mut SearhInfo) {
+    pos.search_history = [[0; 64]; 13];
+    pos.search_killers = [[0; 64]; 2];
+    pos.ply = 0;
+
+    info.stopped = false;
+    info.pv.clear();
+    info.lines.clear();
+    info.nodes = 0;
+    info.fail_high = 0;
+    info.fail_high_first = 0;
+    info.fail_high_researches = 0;
+    info.fail_low_researches = 0;
+    info.score = 0;
+    info.depth = 0;
+}
+
+/// Swaps the highest scored move from `start` onwards into `start`, so moves are searched in
+/// ordering score order without sorting the whole list up front
+#[inline(always)]
+fn pick_next_move(list: &mut [GameMove], start: usize) {
+    let mut best = start;
+    for i in start + 1..list.len() {
+        if list[i].score > list[best].score {
+            best = i;
+        }
+    }
+    list.swap(start, best);
+}
+
+/// Lends the board's principal variation table out alongside the board itself, since the
+/// table's methods need to read or make moves on the board that holds it
+fn with_pv_table<T>(pos: &mut Board, f: impl FnOnce(&mut PVTable, &mut Board) -> T) -> T {
+    let mut table = mem::replace(&mut pos.pvtable, PVTable { ptable: Vec::new() });
+    let result = f(&mut table, pos);
+    pos.pvtable = table;
+    result
+}
+
+/// Gives the principal variation move of the position the highest ordering score, or the
+/// transposition table's move when the principal variation table has none
+fn score_pv_move(pos: &Board, list: &mut [GameMove], hash_move: Option<GameMove>) {
+    if let Some(pv_move) = pos.pvtable.probe(pos).or(hash_move) {
+        if let Some(mov) = list.iter_mut().find(|mov| **mov == pv_move) {
+            mov.score = PV_MOVE_SCORE;
+        }
+    }
+}
+
+/// Makes a mate score count from the current position rather than the root for the
+/// transposition table, as the same position can be reached at different plies
+#[inline(always)]
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    if score > IS_MATE {
+        score + ply
+    } else if score < -IS_MATE {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Makes a mate score from the transposition table count from the root again
+#[inline(always)]
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if score > IS_MATE {
+        score - ply
+    } else if score < -IS_MATE {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// Whether captures trade material the usual way, so static exchange evaluation applies.
+/// Atomic explosions and compulsory antichess captures make it meaningless.
+#[inline(always)]
+fn exchanges_apply(pos: &Board) -> bool {
+    !matches!(pos.variant, Variant::Atomic | Variant::Antichess)
+}
+
+/// Moves captures that lose material by static exchange evaluation behind the killer and
+/// quiet moves, keeping their order among themselves
+fn demote_losing_captures(pos: &Board, list: &mut [GameMove]) {
+    for mov in list.iter_mut().filter(|mov| mov.score >= CAPTURE_SCORE) {
+        if see(pos, *mov) < 0 {
+            mov.score -= CAPTURE_SCORE;
+        }
+    }
+}
+
+/// Scores a finished game from the point of view of the side to move, preferring the
+/// quickest wins and the slowest losses
+fn outcome_score(pos: &Board, outcome: Outcome) -> i32 {
+    let winner = match outcome.result {
+        GameResult::Draw => return 0,
+        GameResult::WhiteWin => WHITE,
+        GameResult::BlackWin => BLACK,
+    };
+    if winner == pos.side {
+        MATE - pos.ply as i32
+    } else {
+        -MATE + pos.ply as i32
+    }
+}
+
+/// Number of plies a late quiet move is searched with less depth, growing with the depth
+/// left and how late the move is ordered
+#[inline(always)]
+fn late_move_reduction(depth: i32, moves_searched: u32) -> i32 {
+    let reduction = 0.5 + (depth as f64).ln() * (moves_searched as f64).ln() / 2.5;
+    (reduction as i32).clamp(1, depth - 2)
+}
+
+/// Searches captures until the position is quiet, so the static evaluation isn't taken in
+/// the middle of an exchange
+///
+/// # Arguments
+///
+/// * `alpha`: The score the side to move is already guaranteed
+/// * `beta`: The score the opponent is already guaranteed
+/// * `pos`: The board state
+/// * `info`: The search limits and statistics
+///
+/// returns: i32 The score from the point of view of the side to move
+fn quiescence(mut alpha: i32, beta: i32, pos: &mut Board, info: &mut SearhInfo) -> i32 {
+    if info.nodes & 2047 == 0 || info.nodes >= info.node_limit {
+        check_up(info);
+    }
+    info.nodes += 1;
+
+    if is_repetition(pos) || pos.fifty_move >= 100 {
+        return 0;
+    }
+    if let Some(outcome) = variant_outcome(pos) {
+        return outcome_score(pos, outcome);
+    }
+
+    let stand_pat = evaluate_position(pos);
+    if pos.ply as usize >= MAX_DEPTH - 1 || stand_pat >= beta {
+        return stand_pat.min(beta);
+    }
+    if stand_pat > alpha {
+        alpha = stand_pat;
+    }
+
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    move_list.retain(|mov| mov.capture() != EMPTY || mov.is_en_passant());
+
+    let prune_losing = exchanges_apply(pos);
+    let mut legal = 0;
+    for i in 0..move_list.len() {
+        pick_next_move(&mut move_list, i);
+        let mov = move_list[i];
+        // A capture that loses material can't raise the stand pat score
+        if prune_losing && see(pos, mov) < 0 {
+            continue;
+        }
+        if !pos.make_move(mov) {
+            continue;
+        }
+        legal += 1;
+        let score = -quiescence(-beta, -alpha, pos, info);
+        pos.undo_move();
+
+        if info.stopped {
+            return 0;
+        }
+        if score > alpha {
+            if score >= beta {
+                if legal == 1 {
+                    info.fail_high_first += 1;
+                }
+                info.fail_high += 1;
+                return beta;
+            }
+            alpha = score;
+        }
+    }
+    alpha
+}
+
+/// Searches the position to the given depth with fail hard alpha beta, storing the best
+/// moves found in the board's principal variation table
+///
+/// # Arguments
+///
+/// * `alpha`: The score the side to move is already guaranteed
+/// * `beta`: The score the opponent is already guaranteed
+/// * `depth`: The number of plies left to search before quiescence
+/// * `pos`: The board state
+/// * `info`: The search limits and statistics
+/// * `do_null`: Whether a null move may be tried, false straight after one
+///
+/// returns: i32 The score from the point of view of the side to move
+fn alpha_beta(mut alpha: i32, mut beta: i32, depth: i32, pos: &mut Board, info: &mut SearhInfo, do_null: bool) -> i32 {
+    if depth <= 0 {
+        return quiescence(alpha, beta, pos, info);
+    }
+
+    if info.nodes & 2047 == 0 || info.nodes >= info.node_limit {
+        check_up(info);
+    }
+    info.nodes += 1;
+
+    if pos.ply > 0 && (is_repetition(pos) || pos.fifty_move >= 100) {
+        return 0;
+    }
+    if pos.ply as usize >= MAX_DEPTH - 1 {
+        return evaluate_position(pos);
+    }
+    if let Some(outcome) = variant_outcome(pos) {
+        return outcome_score(pos, outcome);
+    }
+
+    // Mate distance pruning: no line from here can do better than mating at once or worse
+    // than being mated at once, so a window outside those scores needs no search
+    if pos.ply > 0 {
+        alpha = alpha.max(-MATE + pos.ply as i32);
+        beta = beta.min(MATE - pos.ply as i32 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+    }
+
+    // A result from the transposition table at least as deep as this search ends it when it
+    // is exact or its bound falls outside the window
+    let root_distance = pos.ply as i32;
+    let hit = info.hash.probe(pos.pos_key);
+    if let Some(hit) = hit {
+        let score = score_from_table(hit.score, root_distance);
+        if pos.ply > 0 && hit.depth >= depth {
+            match hit.bound {
+                Bound::Exact => return score.clamp(alpha, beta),
+                Bound::Lower if score >= beta => return beta,
+                Bound::Upper if score <= alpha => return alpha,
+                _ => (),
+            }
+        }
+    }
+
+    let in_check = side_in_check(pos, pos.side);
+    let depth = if in_check { depth + 1 } else { depth };
+
+    // Passing the turn is usually worse than any move, so if the opponent still can't reach
+    // beta after a free move with a shallower search the node is cut. Zugzwang makes this
+    // unsound in pawn endgames and in antichess, where captures are compulsory.
+    if do_null && info.null_move_pruning && !in_check && pos.ply > 0 && depth >= NULL_MOVE_MIN_DEPTH
+        && pos.num_big_pieces[pos.side as usize] > 1 && pos.variant != Variant::Antichess {
+        let reduction = if depth > 6 { 3 } else { 2 };
+        pos.make_null_move();
+        let score = -alpha_beta(-beta, -beta + 1, depth - 1 - reduction, pos, info, false);
+        pos.undo_null_move();
+
+        if info.stopped {
+            return 0;
+        }
+        if score >= beta && score.abs() < MATE - MAX_DEPTH as i32 {
+            return beta;
+        }
+    }
+
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    if exchanges_apply(pos) {
+        demote_losing_captures(pos, &mut move_list);
+    }
+    score_pv_move(pos, &mut move_list, hit.and_then(|hit| hit.mov));
+
+    let old_alpha = alpha;
+    let mut best_move = None;
+    let mut legal = 0;
+
+    for i in 0..move_list.len() {
+        pick_next_move(&mut move_list, i);
+        let mov = move_list[i];
+        if pos.ply == 0 && !is_root_move(info, mov) {
+            continue;
+        }
+        let moving_piece = if mov.is_drop() { mov.dropped_piece() } else { pos.pieces[mov.origin() as usize] };
+        if !pos.make_move(mov) {
+            continue;
+        }
+        legal += 1;
+
+        let quiet = mov.capture() == EMPTY && !mov.is_en_passant() && mov.promoted_piece() == EMPTY;
+        // Late quiet moves are unlikely to be best, so they get a reduced search first and
+        // only a full depth one if they beat alpha
+        let reduce = info.late_move_reductions && quiet && !in_check && depth >= LMR_MIN_DEPTH
+            && legal > LMR_FULL_DEPTH_MOVES && mov.score < SECOND_KILLER_SCORE && !side_in_check(pos, pos.side);
+        let reduction = if reduce { late_move_reduction(depth, legal - 1) } else { 0 };
+        let score = if legal == 1 {
+            -alpha_beta(-beta, -alpha, depth - 1, pos, info, true)
+        } else if info.principal_variation_search {
+            // Once the first move is searched the others are expected to fail low, which a
+            // null window search proves cheaply. Only moves that beat alpha get the full window.
+            let mut score = -alpha_beta(-alpha - 1, -alpha, depth - 1 - reduction, pos, info, true);
+            if score > alpha && reduction > 0 {
+                score = -alpha_beta(-alpha - 1, -alpha, depth - 1, pos, info, true);
+            }
+            if score > alpha && score < beta {
+                score = -alpha_beta(-beta, -alpha, depth - 1, pos, info, true);
+            }
+            score
+        } else if reduction > 0 {
+            let score = -alpha_beta(-alpha - 1, -alpha, depth - 1 - reduction, pos, info, true);
+            if score > alpha {
+                -alpha_beta(-beta, -alpha, depth - 1, pos, info, true)
+            } else {
+                score
+            }
+        } else {
+            -alpha_beta(-beta, -alpha, depth - 1, pos, info, true)
+        };
+        pos.undo_move();
+
+        if info.stopped {
+            return 0;
+        }
+
+        if score > alpha {
+            if score >= beta {
+                if legal == 1 {
+                    info.fail_high_first += 1;
+                }
+                info.fail_high += 1;
+
+                let ply = pos.ply as usize;
+                if quiet && ply < MAX_DEPTH {
+                    pos.search_killers[1][ply] = pos.search_killers[0][ply];
+                    pos.search_killers[0][ply] = mov.move_int as u64;
+                }
+                // Kept so a re-search tries the move first, and so a root fail high reports it
+                with_pv_table(pos, |table, pos| table.store(pos, mov));
+                info.hash.store(pos.pos_key, Some(mov), score_to_table(beta, root_distance), depth, Bound::Lower);
+                return beta;
+            }
+            alpha = score;
+            best_move = Some(mov);
+            if quiet {
+                let to64 = pos.sq64(mov.destination()) as usize;
+                pos.search_history[moving_piece as usize][to64] += (depth * depth) as u64;
+            }
+        }
+    }
+
+    if legal == 0 {
+        return outcome_score(pos, no_moves_outcome(pos));
+    }
+
+    if alpha != old_alpha {
+        if let Some(mov) = best_move {
+            with_pv_table(pos, |table, pos| table.store(pos, mov));
+        }
+        info.hash.store(pos.pos_key, best_move, score_to_table(alpha, root_distance), depth, Bound::Exact);
+    } else {
+        info.hash.store(pos.pos_key, None, score_to_table(alpha, root_distance), depth, Bound::Upper);
+    }
+    alpha
+}
+
+/// Runs an iterative deepening search of the position, searching one ply deeper each
+/// iteration until the depth or time limit is reached
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `info`: The search limits, which also collects the search statistics
+///
+/// returns: Option<GameMove> The best move found, or None if there are no legal moves
+pub fn search_position(pos: &mut Board, info: &mut SearhInfo) -> Option<GameMove> {
+    clear_for_search(pos, info);
+    let best_move = if info.threads <= 1 {
+        iterative_deepening(pos, info, 0)
+    } else {
+        search_threads(pos, info)
+    };
+    // Cleared once the search is over rather than when it starts, so a stop sent before the
+    // search got going is not lost
+    info.stop.store(false, Ordering::Relaxed);
+    best_move
+}
+
+/// Searches the best few moves of the position, each with its own line. Each line's move is
+/// excluded from the root when searching for the next, so every line has a different first
+/// move. See `search_position` for the limits.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `info`: The search limits, which also collects the search statistics
+/// * `lines`: How many lines to find, fewer are given when there are fewer legal moves
+///
+/// returns: Vec<PvLine> The lines of the deepest completed iteration, best first
+pub fn search_multi_pv(pos: &mut Board, info: &mut SearhInfo, lines: usize) -> Vec<PvLine> {
+    info.multi_pv = lines;
+    search_position(pos, info);
+    info.lines.clone()
+}
+
+/// The limits for a helper thread. Helpers stay quiet and leave the node limit to the main
+/// thread, which stops them all through the shared stop flag.
+fn helper_info(info: &SearhInfo) -> SearhInfo {
+    SearhInfo { post_thinking: false, node_limit: u64::MAX, ..info.clone() }
+}
+
+/// Lazy SMP: helper threads search the same position on their own boards, sharing what they
+/// find through the transposition table. The main thread's result is the one used, and when
+/// it finishes the helpers are stopped.
+fn search_threads(pos: &mut Board, info: &mut SearhInfo) -> Option<GameMove> {
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..info.threads).map(|id| {
+            let mut board = pos.clone();
+            let mut helper = helper_info(info);
+            scope.spawn(move || {
+                iterative_deepening(&mut board, &mut helper, id);
+                helper.nodes
+            })
+        }).collect();
+
+        let best_move = iterative_deepening(pos, info, 0);
+        info.stop.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            info.nodes += helper.join().unwrap_or(0);
+        }
+        best_move
+    })
+}
+
+/// Searches one ply deeper each iteration. Odd numbered helper threads search each depth one
+/// ply deeper than the main thread, so the threads spread over more of the tree.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `info`: The search limits, which also collects this thread's search statistics
+/// * `thread_id`: 0 for the main thread, from 1 up for the helpers
+///
+/// returns: Option<GameMove> The best move of the deepest completed iteration
+fn iterative_deepening(pos: &mut Board, info: &mut SearhInfo, thread_id: usize) -> Option<GameMove> {
+    let mut best_move = None;
+    let max_depth = info.depthset.min(MAX_DEPTH as u64 - 1);
+    let line_count = if thread_id == 0 { info.multi_pv.clamp(1, count_root_moves(pos, info).max(1)) } else { 1 };
+
+    for depth in 1..=max_depth {
+        let current_depth = (depth + (thread_id % 2) as u64).min(max_depth);
+        // Each line after the first is the best move left once the moves of the lines
+        // before it are excluded at the root
+        let mut lines = Vec::new();
+        for line in 1..=line_count {
+            let score = if line == 1 {
+                aspiration_search(pos, info, current_depth)
+            } else {
+                alpha_beta(-INFINITE, INFINITE, current_depth as i32, pos, info, true)
+            };
+            if info.stopped {
+                break;
+            }
+            let pv_moves = report_iteration(pos, info, current_depth, score, "", line);
+            if pv_moves == 0 {
+                break;
+            }
+            lines.push((pos.pvarray[0], score, pos.pvarray[..pv_moves as usize].to_vec()));
+            info.excluded.push(pos.pvarray[0]);
+        }
+        info.excluded.clear();
+        if info.stopped || lines.is_empty() {
+            break;
+        }
+        if lines.len() > 1 {
+            // The later lines replaced the root's entry, the next iteration should try the best move first
+            with_pv_table(pos, |table, pos| table.store(pos, lines[0].0));
+        }
+
+        info.score = lines[0].1;
+        info.pv = lines[0].2.clone();
+        best_move = Some(lines[0].0);
+        info.lines = lines;
+        info.depth = current_depth;
+        // A stop sent during the first iteration is only seen once it is done
+        check_up(info);
+        if info.stopped {
+            break;
+        }
+        if info.mate_limit.is_some_and(|moves| info.score > IS_MATE && mate_moves(info.score) <= moves as i32) {
+            break;
+        }
+    }
+    best_move
+}
+
+/// Searches the root to a depth inside an aspiration window. Scores rarely move far between
+/// iterations, so a narrow window around the last one cuts more of the tree. It is widened
+/// on whichever side the score falls outside.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `info`: The search limits and statistics, holding the last iteration's score
+/// * `depth`: The depth of the iteration
+///
+/// returns: i32 The score, meaningless if the search was stopped
+fn aspiration_search(pos: &mut Board, info: &mut SearhInfo, depth: u64) -> i32 {
+    let use_window = info.aspiration_windows && depth >= ASPIRATION_MIN_DEPTH
+        && info.score.abs() < MATE - MAX_DEPTH as i32;
+    let mut delta = ASPIRATION_WINDOW;
+    let (mut alpha, mut beta) = if use_window {
+        (info.score - delta, info.score + delta)
+    } else {
+        (-INFINITE, INFINITE)
+    };
+
+    loop {
+        let score = alpha_beta(alpha, beta, depth as i32, pos, info, true);
+        if info.stopped {
+            return score;
+        }
+        delta *= 2;
+        if score <= alpha && alpha > -INFINITE {
+            info.fail_low_researches += 1;
+            report_iteration(pos, info, depth, score, " upperbound", 1);
+            alpha = (score - delta).max(-INFINITE);
+        } else if score >= beta && beta < INFINITE {
+            info.fail_high_researches += 1;
+            report_iteration(pos, info, depth, score, " lowerbound", 1);
+            beta = (score + delta).min(INFINITE);
+        } else {
+            return score;
+        }
+    }
+}
+
+/// Whether a move is searched at the root, being one of the search moves if any were given
+/// and not the move of an earlier line
+#[inline(always)]
+fn is_root_move(info: &SearhInfo, mov: GameMove) -> bool {
+    (info.search_moves.is_empty() || info.search_moves.contains(&mov)) && !info.excluded.contains(&mov)
+}
+
+/// Counts the legal moves searched at the root
+fn count_root_moves(pos: &mut Board, info: &SearhInfo) -> usize {
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    move_list.into_iter().filter(|&mov| {
+        if !is_root_move(info, mov) {
+            return false;
+        }
+        let legal = pos.make_move(mov);
+        if legal {
+            pos.undo_move();
+        }
+        legal
+    }).count()
+}
+
+/// Moves to a mate from a mate score, negative when the side to move is getting mated
+#[inline(always)]
+fn mate_moves(score: i32) -> i32 {
+    if score > 0 {
+        (MATE - score + 1) / 2
+    } else {
+        -(MATE + score) / 2
+    }
+}
+
+/// Writes a score the way UCI reports it, `mate <moves>` for mates and `cp <centipawns>`
+/// otherwise
+pub fn uci_score(score: i32) -> String {
+    if score.abs() > IS_MATE {
+        format!("mate {}", mate_moves(score))
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Reads the principal variation into the board's `pvarray` and prints the search
+/// information for it when thinking is posted. A bound marks the score of a search that
+/// failed outside its aspiration window.
+///
+/// # Arguments
+///
+/// * `pos`: The board state at the root of the search
+/// * `info`: The search statistics
+/// * `depth`: The depth of the iteration
+/// * `score`: The score the search returned
+/// * `bound`: " lowerbound" after a fail high, " upperbound" after a fail low, otherwise empty
+/// * `line`: The rank of the line from 1, only written when more than one line is searched
+///
+/// returns: u8 The number of moves in the principal variation
+fn report_iteration(pos: &mut Board, info: &SearhInfo, depth: u64, score: i32, bound: &str, line: usize) -> u8 {
+    let pv_moves = with_pv_table(pos, |table, pos| table.get_line(depth as u8, pos));
+    if info.post_thinking {
+        let pv: Vec<String> = pos.pvarray[..pv_moves as usize].iter().map(|mov| move_to_uci(pos, *mov)).collect();
+        let multi_pv = if info.multi_pv > 1 { format!(" multipv {}", line) } else { String::new() };
+        println!("info depth {}{} score {}{} nodes {} time {} pv {}",
+                 depth, multi_pv, uci_score(score), bound, info.nodes, time_ms().saturating_sub(info.start_time), pv.join(" "));
+    }
+    pv_moves
+}
+
 #[cfg(test)]
 mod test {
-    use crate::alphabeta::search::is_repetition;
+    use std::sync::atomic::Ordering;
+    use crate::alphabeta::search::{check_up, helper_info, is_repetition, search_multi_pv, search_position, uci_score, SearhInfo, MATE, MAX_DEPTH};
     use crate::game_board::board::Board;
     use crate::moves::gamemove::GameMove;
     use crate::utils::square_utils::fr2sq;
@@ -66,4 +791,215 @@ mod test {
         board.make_move(GameMove::new(fr2sq(FILE_H, RANK_6), fr2sq(FILE_G, RANK_8), 0, 0, 0));
         assert_eq!(is_repetition(&board), true);
     }
-}
\ No newline at end of file
+
+    fn search(fen: &str, depth: u64, null_move_pruning: bool, late_move_reductions: bool) -> (String, SearhInfo) {
+        let mut board = Board::new();
+        board.set_fen(fen).unwrap();
+        let mut info = SearhInfo { depthset: depth, post_thinking: false, null_move_pruning, late_move_reductions, ..SearhInfo::default() };
+        let best = search_position(&mut board, &mut info).expect("Did not find a move");
+        (crate::utils::io::move_to_uci(&board, best), info)
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        let (best, _) = search("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 3, true, true);
+        assert_eq!(best, "d1d8");
+    }
+
+    #[test]
+    fn test_wins_hanging_queen() {
+        let (best, _) = search("3qk3/8/8/8/8/8/8/3RK3 w - - 0 1", 4, true, true);
+        assert_eq!(best, "d1d8");
+    }
+
+    #[test]
+    fn test_mate_score() {
+        let (_, info) = search("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 3, true, true);
+        assert_eq!(info.score, MATE - 1, "Did not score mate in one ply");
+    }
+
+    #[test]
+    fn test_pruning_reduces_nodes() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let (_, plain) = search(fen, 5, false, false);
+        let (_, null_move) = search(fen, 5, true, false);
+        let (_, reduced) = search(fen, 5, true, true);
+        assert!(null_move.nodes < plain.nodes, "Null move pruning did not reduce the tree");
+        assert!(reduced.nodes < null_move.nodes, "Late move reductions did not reduce the tree");
+    }
+
+    fn search_with(fen: &str, depth: u64, configure: fn(&mut SearhInfo)) -> SearhInfo {
+        let mut board = Board::new();
+        board.set_fen(fen).unwrap();
+        let mut info = SearhInfo { depthset: depth, post_thinking: false, ..SearhInfo::default() };
+        configure(&mut info);
+        search_position(&mut board, &mut info);
+        info
+    }
+
+    #[test]
+    fn test_windows_keep_the_score() {
+        // Without pruning, narrower windows only change how much of the tree is searched
+        let exhaustive = |info: &mut SearhInfo| {
+            info.null_move_pruning = false;
+            info.late_move_reductions = false;
+            info.principal_variation_search = false;
+            info.aspiration_windows = false;
+        };
+        let windowed = |info: &mut SearhInfo| {
+            info.null_move_pruning = false;
+            info.late_move_reductions = false;
+        };
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10"] {
+            let full = search_with(fen, 4, exhaustive);
+            let narrow = search_with(fen, 4, windowed);
+            assert_eq!(narrow.score, full.score, "Windowed search changed the score of {}", fen);
+            assert!(narrow.nodes < full.nodes, "Windowed search did not reduce the tree of {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_aspiration_research() {
+        // The score of both positions moves by more than the window between iterations
+        let mut researches = 0;
+        for fen in ["8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8"] {
+            let info = search_with(fen, 6, |info| {
+                info.null_move_pruning = false;
+                info.late_move_reductions = false;
+            });
+            let full = search_with(fen, 6, |info| {
+                info.null_move_pruning = false;
+                info.late_move_reductions = false;
+                info.aspiration_windows = false;
+            });
+            assert_eq!(info.score, full.score, "Re-searching did not find the full window score of {}", fen);
+            researches += info.fail_high_researches + info.fail_low_researches;
+        }
+        assert!(researches > 0, "Aspiration windows never failed");
+    }
+
+    #[test]
+    fn test_helper_threads() {
+        let mut board = Board::new();
+        board.set_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let mut info = SearhInfo { depthset: 4, post_thinking: false, threads: 3, ..SearhInfo::default() };
+        let best = search_position(&mut board, &mut info).expect("Did not find a move");
+        assert_eq!(crate::utils::io::move_to_uci(&board, best), "d1d8");
+        assert_eq!(info.score, MATE - 1);
+        assert_eq!(info.depth, 4);
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let single = search_with(fen, 4, |_| ());
+        let shared = search_with(fen, 4, |info| info.threads = 2);
+        assert!(shared.nodes > single.nodes, "Helper nodes were not counted");
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let mut board = Board::new();
+        board.set_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut info = SearhInfo { post_thinking: false, threads: 2, ..SearhInfo::default() };
+        let stop = info.stop.clone();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+        let best = search_position(&mut board, &mut info);
+        stopper.join().unwrap();
+        assert!(best.is_some(), "No move from the completed iterations");
+        assert!(info.stopped);
+
+        // A stop sent before the search starts still lets the first iteration finish
+        let mut info = SearhInfo { post_thinking: false, ..SearhInfo::default() };
+        info.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(search_position(&mut board, &mut info).is_some(), "No move after an early stop");
+        assert_eq!(info.depth, 1);
+        let mut info = SearhInfo { post_thinking: false, timeset: true, end_time: 0, ..SearhInfo::default() };
+        assert!(search_position(&mut board, &mut info).is_some(), "No move once the time was up");
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        let mut board = Board::new();
+        board.set_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let mut info = SearhInfo { depthset: 4, post_thinking: false, ..SearhInfo::default() };
+        let lines = search_multi_pv(&mut board, &mut info, 3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(crate::utils::io::move_to_uci(&board, lines[0].0), "d1d8");
+        assert_eq!(lines[0].1, MATE - 1);
+        assert_eq!(info.score, MATE - 1);
+        for (i, (mov, score, pv)) in lines.iter().enumerate() {
+            assert_eq!(pv[0], *mov, "Line does not start with its move");
+            assert!(lines[..i].iter().all(|line| line.0 != *mov), "Move repeated in a later line");
+            assert!(i == 0 || *score <= lines[i - 1].1, "Lines not ordered by score");
+        }
+
+        // Only as many lines as there are legal moves
+        board.set_fen("7k/8/8/8/8/8/8/K5R1 b - - 0 1").unwrap();
+        let lines = search_multi_pv(&mut board, &mut info, 4);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(crate::utils::io::move_to_uci(&board, lines[0].0), "h8h7");
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let search = |limit| {
+            let mut board = Board::with_seed(1);
+            board.set_fen(fen).unwrap();
+            let mut info = SearhInfo { node_limit: limit, post_thinking: false, ..SearhInfo::default() };
+            let best = search_position(&mut board, &mut info).expect("No move within the node limit");
+            (best, info.nodes, info.depth)
+        };
+        let (best, nodes, depth) = search(20000);
+        assert!(nodes <= 20001, "Searched {} nodes", nodes);
+        assert_eq!(search(20000), (best, nodes, depth), "Node limited search is not repeatable");
+        // The first iteration always completes
+        assert_eq!(search(1).2, 1);
+    }
+
+    #[test]
+    fn test_node_limit_with_threads() {
+        // A helper that goes past the node limit must not stop the search
+        let mut helper = helper_info(&SearhInfo { node_limit: 1000, threads: 4, ..SearhInfo::default() });
+        helper.nodes = 5000;
+        helper.depth = 3;
+        check_up(&mut helper);
+        assert!(!helper.stopped);
+        assert!(!helper.stop.load(Ordering::Relaxed));
+
+        let mut board = Board::with_seed(1);
+        board.set_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut info = SearhInfo { node_limit: 20000, threads: 3, post_thinking: false, ..SearhInfo::default() };
+        assert!(search_position(&mut board, &mut info).is_some());
+        // The main thread alone searched up to the limit, the helpers' nodes come on top
+        assert!(info.nodes >= 20000, "Stopped after {} nodes", info.nodes);
+    }
+
+    #[test]
+    fn test_mate_limit_and_score() {
+        let mut board = Board::new();
+        board.set_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let mut info = SearhInfo { mate_limit: Some(1), post_thinking: false, ..SearhInfo::default() };
+        search_position(&mut board, &mut info).unwrap();
+        assert_eq!(info.score, MATE - 1);
+        assert!(info.depth <= 2, "Searched on to depth {} after finding the mate", info.depth);
+
+        assert_eq!(uci_score(MATE - 1), "mate 1");
+        assert_eq!(uci_score(MATE - 3), "mate 2");
+        assert_eq!(uci_score(-MATE + 2), "mate -1");
+        assert_eq!(uci_score(-35), "cp -35");
+    }
+
+    #[test]
+    fn test_search_moves() {
+        let mut board = Board::new();
+        board.set_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let only = crate::utils::io::move_from_uci(&board, "d1d2").unwrap();
+        let mut info = SearhInfo { depthset: 3, post_thinking: false, search_moves: vec![only], ..SearhInfo::default() };
+        assert_eq!(search_position(&mut board, &mut info), Some(only));
+        assert!(info.score < MATE - MAX_DEPTH as i32);
+    }
+}
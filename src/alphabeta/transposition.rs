@@ -0,0 +1,205 @@
+//! Transposition table shared by all search threads. Entries are two atomic words, the
+//! entry's data and the position key xored with the data. A reader only trusts an entry
+//! whose words xor back to its key, so an entry torn by two threads writing at once reads
+//! as a miss rather than as another position's result, and no locks are needed.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::moves::gamemove::GameMove;
+
+/// Size of the table when none is given, in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// What a stored score says about the true score of the position
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower, // The search failed high, the true score is at least this
+    Upper, // The search failed low, the true score is at most this
+}
+
+/// A result found in the table
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TableHit {
+    pub mov: Option<GameMove>,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+}
+
+#[derive(Default)]
+struct Entry {
+    key: AtomicU64, // Position key xor data
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Entry>,
+}
+
+impl fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TranspositionTable {{ entries: {} }}", self.entries.len())
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+// Data word: move in the low 32 bits, then score, depth and bound
+#[inline(always)]
+fn pack(mov: Option<GameMove>, score: i32, depth: i32, bound: Bound) -> u64 {
+    let bound = match bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    mov.map_or(0, |mov| mov.move_int as u64)
+        | (score as i16 as u16 as u64) << 32
+        | (depth.clamp(0, 255) as u64) << 48
+        | bound << 56
+}
+
+#[inline(always)]
+fn unpack(data: u64) -> Option<TableHit> {
+    let bound = match data >> 56 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let move_int = data as u32;
+    Some(TableHit {
+        mov: if move_int == 0 { None } else { Some(GameMove { move_int, score: 0 }) },
+        score: (data >> 32) as u16 as i16 as i32,
+        depth: (data >> 48 & 0xFF) as i32,
+        bound,
+    })
+}
+
+impl TranspositionTable {
+    /// Creates an empty table
+    ///
+    /// # Arguments
+    ///
+    /// * `megabytes`: The memory to use, at least one entry is always made
+    ///
+    /// returns: TranspositionTable
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let count = (megabytes * 1024 * 1024 / std::mem::size_of::<Entry>()).max(1);
+        TranspositionTable { entries: (0..count).map(|_| Entry::default()).collect() }
+    }
+
+    /// Empties the table, for a new game
+    pub fn clear(&self) {
+        for entry in self.entries.iter() {
+            entry.key.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    #[inline(always)]
+    fn entry(&self, key: u64) -> &Entry {
+        &self.entries[(key % self.entries.len() as u64) as usize]
+    }
+
+    /// Looks up a position
+    #[inline]
+    pub fn probe(&self, key: u64) -> Option<TableHit> {
+        let entry = self.entry(key);
+        let data = entry.data.load(Ordering::Relaxed);
+        if entry.key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        unpack(data)
+    }
+
+    /// Stores a search result for a position. It replaces whatever is in its slot, except a
+    /// deeper result for the same position.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The position key
+    /// * `mov`: The best move found, if any
+    /// * `score`: The score, with mate scores counted from this position
+    /// * `depth`: The depth searched
+    /// * `bound`: How the score relates to the true score
+    ///
+    /// returns: ()
+    #[inline]
+    pub fn store(&self, key: u64, mov: Option<GameMove>, score: i32, depth: i32, bound: Bound) {
+        let entry = self.entry(key);
+        if let Some(old) = self.probe(key) {
+            if old.depth > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+        let data = pack(mov, score, depth, bound);
+        entry.data.store(data, Ordering::Relaxed);
+        entry.key.store(key ^ data, Ordering::Relaxed);
+    }
+
+    /// Share of the table in use, in thousandths, from a sample of its entries
+    pub fn hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample].iter().filter(|entry| entry.data.load(Ordering::Relaxed) != 0).count();
+        used * 1000 / sample
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+    use crate::alphabeta::transposition::{*};
+    use crate::moves::gamemove::GameMove;
+
+    #[test]
+    fn test_store_and_probe() {
+        let table = TranspositionTable::new(1);
+        let mov = GameMove::new(35, 55, 0, 0, 0);
+        assert_eq!(table.probe(42), None);
+
+        table.store(42, Some(mov), -28990, 7, Bound::Lower);
+        let hit = table.probe(42).unwrap();
+        assert_eq!((hit.mov, hit.score, hit.depth, hit.bound), (Some(mov), -28990, 7, Bound::Lower));
+
+        table.store(42, None, 10, 3, Bound::Upper);
+        assert_eq!(table.probe(42).unwrap().depth, 7, "Deeper result was replaced");
+        table.store(42, None, 10, 3, Bound::Exact);
+        assert_eq!(table.probe(42).unwrap().bound, Bound::Exact);
+
+        assert!(table.hashfull() > 0);
+        table.clear();
+        assert_eq!(table.probe(42), None);
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_torn_entries_miss() {
+        // Threads writing different positions to the same slot never make a reader see one
+        // position's data under another's key
+        let table = Arc::new(TranspositionTable::new(0));
+        let writers: Vec<_> = (1..=4u64).map(|id| {
+            let table = table.clone();
+            thread::spawn(move || {
+                for i in 0..20000 {
+                    table.store(id, None, id as i32, i % 100, Bound::Exact);
+                }
+            })
+        }).collect();
+        for _ in 0..20000 {
+            for id in 1..=4u64 {
+                if let Some(hit) = table.probe(id) {
+                    assert_eq!(hit.score, id as i32, "Read another position's entry");
+                }
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::env;
+use std::process;
+use chess::alphabeta::bench::{run_bench, BenchResult, BENCH_FENS};
+use chess::alphabeta::search::SearhInfo;
+
+const USAGE: &str = "Usage:
+    bench [depth]              Compare node counts of the bench positions searched to a depth
+    bench --movetime <ms>      Compare depths reached with a fixed time for each position
+    bench --smp <threads> <ms> Compare one thread to several with a fixed time for each position";
+
+// Search settings compared side by side, each adding one feature: null move pruning, late
+// move reductions, principal variation search and aspiration windows
+const CONFIGURATIONS: [(&str, [bool; 4]); 5] = [
+    ("plain", [false, false, false, false]),
+    ("+null move", [true, false, false, false]),
+    ("+lmr", [true, true, false, false]),
+    ("+pvs", [true, true, true, false]),
+    ("+aspiration", [true, true, true, true]),
+];
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Searches the bench positions for a fixed time with one thread and with several, showing
+/// how much deeper the threads get together
+fn compare_threads(threads: usize, time: u64) {
+    let template = SearhInfo { post_thinking: false, timeset: true, end_time: time, ..SearhInfo::default() };
+    let results: Vec<Vec<BenchResult>> = [1, threads].iter()
+        .map(|threads| run_bench(&SearhInfo { threads: *threads, ..template.clone() }))
+        .collect();
+
+    println!("{:>3} {:>16} {:>16}", "#", "1 thread", format!("{} threads", threads));
+    for position in 0..BENCH_FENS.len() {
+        print!("{:>3}", position + 1);
+        for configuration in results.iter() {
+            let result = configuration[position];
+            print!(" {:>16}", format!("{} d{}", result.nodes, result.depth));
+        }
+        println!();
+    }
+    print!("{:<8}", "Depth");
+    for configuration in results.iter() {
+        let depth: u64 = configuration.iter().map(|r| r.depth).sum();
+        print!(" {:>11.2}", depth as f64 / BENCH_FENS.len() as f64);
+    }
+    println!();
+    print!("{:<8}", "NPS");
+    for configuration in results.iter() {
+        let nodes: u64 = configuration.iter().map(|r| r.nodes).sum();
+        let time: u64 = configuration.iter().map(|r| r.time_ms).sum();
+        print!(" {:>11}", nodes * 1000 / time.max(1));
+    }
+    println!();
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut template = SearhInfo { post_thinking: false, ..SearhInfo::default() };
+    match args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>()[..] {
+        ["--smp", threads, time] => {
+            let threads = threads.parse().unwrap_or_else(|_| fail("Invalid number of threads"));
+            return compare_threads(threads, time.parse().unwrap_or_else(|_| fail("Invalid time")));
+        }
+        [] => template.depthset = 6,
+        ["--movetime", time] => {
+            template.timeset = true;
+            template.end_time = time.parse().unwrap_or_else(|_| fail("Invalid time"));
+        }
+        [depth] => template.depthset = depth.parse().unwrap_or_else(|_| fail("Invalid depth")),
+        _ => fail("Unexpected arguments"),
+    }
+
+    let results: Vec<Vec<BenchResult>> = CONFIGURATIONS.iter().map(|(_, [null_move, lmr, pvs, aspiration])| {
+        run_bench(&SearhInfo {
+            null_move_pruning: *null_move,
+            late_move_reductions: *lmr,
+            principal_variation_search: *pvs,
+            aspiration_windows: *aspiration,
+            ..template.clone()
+        })
+    }).collect();
+
+    print!("{:>3}", "#");
+    for (name, ..) in CONFIGURATIONS.iter() {
+        print!(" {:>16}", name);
+    }
+    println!();
+    for position in 0..BENCH_FENS.len() {
+        print!("{:>3}", position + 1);
+        for configuration in results.iter() {
+            let result = configuration[position];
+            print!(" {:>16}", format!("{} d{}", result.nodes, result.depth));
+        }
+        println!();
+    }
+
+    for (label, total) in [
+        ("Nodes", (|r: &BenchResult| r.nodes) as fn(&BenchResult) -> u64),
+        ("Depth", |r: &BenchResult| r.depth),
+        ("Time ms", |r: &BenchResult| r.time_ms),
+        ("Re-srch", |r: &BenchResult| r.researches),
+    ] {
+        print!("{:<8}", label);
+        for configuration in results.iter() {
+            print!(" {:>11}", configuration.iter().map(total).sum::<u64>());
+        }
+        println!();
+    }
+    print!("{:<8}", "FHF %");
+    for configuration in results.iter() {
+        let fail_high: u64 = configuration.iter().map(|r| r.fail_high).sum();
+        let first: u64 = configuration.iter().map(|r| r.fail_high_first).sum();
+        print!(" {:>11.1}", 100.0 * first as f64 / fail_high.max(1) as f64);
+    }
+    println!();
+}
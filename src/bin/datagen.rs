@@ -0,0 +1,91 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::process;
+use std::thread;
+use chess::game_board::board::Board;
+use chess::tuning::datagen::{generate, DatagenConfig};
+
+const USAGE: &str = "Usage:
+    datagen <out> [--games N] [--depth D] [--nodes N] [--threads T] [--seed S]
+                  [--random-plies N] [--max-plies N] [--book FILE] [--binary]
+
+Plays the engine against itself and writes the quiet positions of each game with their
+search score and the game's result. Text output has one `FEN score [result]` line for each
+position, which the tune binary reads. --binary writes packed 33 byte records instead. The
+book is a file of FEN or EPD lines to start games from, before the random moves. Each move
+is searched to depth D, 6 by default, or until it has used N nodes. With --nodes and no
+--depth only the node budget applies.";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Reads the positions of an opening book, the first four fields of each line
+fn read_book(path: &str) -> Vec<String> {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| fail(&format!("Can't read {}: {}", path, err)));
+    let mut board = Board::new();
+    text.lines()
+        .map(|line| line.split_whitespace().take(4).collect::<Vec<&str>>().join(" "))
+        .filter(|fen| !fen.is_empty())
+        .map(|fen| match board.set_fen(&fen) {
+            Ok(()) => fen,
+            Err(err) => fail(&format!("Bad opening {}: {}", fen, err)),
+        })
+        .collect()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut config = DatagenConfig {
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        ..DatagenConfig::default()
+    };
+    let (mut out, mut binary, mut depth_given) = (None, false, false);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut number = || iter.next().and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| fail(&format!("Missing or invalid value for {}", arg)));
+        match arg.as_str() {
+            "--games" => config.games = number() as usize,
+            "--depth" => {
+                config.depth = number();
+                depth_given = true;
+            }
+            "--nodes" => config.nodes = Some(number()),
+            "--threads" => config.threads = number() as usize,
+            "--seed" => config.seed = number(),
+            "--random-plies" => config.random_plies = number() as usize,
+            "--max-plies" => config.max_plies = number() as usize,
+            "--book" => {
+                let path = iter.next().unwrap_or_else(|| fail("Missing book file"));
+                config.openings = read_book(path);
+            }
+            "--binary" => binary = true,
+            _ if out.is_none() => out = Some(arg.clone()),
+            _ => fail("Unexpected arguments"),
+        }
+    }
+    if config.nodes.is_some() && !depth_given {
+        config.depth = u64::MAX;
+    }
+    let out = out.unwrap_or_else(|| fail("No output file"));
+    let file = File::create(&out).unwrap_or_else(|err| fail(&format!("Can't create {}: {}", out, err)));
+    let mut writer = BufWriter::new(file);
+
+    let mut total = 0;
+    generate(&config, |game, positions| {
+        for position in positions.iter() {
+            let written = if binary {
+                writer.write_all(&position.to_bytes())
+            } else {
+                writeln!(writer, "{}", position.to_text())
+            };
+            written.unwrap_or_else(|err| fail(&format!("Can't write {}: {}", out, err)));
+        }
+        total += positions.len();
+        println!("Game {:>6}/{} {:>4} positions, {} in total", game + 1, config.games, positions.len(), total);
+    });
+    writer.flush().unwrap_or_else(|err| fail(&format!("Can't write {}: {}", out, err)));
+}
@@ -0,0 +1,216 @@
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+use chess::tournament::engine::EngineConfig;
+use chess::tournament::game::{DrawRule, GameSettings, ResignRule, TimeControl};
+use chess::tournament::openings::load_openings;
+use chess::tournament::runner::{half_points, run_match, MatchConfig, MatchScore};
+use chess::tournament::sprt::{Pentanomial, SprtConfig, SprtStatus};
+
+const USAGE: &str = "Usage:
+    match --engine cmd=PROGRAM [name=NAME] [option.NAME=VALUE]...
+          --engine cmd=PROGRAM [name=NAME] [option.NAME=VALUE]...
+          [--games N] [--tc BASE+INC] [--concurrency N] [--openings FILE] [--pgn FILE]
+          [--event NAME] [--resign moves=N score=CP] [--draw movenumber=N moves=N score=CP]
+          [--sprt elo0=E elo1=E alpha=A beta=B]
+
+Plays games between two UCI engines and prints the first engine's wins, losses and draws
+with its Elo difference. Each opening is played twice with the colors swapped. Openings are
+read from a PGN file when the name ends in .pgn, otherwise from EPD lines. The time control
+is in seconds, 10+0.1 by default. --resign ends a game once both engines agree a side is
+ahead by CP centipawns for N moves each, --draw once both give scores within CP of 0 for N
+moves each from move number N on.
+
+--sprt stops the match once a sequential probability ratio test on the game pairs accepts
+H0, that the first engine is elo0 stronger, or H1, that it is elo1 stronger, with alpha and
+beta the chances of wrongly accepting H1 and H0. Defaults are elo0=0 elo1=5 alpha=0.05
+beta=0.05, and the match then runs until the test ends unless --games is given, which must
+then be even so every game belongs to a pair. The final Elo difference comes from the game
+pairs when there are any, otherwise from the single games.";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Takes the `key=value` arguments following an option
+fn key_values<'a>(iter: &mut std::iter::Peekable<std::slice::Iter<'a, String>>) -> Vec<(&'a str, &'a str)> {
+    let mut pairs = Vec::new();
+    while let Some(arg) = iter.next_if(|arg| !arg.starts_with("--")) {
+        let pair = arg.split_once('=').unwrap_or_else(|| fail(&format!("Expected key=value, got {}", arg)));
+        pairs.push(pair);
+    }
+    pairs
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| fail(&format!("Invalid value for {}: {}", key, value)))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut engines = Vec::new();
+    let (mut games, mut concurrency) = (None, 1);
+    let mut settings = GameSettings {
+        time_control: TimeControl { base: 10000, increment: 100 },
+        resign: None,
+        draw: None,
+    };
+    let (mut openings, mut pgn_path, mut event) = (Vec::new(), None, String::from("Engine match"));
+    let mut sprt = None;
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| fail(&format!("Missing value for {}", arg)));
+        match arg.as_str() {
+            "--games" => games = Some(number(arg, value())),
+            "--concurrency" => concurrency = number(arg, value()),
+            "--tc" => {
+                let text = value();
+                settings.time_control = TimeControl::parse(text)
+                    .unwrap_or_else(|| fail(&format!("Invalid time control: {}", text)));
+            }
+            "--openings" => {
+                let path = value();
+                let (loaded, skipped) = load_openings(path)
+                    .unwrap_or_else(|err| fail(&format!("Can't read {}: {}", path, err)));
+                if skipped > 0 {
+                    eprintln!("Skipped {} invalid openings in {}", skipped, path);
+                }
+                openings = loaded;
+            }
+            "--pgn" => pgn_path = Some(value().clone()),
+            "--event" => event = value().clone(),
+            "--engine" => {
+                let mut engine = EngineConfig::default();
+                for (key, value) in key_values(&mut iter) {
+                    match key {
+                        "cmd" => engine.command = value.to_string(),
+                        "name" => engine.name = Some(value.to_string()),
+                        _ => match key.strip_prefix("option.") {
+                            Some(option) => engine.options.push((option.to_string(), value.to_string())),
+                            None => fail(&format!("Unknown engine setting: {}", key)),
+                        },
+                    }
+                }
+                if engine.command.is_empty() {
+                    fail("An engine needs a cmd");
+                }
+                engines.push(engine);
+            }
+            "--resign" => {
+                let mut rule = ResignRule { moves: 3, score: 600 };
+                for (key, value) in key_values(&mut iter) {
+                    match key {
+                        "moves" => rule.moves = number(key, value),
+                        "score" => rule.score = number(key, value),
+                        _ => fail(&format!("Unknown resign setting: {}", key)),
+                    }
+                }
+                settings.resign = Some(rule);
+            }
+            "--draw" => {
+                let mut rule = DrawRule { move_number: 40, moves: 8, score: 10 };
+                for (key, value) in key_values(&mut iter) {
+                    match key {
+                        "movenumber" => rule.move_number = number(key, value),
+                        "moves" => rule.moves = number(key, value),
+                        "score" => rule.score = number(key, value),
+                        _ => fail(&format!("Unknown draw setting: {}", key)),
+                    }
+                }
+                settings.draw = Some(rule);
+            }
+            "--sprt" => {
+                let mut test = SprtConfig::default();
+                for (key, value) in key_values(&mut iter) {
+                    match key {
+                        "elo0" => test.elo0 = number(key, value),
+                        "elo1" => test.elo1 = number(key, value),
+                        "alpha" => test.alpha = number(key, value),
+                        "beta" => test.beta = number(key, value),
+                        _ => fail(&format!("Unknown SPRT setting: {}", key)),
+                    }
+                }
+                let valid_chance = |chance: f64| chance > 0.0 && chance < 1.0;
+                if test.elo1 <= test.elo0 || !valid_chance(test.alpha) || !valid_chance(test.beta) {
+                    fail("SPRT needs elo0 below elo1 and alpha and beta between 0 and 1");
+                }
+                sprt = Some(test);
+            }
+            _ => fail(&format!("Unexpected argument: {}", arg)),
+        }
+    }
+    let engines: [EngineConfig; 2] = engines.try_into().unwrap_or_else(|_| fail("Two engines are needed"));
+
+    let mut pgn = pgn_path.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|err| fail(&format!("Can't create {}: {}", path, err)));
+        BufWriter::new(file)
+    });
+    if sprt.is_some() && games.is_some_and(|games: usize| games % 2 == 1) {
+        fail("--sprt needs an even number of games");
+    }
+    let games = games.unwrap_or(if sprt.is_some() { usize::MAX } else { 10 });
+    let config = MatchConfig { engines, games, concurrency, openings, settings };
+    let mut score = MatchScore::default();
+    let (mut pairs, mut first_of_pair) = (Pentanomial::default(), 0);
+    let mut status = SprtStatus::Continue;
+    let mut names = (String::new(), String::new());
+    let played = run_match(&config, |game, record| {
+        let first_is_white = game % 2 == 0;
+        if first_is_white {
+            names = (record.white.clone(), record.black.clone());
+        }
+        score.add(record.result, first_is_white);
+        let points = half_points(record.result, first_is_white);
+        if first_is_white {
+            first_of_pair = points;
+        } else {
+            pairs.add(first_of_pair, points);
+        }
+        println!("Finished game {} ({} vs {}): {} {{{}}}",
+                 game + 1, record.white, record.black, record.result.pgn(), record.reason);
+        println!("Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+                 names.0, names.1, score.wins, score.losses, score.draws, score.score(), score.games());
+        if let (Some(writer), Some(path)) = (pgn.as_mut(), pgn_path.as_ref()) {
+            writer.write_all(record.to_pgn(&event, game + 1).as_bytes())
+                .and_then(|_| writer.flush())
+                .unwrap_or_else(|err| fail(&format!("Can't write {}: {}", path, err)));
+        }
+        match sprt {
+            Some(test) if !first_is_white => {
+                let (lower, upper) = test.bounds();
+                println!("LLR: {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}]", test.llr(&pairs), lower, upper, test.elo0, test.elo1);
+                status = test.status(&pairs);
+                status == SprtStatus::Continue
+            }
+            _ => true,
+        }
+    });
+    if let Err(err) = played {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+
+    // With game pairs the pentanomial spread gives the tighter error bars
+    let ((elo, error), source) = if pairs.pairs() > 0 {
+        (pairs.elo(), format!("pentanomial, {} pairs", pairs.pairs()))
+    } else {
+        (score.elo(), format!("W/D/L, {} games", score.games()))
+    };
+    println!("\nResults of {} vs {} ({}):", names.0, names.1, config.settings.time_control.pgn());
+    println!("Games: {}, Wins: {}, Losses: {}, Draws: {}, Points: {:.1} ({:.2} %)",
+             score.games(), score.wins, score.losses, score.draws,
+             score.wins as f64 + score.draws as f64 / 2.0, score.score() * 100.0);
+    println!("Ptnml(0-2): {:?}", pairs.counts);
+    println!("Elo difference ({}): {:.1} +/- {:.1}", source, elo, error);
+    if let Some(test) = sprt {
+        let (lower, upper) = test.bounds();
+        println!("LLR: {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}]", test.llr(&pairs), lower, upper, test.elo0, test.elo1);
+        match status {
+            SprtStatus::AcceptH0 => println!("SPRT: H0 was accepted"),
+            SprtStatus::AcceptH1 => println!("SPRT: H1 was accepted"),
+            SprtStatus::Continue => println!("SPRT: no hypothesis was accepted"),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::env;
+use std::process;
+use std::time::Instant;
+use chess::perft::parallel::{parallel_divide, parallel_perft, ParallelOptions};
+use chess::perft::suite::SuitePosition;
+use chess::utils::io::move_to_uci;
+use chess::variants::variant::Variant;
+
+const USAGE: &str = "Usage:
+    perft [options] <depth> [fen]     Divide counts for each root move of the position
+    perft [options] --suite <file>    Check every position of a suite file
+
+Options:
+    --hash <mb>         Reuse counts of transposed positions with tables of this total size
+    --threads <n>       Worker threads (default: every available core)
+    --split <n>         Plies made before the tree is shared out between threads (default: 1)
+    --variant <name>    Variant to play, such as atomic or crazyhouse
+    --chess960          Use Chess960 castling notation
+    --depth <n>         Deepest depth checked in suite mode (default: every depth listed)";
+
+struct Options {
+    parallel: ParallelOptions,
+    variant: Variant,
+    chess960: bool,
+    suite: Option<String>,
+    depth: Option<u8>,
+    fen: Option<String>,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        parallel: ParallelOptions::default(),
+        variant: Variant::Standard,
+        chess960: false,
+        suite: None,
+        depth: None,
+        fen: None,
+    };
+    let mut fen_fields: Vec<String> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("Missing value for {}", name)));
+        match arg.as_str() {
+            "--hash" => options.parallel.hash_mb = value("--hash").parse().unwrap_or_else(|_| fail("Invalid hash size")),
+            "--threads" => {
+                options.parallel.threads = value("--threads").parse().unwrap_or_else(|_| fail("Invalid thread count"))
+            }
+            "--split" => {
+                options.parallel.split_depth = value("--split").parse().unwrap_or_else(|_| fail("Invalid split depth"))
+            }
+            "--variant" => {
+                options.variant = Variant::from_name(&value("--variant")).unwrap_or_else(|| fail("Unknown variant"))
+            }
+            "--chess960" => options.chess960 = true,
+            "--suite" => options.suite = Some(value("--suite")),
+            "--depth" => options.depth = Some(value("--depth").parse().unwrap_or_else(|_| fail("Invalid depth"))),
+            "-h" | "--help" => fail("Count the leaf nodes of the legal move tree"),
+            _ if options.depth.is_none() && options.suite.is_none() => {
+                options.depth = Some(arg.parse().unwrap_or_else(|_| fail("Invalid depth")))
+            }
+            _ => fen_fields.push(arg),
+        }
+    }
+
+    if !fen_fields.is_empty() {
+        options.fen = Some(fen_fields.join(" "));
+    }
+    options
+}
+
+fn nodes_per_second(nodes: u64, start: Instant) -> u64 {
+    let seconds = start.elapsed().as_secs_f64();
+    if seconds > 0.0 { (nodes as f64 / seconds) as u64 } else { nodes }
+}
+
+fn run_divide(options: &Options) {
+    let depth = options.depth.unwrap_or_else(|| fail("Missing depth"));
+    let position = SuitePosition {
+        variant: options.variant,
+        fen: options.fen.clone().unwrap_or_else(|| options.variant.starting_fen().to_string()),
+        counts: vec![],
+    };
+    let board = position.board(options.chess960).unwrap_or_else(|err| fail(&err.to_string()));
+
+    let start = Instant::now();
+    let counts = parallel_divide(&board, depth, options.parallel);
+    for (mov, nodes) in counts.iter() {
+        println!("{}: {}", move_to_uci(&board, *mov), nodes);
+    }
+
+    let nodes: u64 = counts.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("Moves: {}", counts.len());
+    println!("Nodes: {}", nodes);
+    println!("Time: {} ms", start.elapsed().as_millis());
+    println!("NPS: {}", nodes_per_second(nodes, start));
+}
+
+fn run_suite(options: &Options, file_name: &str) -> bool {
+    let contents = std::fs::read_to_string(file_name).unwrap_or_else(|_| fail("Could not read suite file"));
+    let mut failures = 0;
+    let mut total_nodes = 0;
+    let start = Instant::now();
+
+    for (line_number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let position: SuitePosition = match line.parse() {
+            Ok(position) => position,
+            Err(_) => {
+                println!("line {}: could not parse", line_number + 1);
+                failures += 1;
+                continue;
+            }
+        };
+        let board = match position.board(options.chess960) {
+            Ok(board) => board,
+            Err(err) => {
+                println!("line {}: {}: {}", line_number + 1, err, position.fen);
+                failures += 1;
+                continue;
+            }
+        };
+        let max_depth = options.depth.map_or(position.counts.len(), |d| position.counts.len().min(d as usize));
+
+        let mut passed = true;
+        for (depth, expected) in position.counts.iter().enumerate().take(max_depth) {
+            let nodes = parallel_perft(&board, depth as u8 + 1, options.parallel);
+            total_nodes += nodes;
+            if nodes != *expected {
+                println!("line {}: FAIL {} at depth {}: expected {}, found {}",
+                         line_number + 1, position.fen, depth + 1, expected, nodes);
+                passed = false;
+                break;
+            }
+        }
+        if passed {
+            println!("line {}: pass {}", line_number + 1, position.fen);
+        } else {
+            failures += 1;
+        }
+    }
+
+    println!();
+    println!("Failures: {}", failures);
+    println!("Nodes: {}", total_nodes);
+    println!("Time: {} ms", start.elapsed().as_millis());
+    println!("NPS: {}", nodes_per_second(total_nodes, start));
+    failures == 0
+}
+
+fn main() {
+    let options = parse_args();
+    if options.parallel.threads == 0 {
+        fail("Thread count must be at least 1");
+    }
+
+    match &options.suite {
+        Some(file_name) => {
+            if !run_suite(&options, file_name) {
+                process::exit(1);
+            }
+        }
+        None => run_divide(&options),
+    }
+}
@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+use std::process;
+use chess::evaluation::params::{default_params, to_rust_source};
+use chess::evaluation::terms::EvalWeights;
+use chess::tuning::texel::{evaluation_error, fit_k, from_scalars, load_positions, to_scalars, tune};
+
+const USAGE: &str = "Usage:
+    tune <positions> [--epochs N] [--rate R] [--out FILE]
+
+Each line of the positions file is a FEN followed by the game's result for white, as 1-0,
+1/2-1/2 or 0-1, or as 1.0, 0.5 or 0.0. The tuned tables are written as Rust source to FILE,
+or printed when no file is given.";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut path = None;
+    let (mut epochs, mut rate, mut out) = (1000, 1.0, None);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| fail(&format!("Missing value for {}", arg)));
+        match arg.as_str() {
+            "--epochs" => epochs = value().parse().unwrap_or_else(|_| fail("Invalid number of epochs")),
+            "--rate" => rate = value().parse().unwrap_or_else(|_| fail("Invalid learning rate")),
+            "--out" => out = Some(value().clone()),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => fail("Unexpected arguments"),
+        }
+    }
+    let path = path.unwrap_or_else(|| fail("No positions file"));
+    let text = fs::read_to_string(&path).unwrap_or_else(|err| fail(&format!("Can't read {}: {}", path, err)));
+
+    let (positions, skipped) = load_positions(&text);
+    if positions.is_empty() {
+        fail("No positions to tune with");
+    }
+    println!("Loaded {} positions, skipped {}", positions.len(), skipped);
+
+    let mut scalars = to_scalars(&default_params(&EvalWeights::default()));
+    let k = fit_k(&positions, &scalars);
+    println!("K {:.4}, error {:.6}", k, evaluation_error(&positions, &scalars, k));
+
+    tune(&positions, &mut scalars, k, epochs, rate, |epoch, error| {
+        if epoch % 10 == 0 || epoch == epochs {
+            println!("Epoch {:>5} error {:.6}", epoch, error);
+        }
+    });
+
+    let source = to_rust_source(&from_scalars(&scalars));
+    match out {
+        Some(file) => {
+            fs::write(&file, source).unwrap_or_else(|err| fail(&format!("Can't write {}: {}", file, err)));
+            println!("Wrote {}", file);
+        }
+        None => print!("{}", source),
+    }
+}
@@ -39,6 +39,9 @@ pub const LOOP_NONSLIDE_INDEX:[u8; 2] = [0, 3];
 pub const VALUE:[u32; 13] = [0, 100, 325, 325,  550, 1000, 50000, 100, 325, 325, 550, 1000, 50000];
 pub const PIECE_COLOR:[u8; 13] = [BOTH, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE,
     BLACK, BLACK, BLACK, BLACK, BLACK, BLACK];
+pub const PIECE_CHARS:[char; 13] = ['.', 'P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'];
+// The same piece type in the other color
+pub const FLIP_COLOR:[u8; 13] = [EMPTY, BP, BN, BB, BR, BQ, BK, WP, WN, WB, WR, WQ, WK];
 
 pub const PIECE_DIR:[[i32; 8]; 13] = [
     [ 0, 0, 0, 0, 0, 0, 0, 0 ],
@@ -61,6 +64,8 @@ pub const WK_CASTLE:u8 = 1;
 pub const WQ_CASTLE:u8 = 2;
 pub const BK_CASTLE:u8 = 4;
 pub const BQ_CASTLE:u8 = 8;
+// Castle rights in the order used to index per-right castling tables
+pub const CASTLE_RIGHTS:[u8; 4] = [WK_CASTLE, WQ_CASTLE, BK_CASTLE, BQ_CASTLE];
 
 
 
@@ -114,5 +114,10 @@ pub const H6:u8 = 78;
 pub const H7:u8 = 88;
 pub const H8:u8 = 98;
 
+// Destination squares for the king and rook when castling, indexed the same as CASTLE_RIGHTS.
+// These are the same for standard chess and Chess960.
+pub const CASTLE_KING_TO:[u8; 4] = [G1, C1, G8, C8];
+pub const CASTLE_ROOK_TO:[u8; 4] = [F1, D1, F8, D8];
+
 pub const NO_SQ:u8 = 99;
 pub const OFFBOARD:u8 = 100;
@@ -0,0 +1,244 @@
+//! Static evaluation of a position from material, piece square tables, pawn structure and
+//! the weighted mobility, king safety and piece terms. Each of them gives a midgame and an
+//! endgame score, blended by the game phase.
+
+use crate::constants::pieces::{*};
+use crate::evaluation::pawns::evaluate_pawns;
+use crate::evaluation::tapered::{endgame_scale, game_phase, taper, Score};
+use crate::evaluation::terms::term_features;
+use crate::game_board::board::Board;
+
+// Midgame and endgame values of each piece. Kings are left out, since variants can lose or
+// lack them.
+pub(crate) const MATERIAL_MG: [i32; 13] = [0, 100, 325, 325, 550, 1000, 0, 100, 325, 325, 550, 1000, 0];
+pub(crate) const MATERIAL_EG: [i32; 13] = [0, 120, 300, 330, 600, 1050, 0, 120, 300, 330, 600, 1050, 0];
+
+// Piece square tables from white's point of view, indexed by 64 square number (a1 = 0).
+// Black pieces use the square mirrored vertically. The midgame tables come first.
+const PAWN_TABLE: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    10, 10, 0, -10, -10, 0, 10, 10,
+    5, 0, 0, 5, 5, 0, 0, 5,
+    0, 0, 10, 20, 20, 10, 0, 0,
+    5, 5, 5, 10, 10, 5, 5, 5,
+    10, 10, 10, 20, 20, 10, 10, 10,
+    20, 20, 20, 30, 30, 20, 20, 20,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+    0, -10, 0, 0, 0, 0, -10, 0,
+    0, 0, 0, 5, 5, 0, 0, 0,
+    0, 0, 10, 10, 10, 10, 0, 0,
+    0, 0, 10, 20, 20, 10, 5, 0,
+    5, 10, 15, 20, 20, 15, 10, 5,
+    5, 10, 10, 20, 20, 10, 10, 5,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+    0, 0, -10, 0, 0, -10, 0, 0,
+    0, 0, 0, 10, 10, 0, 0, 0,
+    0, 0, 10, 15, 15, 10, 0, 0,
+    0, 10, 15, 20, 20, 15, 10, 0,
+    0, 10, 15, 20, 20, 15, 10, 0,
+    0, 0, 10, 15, 15, 10, 0, 0,
+    0, 0, 0, 10, 10, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const ROOK_TABLE: [i32; 64] = [
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    25, 25, 25, 25, 25, 25, 25, 25,
+    0, 0, 5, 10, 10, 5, 0, 0,
+];
+
+const KING_TABLE: [i32; 64] = [
+    0, 5, 5, -10, -10, 0, 10, 5,
+    -30, -30, -30, -30, -30, -30, -30, -30,
+    -50, -50, -50, -50, -50, -50, -50, -50,
+    -70, -70, -70, -70, -70, -70, -70, -70,
+    -70, -70, -70, -70, -70, -70, -70, -70,
+    -70, -70, -70, -70, -70, -70, -70, -70,
+    -70, -70, -70, -70, -70, -70, -70, -70,
+    -70, -70, -70, -70, -70, -70, -70, -70,
+];
+
+const QUEEN_TABLE: [i32; 64] = [0; 64];
+
+// Pawns want to run and knights and the king to centralise once the pieces come off
+const PAWN_ENDGAME_TABLE: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    5, 5, 5, 5, 5, 5, 5, 5,
+    10, 10, 10, 10, 10, 10, 10, 10,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const KNIGHT_ENDGAME_TABLE: [i32; 64] = [
+    -20, -10, -5, -5, -5, -5, -10, -20,
+    -10, 0, 5, 5, 5, 5, 0, -10,
+    -5, 5, 10, 15, 15, 10, 5, -5,
+    -5, 5, 15, 20, 20, 15, 5, -5,
+    -5, 5, 15, 20, 20, 15, 5, -5,
+    -5, 5, 10, 15, 15, 10, 5, -5,
+    -10, 0, 5, 5, 5, 5, 0, -10,
+    -20, -10, -5, -5, -5, -5, -10, -20,
+];
+
+// Bishops, rooks and queens start out as in the midgame, the tuner can set them apart
+const BISHOP_ENDGAME_TABLE: [i32; 64] = [
+    0, 0, -10, 0, 0, -10, 0, 0,
+    0, 0, 0, 10, 10, 0, 0, 0,
+    0, 0, 10, 15, 15, 10, 0, 0,
+    0, 10, 15, 20, 20, 15, 10, 0,
+    0, 10, 15, 20, 20, 15, 10, 0,
+    0, 0, 10, 15, 15, 10, 0, 0,
+    0, 0, 0, 10, 10, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const ROOK_ENDGAME_TABLE: [i32; 64] = [
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    0, 0, 5, 10, 10, 5, 0, 0,
+    25, 25, 25, 25, 25, 25, 25, 25,
+    0, 0, 5, 10, 10, 5, 0, 0,
+];
+
+const QUEEN_ENDGAME_TABLE: [i32; 64] = [0; 64];
+
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -30, -20, -10, -10, -10, -10, -20, -30,
+    -20, -5, 0, 5, 5, 0, -5, -20,
+    -10, 0, 15, 20, 20, 15, 0, -10,
+    -10, 5, 20, 30, 30, 20, 5, -10,
+    -10, 5, 20, 30, 30, 20, 5, -10,
+    -10, 0, 15, 20, 20, 15, 0, -10,
+    -20, -5, 0, 5, 5, 0, -5, -20,
+    -30, -20, -10, -10, -10, -10, -20, -30,
+];
+
+pub(crate) const PIECE_TABLES: [&[i32; 64]; 6] = [&PAWN_TABLE, &KNIGHT_TABLE, &BISHOP_TABLE, &ROOK_TABLE, &QUEEN_TABLE, &KING_TABLE];
+pub(crate) const PIECE_ENDGAME_TABLES: [&[i32; 64]; 6] = [
+    &PAWN_ENDGAME_TABLE, &KNIGHT_ENDGAME_TABLE, &BISHOP_ENDGAME_TABLE, &ROOK_ENDGAME_TABLE, &QUEEN_ENDGAME_TABLE,
+    &KING_ENDGAME_TABLE,
+];
+
+/// Returns the piece square table bonus for a piece standing on a square
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `pce`: The piece
+/// * `sq`: The 120 square board number the piece stands on
+///
+/// returns: Score The bonus from the point of view of the piece's side
+#[inline(always)]
+fn piece_square_score(pos: &Board, pce: u8, sq: u8) -> Score {
+    let sq64 = pos.sq64(sq) as usize;
+    let (piece, index) = if PIECE_COLOR[pce as usize] == WHITE {
+        ((pce - WP) as usize, sq64)
+    } else {
+        ((pce - BP) as usize, sq64 ^ 56)
+    };
+    Score::new(PIECE_TABLES[piece][index], PIECE_ENDGAME_TABLES[piece][index])
+}
+
+/// Evaluates the position using material, piece placement, pawn structure and the enabled
+/// terms of the board's `eval_weights`, tapered between midgame and endgame by the game
+/// phase. Boards with a neural network set are evaluated by the network instead. Takes the
+/// board mutably to cache pawn structures in its pawn hash table and refresh the network's
+/// accumulator.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: i32 The score in centipawns from the point of view of the side to move
+pub fn evaluate_position(pos: &mut Board) -> i32 {
+    if let Some(mut nnue) = pos.nnue.take() {
+        let score = nnue.evaluate(pos);
+        pos.nnue = Some(nnue);
+        return score;
+    }
+
+    let mut score = material_score(pos, WHITE) - material_score(pos, BLACK);
+    score += placement_score(pos, WHITE) - placement_score(pos, BLACK);
+    score += evaluate_pawns(pos);
+    score += pos.eval_weights.score(&term_features(pos));
+
+    let score = taper(score, game_phase(pos), endgame_scale(pos, score.eg));
+    if pos.side == WHITE { score } else { -score }
+}
+
+/// Counts up the material a side has on the board
+pub(crate) fn material_score(pos: &Board, side: u8) -> Score {
+    let first = if side == WHITE { WP } else { BP };
+    (first..first + 6)
+        .map(|pce| Score::new(MATERIAL_MG[pce as usize], MATERIAL_EG[pce as usize]) * pos.num_pieces[pce as usize] as i32)
+        .sum()
+}
+
+/// Adds up the piece square table bonuses of a side's pieces
+pub(crate) fn placement_score(pos: &Board, side: u8) -> Score {
+    let first = if side == WHITE { WP } else { BP };
+    (first..first + 6)
+        .flat_map(|pce| pos.piece_list[pce as usize][..pos.num_pieces[pce as usize] as usize].iter()
+            .map(move |sq| piece_square_score(pos, pce, *sq)))
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::evaluation::evaluate::evaluate_position;
+    use crate::game_board::board::Board;
+
+    #[test]
+    fn test_start_position_is_even() {
+        let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(evaluate_position(&mut board), 0);
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        let mut white = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut black = Board::from_fen("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4").unwrap();
+        assert_eq!(evaluate_position(&mut white), evaluate_position(&mut black));
+    }
+
+    #[test]
+    fn test_material_advantage() {
+        let mut board = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert!(evaluate_position(&mut board) < -900, "Missing queen was not counted against black");
+    }
+
+    #[test]
+    fn test_king_centralises_in_the_endgame() {
+        // With the queens on the king belongs near its pawns, without them in the center
+        let mut sheltered = Board::from_fen("r1q1k3/ppp5/8/8/8/8/PPP5/1K1Q3r w - - 0 1").unwrap();
+        let mut central = Board::from_fen("r1q1k3/ppp5/8/8/3K4/8/PPP5/3Q3r w - - 0 1").unwrap();
+        assert!(evaluate_position(&mut sheltered) > evaluate_position(&mut central));
+        let mut sheltered = Board::from_fen("4k3/ppp5/8/8/8/8/PPP5/1K6 w - - 0 1").unwrap();
+        let mut central = Board::from_fen("4k3/ppp5/8/8/3K4/8/PPP5/8 w - - 0 1").unwrap();
+        assert!(evaluate_position(&mut sheltered) < evaluate_position(&mut central));
+    }
+
+    #[test]
+    fn test_drawish_ending_is_scaled() {
+        let mut rook_against_bishop = Board::from_fen("4k3/8/8/8/8/8/8/3RK1b1 w - - 0 1").unwrap();
+        assert!(evaluate_position(&mut rook_against_bishop) < 100, "Rook against bishop should be close to a draw");
+    }
+}
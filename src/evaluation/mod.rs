@@ -0,0 +1,7 @@
+pub mod evaluate;
+pub mod pawns;
+pub mod tapered;
+pub mod terms;
+pub mod trace;
+pub mod params;
+pub mod nnue;
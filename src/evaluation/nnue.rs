@@ -0,0 +1,453 @@
+//! Efficiently updatable neural network evaluation. The network has a HalfKA style input
+//! layer: every piece on its square, seen from each side's point of view and bucketed by
+//! where that side's king stands. The input layer's output, the accumulator, is kept up to
+//! date as pieces are added, removed and moved, so evaluating a position only costs the
+//! small output layer. A copy of the accumulator is saved for each move made and put back
+//! when the move is undone.
+//!
+//! Weights are quantised: the input layer is int16 and the output layer int8. Inference is
+//! plain scalar code over fixed length arrays, which the compiler vectorises where it can.
+
+use std::error::Error;
+use std::sync::Arc;
+use std::{fmt, fs};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::constants::pieces::{*};
+use crate::game_board::board::Board;
+
+/// Neurons in the accumulator for each point of view
+pub const HIDDEN: usize = 128;
+/// Number of king buckets
+pub const KING_BUCKETS: usize = 4;
+/// Inputs for one point of view: a king bucket, piece relative to that side and square
+pub const INPUTS: usize = KING_BUCKETS * 12 * 64;
+// Largest activation of a clipped accumulator value
+const QA: i32 = 255;
+// Scale of the int8 output weights
+const QB: i32 = 64;
+// Centipawns of an output of 1.0
+const SCALE: i32 = 400;
+
+const MAGIC: &[u8; 4] = b"CNUE";
+const VERSION: u32 = 1;
+
+// King bucket by the rank of the king, relative to its own side
+const BUCKET_BY_RANK: [u8; 8] = [0, 1, 2, 2, 3, 3, 3, 3];
+
+/// A network that couldn't be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkError {
+    pub reason: String,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid network: {}", self.reason)
+    }
+}
+
+impl Error for NetworkError {}
+
+fn network_error(reason: impl Into<String>) -> NetworkError {
+    NetworkError { reason: reason.into() }
+}
+
+/// The weights of a network. The input weights are stored a feature at a time, so adding
+/// or removing a feature reads one contiguous row.
+#[derive(Clone)]
+pub struct Network {
+    input_weights: Vec<i16>, // INPUTS rows of HIDDEN
+    input_bias: Vec<i16>,
+    output_weights: Vec<i8>, // The side to move's HIDDEN, then the other side's
+    output_bias: i32,
+}
+
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Network {{ inputs: {}, hidden: {} }}", INPUTS, HIDDEN)
+    }
+}
+
+impl Network {
+    /// Makes a network with small random weights, for testing the evaluation's plumbing
+    pub fn random(seed: u64) -> Network {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Network {
+            input_weights: (0..INPUTS * HIDDEN).map(|_| rng.gen_range(-16..=16)).collect(),
+            input_bias: (0..HIDDEN).map(|_| rng.gen_range(0..=64)).collect(),
+            output_weights: (0..2 * HIDDEN).map(|_| rng.gen_range(-64..=64)).collect(),
+            output_bias: rng.gen_range(-QA * QB..=QA * QB),
+        }
+    }
+
+    /// Reads a network from a weight file
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The file, in the format written by `to_bytes`
+    ///
+    /// returns: Result<Network, NetworkError>
+    pub fn load(path: &str) -> Result<Network, NetworkError> {
+        let bytes = fs::read(path).map_err(|err| network_error(format!("can't read {}: {}", path, err)))?;
+        Network::from_bytes(&bytes)
+    }
+
+    /// Reads a network from the weight file format. It starts with the magic bytes `CNUE`
+    /// and the version, input count and hidden size as u32s. Then come the input weights and
+    /// biases as i16s, the output weights as i8s and the output bias as an i32, all little
+    /// endian.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, NetworkError> {
+        let expected = 16 + 2 * (INPUTS * HIDDEN + HIDDEN) + 2 * HIDDEN + 4;
+        if bytes.len() < 16 || &bytes[0..4] != MAGIC {
+            return Err(network_error("not a network file"));
+        }
+        let header = |i: usize| u32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+        if header(1) != VERSION {
+            return Err(network_error(format!("unsupported version {}", header(1))));
+        }
+        if header(2) as usize != INPUTS || header(3) as usize != HIDDEN {
+            return Err(network_error(format!("expected {} inputs and {} hidden neurons, found {} and {}",
+                                             INPUTS, HIDDEN, header(2), header(3))));
+        }
+        if bytes.len() != expected {
+            return Err(network_error(format!("expected {} bytes, found {}", expected, bytes.len())));
+        }
+
+        let read_i16s = |start: usize, count: usize| -> Vec<i16> {
+            bytes[start..start + 2 * count].chunks(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect()
+        };
+        let bias_start = 16 + 2 * INPUTS * HIDDEN;
+        let output_start = bias_start + 2 * HIDDEN;
+        let output_bias_start = output_start + 2 * HIDDEN;
+        Ok(Network {
+            input_weights: read_i16s(16, INPUTS * HIDDEN),
+            input_bias: read_i16s(bias_start, HIDDEN),
+            output_weights: bytes[output_start..output_bias_start].iter().map(|byte| *byte as i8).collect(),
+            output_bias: i32::from_le_bytes(bytes[output_bias_start..].try_into().unwrap()),
+        })
+    }
+
+    /// Writes the network in the weight file format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for value in [VERSION, INPUTS as u32, HIDDEN as u32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.input_weights.iter().chain(self.input_bias.iter()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend(self.output_weights.iter().map(|value| *value as u8));
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    #[inline(always)]
+    fn row(&self, feature: usize) -> &[i16] {
+        &self.input_weights[feature * HIDDEN..(feature + 1) * HIDDEN]
+    }
+}
+
+/// How a side's king places its view of the board: the king bucket, and the squares flipped
+/// so the side plays up the board and its king stands on the queen side
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Orientation {
+    bucket: u8,
+    flip: u8, // Xor of 64 square numbers
+}
+
+impl Orientation {
+    fn new(side: u8, king_sq64: u8) -> Orientation {
+        let vertical = if side == WHITE { 0 } else { 56 };
+        let king = king_sq64 ^ vertical;
+        let horizontal = if king % 8 >= 4 { 7 } else { 0 };
+        Orientation { bucket: BUCKET_BY_RANK[(king / 8) as usize], flip: vertical ^ horizontal }
+    }
+
+    /// Index of the input for a piece on a square from this point of view
+    #[inline(always)]
+    fn feature(self, side: u8, pce: u8, sq64: u8) -> usize {
+        let kind = (pce as usize - 1) % 6;
+        let relative = if PIECE_COLOR[pce as usize] == side { kind } else { 6 + kind };
+        self.bucket as usize * 12 * 64 + relative * 64 + (sq64 ^ self.flip) as usize
+    }
+}
+
+/// The input layer's output for both points of view. A point of view is stale after its
+/// king moves or is put on a square with another orientation, or is taken off the board, and
+/// is refreshed before the next evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN]; 2],
+    orientation: [Orientation; 2],
+    stale: [bool; 2],
+}
+
+impl Accumulator {
+    fn stale() -> Accumulator {
+        Accumulator { values: [[0; HIDDEN]; 2], orientation: [Orientation::new(WHITE, 0); 2], stale: [true; 2] }
+    }
+
+    #[inline(always)]
+    fn add(&mut self, network: &Network, side: usize, feature: usize) {
+        for (value, weight) in self.values[side].iter_mut().zip(network.row(feature)) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    #[inline(always)]
+    fn sub(&mut self, network: &Network, side: usize, feature: usize) {
+        for (value, weight) in self.values[side].iter_mut().zip(network.row(feature)) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+}
+
+/// Finds a side's king square for its point of view, the king's starting square if it has
+/// none, as can happen in antichess
+fn king_square(pos: &Board, side: u8) -> u8 {
+    let king = if side == WHITE { WK } else { BK };
+    (0..64).find(|sq64| pos.pieces[pos.sq120(*sq64) as usize] == king).unwrap_or(if side == WHITE { 4 } else { 60 })
+}
+
+/// A network in use on a board, with its accumulators
+#[derive(Debug, Clone)]
+pub struct Nnue {
+    network: Arc<Network>,
+    current: Accumulator,
+    saved: Vec<Accumulator>, // The accumulator before each move made
+}
+
+impl Nnue {
+    pub fn new(network: Arc<Network>) -> Nnue {
+        Nnue { network, current: Accumulator::stale(), saved: Vec::new() }
+    }
+
+    /// Forgets the accumulators, after the board is set up from scratch
+    pub fn reset(&mut self) {
+        self.current = Accumulator::stale();
+        self.saved.clear();
+    }
+
+    /// Saves the accumulator before a move is made
+    #[inline]
+    pub fn push(&mut self) {
+        self.saved.push(self.current.clone());
+    }
+
+    /// Puts back the accumulator from before the move being undone
+    #[inline]
+    pub fn pop(&mut self) {
+        self.current = self.saved.pop().unwrap_or_else(Accumulator::stale);
+    }
+
+    /// Updates the accumulator for a piece put on a square
+    #[inline]
+    pub fn add_piece(&mut self, pce: u8, sq64: u8) {
+        for side in [WHITE, BLACK] {
+            if piece_is_own_king(pce, side) && Orientation::new(side, sq64) != self.current.orientation[side as usize] {
+                self.current.stale[side as usize] = true;
+            } else if !self.current.stale[side as usize] {
+                let feature = self.current.orientation[side as usize].feature(side, pce, sq64);
+                self.current.add(&self.network, side as usize, feature);
+            }
+        }
+    }
+
+    /// Updates the accumulator for a piece taken off a square
+    #[inline]
+    pub fn clear_piece(&mut self, pce: u8, sq64: u8) {
+        for side in [WHITE, BLACK] {
+            // The orientation then comes from whichever king is left, which isn't known here
+            if piece_is_own_king(pce, side) {
+                self.current.stale[side as usize] = true;
+            } else if !self.current.stale[side as usize] {
+                let feature = self.current.orientation[side as usize].feature(side, pce, sq64);
+                self.current.sub(&self.network, side as usize, feature);
+            }
+        }
+    }
+
+    /// Updates the accumulator for a piece moved between squares
+    #[inline]
+    pub fn move_piece(&mut self, pce: u8, from64: u8, to64: u8) {
+        for side in [WHITE, BLACK] {
+            let accumulator = &mut self.current;
+            let index = side as usize;
+            if piece_is_own_king(pce, side) && Orientation::new(side, to64) != accumulator.orientation[index] {
+                accumulator.stale[index] = true;
+            } else if !accumulator.stale[index] {
+                let orientation = accumulator.orientation[index];
+                accumulator.sub(&self.network, index, orientation.feature(side, pce, from64));
+                accumulator.add(&self.network, index, orientation.feature(side, pce, to64));
+            }
+        }
+    }
+
+    /// Recomputes a point of view of the accumulator from every piece on the board
+    fn refresh(&mut self, pos: &Board, side: u8) {
+        let index = side as usize;
+        let orientation = Orientation::new(side, king_square(pos, side));
+        self.current.orientation[index] = orientation;
+        self.current.stale[index] = false;
+        self.current.values[index].copy_from_slice(&self.network.input_bias);
+        for sq64 in 0..64 {
+            let pce = pos.pieces[pos.sq120(sq64) as usize];
+            if pce != EMPTY {
+                self.current.add(&self.network, index, orientation.feature(side, pce, sq64));
+            }
+        }
+    }
+
+    /// Runs the output layer on the accumulator, refreshing any stale point of view first
+    ///
+    /// # Arguments
+    ///
+    /// * `pos`: The board state the accumulator is for
+    ///
+    /// returns: i32 The score in centipawns from the point of view of the side to move
+    pub fn evaluate(&mut self, pos: &Board) -> i32 {
+        for side in [WHITE, BLACK] {
+            if self.current.stale[side as usize] {
+                self.refresh(pos, side);
+            }
+        }
+
+        let (us, them) = (&self.current.values[pos.side as usize], &self.current.values[pos.side as usize ^ 1]);
+        let (our_weights, their_weights) = self.network.output_weights.split_at(HIDDEN);
+        let activate = |values: &[i16; HIDDEN], weights: &[i8]| -> i32 {
+            values.iter().zip(weights).map(|(value, weight)| (*value as i32).clamp(0, QA) * *weight as i32).sum()
+        };
+        let output = activate(us, our_weights) + activate(them, their_weights) + self.network.output_bias;
+        output * SCALE / (QA * QB)
+    }
+
+    /// The accumulator, for comparing incremental updates to a full refresh
+    pub fn accumulator(&mut self, pos: &Board) -> &Accumulator {
+        for side in [WHITE, BLACK] {
+            if self.current.stale[side as usize] {
+                self.refresh(pos, side);
+            }
+        }
+        &self.current
+    }
+
+    /// Computes the accumulator from scratch for a position
+    pub fn refreshed(&self, pos: &Board) -> Accumulator {
+        let mut fresh = Nnue::new(self.network.clone());
+        fresh.refresh(pos, WHITE);
+        fresh.refresh(pos, BLACK);
+        fresh.current
+    }
+}
+
+#[inline(always)]
+fn piece_is_own_king(pce: u8, side: u8) -> bool {
+    pce == if side == WHITE { WK } else { BK }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::evaluation::evaluate::evaluate_position;
+    use crate::evaluation::nnue::{*};
+    use crate::game_board::board::Board;
+    use crate::moves::gamemove::GameMove;
+    use crate::moves::movegen::generate_all_moves;
+    use crate::variants::variant::Variant;
+
+    fn board(variant: Variant, fen: &str) -> Board {
+        let mut board = Board::from_variant_fen(variant, fen).unwrap();
+        board.set_network(Some(Arc::new(Network::random(1))));
+        board
+    }
+
+    fn assert_matches_refresh(board: &mut Board, context: &str) {
+        let mut nnue = board.nnue.take().unwrap();
+        let fresh = nnue.refreshed(board);
+        assert_eq!(nnue.accumulator(board), &fresh, "Accumulator differs from a refresh {}", context);
+        board.nnue = Some(nnue);
+    }
+
+    /// Plays random games, checking the accumulator after every move and undo
+    fn random_walk(variant: Variant, fen: &str, seed: u64) {
+        let mut board = board(variant, fen);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut made = 0;
+        for _ in 0..60 {
+            let mut move_list: Vec<GameMove> = Vec::new();
+            generate_all_moves(&board, &mut move_list);
+            let legal: Vec<GameMove> = move_list.into_iter().filter(|mov| {
+                let legal = board.make_move(*mov);
+                if legal {
+                    board.undo_move();
+                }
+                legal
+            }).collect();
+            if legal.is_empty() {
+                break;
+            }
+            // Try every move and undo it, then play a random one
+            for mov in legal.iter() {
+                board.make_move(*mov);
+                assert_matches_refresh(&mut board, &format!("after {}", mov));
+                board.undo_move();
+                assert_matches_refresh(&mut board, &format!("after undoing {}", mov));
+            }
+            board.make_move(legal[rng.gen_range(0..legal.len())]);
+            made += 1;
+        }
+        for _ in 0..made {
+            board.undo_move();
+            assert_matches_refresh(&mut board, "taking back the game");
+        }
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        random_walk(Variant::Standard, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 1);
+        random_walk(Variant::Standard, "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 2);
+        random_walk(Variant::Standard, "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3);
+        random_walk(Variant::Atomic, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4);
+        random_walk(Variant::Antichess, "8/1P3P2/2k5/8/8/5K2/1p3p2/8 w - - 0 1", 6);
+        random_walk(Variant::Crazyhouse, "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[Pn] b KQkq - 0 1", 5);
+    }
+
+    #[test]
+    fn test_evaluation_switch() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let mut handcrafted = Board::new();
+        handcrafted.set_fen(fen).unwrap();
+        let mut neural = board(Variant::Standard, fen);
+        let mut nnue = Nnue::new(Arc::new(Network::random(1)));
+        assert_eq!(evaluate_position(&mut neural), nnue.evaluate(&neural));
+
+        neural.set_network(None);
+        assert_eq!(evaluate_position(&mut neural), evaluate_position(&mut handcrafted));
+    }
+
+    #[test]
+    fn test_mirrored_positions() {
+        // A position and its colour flipped mirror score the same for the side to move
+        let mut white = board(Variant::Standard, "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut black = board(Variant::Standard, "rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3");
+        assert_eq!(evaluate_position(&mut white), evaluate_position(&mut black));
+    }
+
+    #[test]
+    fn test_weight_file() {
+        let network = Network::random(7);
+        let bytes = network.to_bytes();
+        let loaded = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        assert!(Network::from_bytes(b"nope").is_err());
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err(), "Truncated file loaded");
+        let mut wrong_size = bytes.clone();
+        wrong_size[12] ^= 1;
+        assert!(Network::from_bytes(&wrong_size).unwrap_err().reason.contains("hidden"));
+        assert!(Network::load("/nonexistent/net.nnue").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Every evaluation parameter as one vector, for tuning. The evaluation is linear in its
+//! parameters before tapering, so a position can be boiled down to how many times each
+//! parameter counts for white minus black, and evaluated for any parameter vector from that.
+
+use crate::constants::pieces::{*};
+use crate::evaluation::evaluate::{MATERIAL_EG, MATERIAL_MG, PIECE_ENDGAME_TABLES, PIECE_TABLES};
+use crate::evaluation::pawns::{free_passer_ranks, pawn_features, BACKWARD_PAWN, CONNECTED_PAWN, DOUBLED_PAWN,
+                               FREE_PASSER_BONUS, ISOLATED_PAWN, PASSED_PAWN_BONUS, PAWN_ISLAND};
+use crate::evaluation::tapered::{taper, Score, MAX_PHASE, SCALE_NORMAL};
+use crate::evaluation::terms::{side_features, EvalWeights, Term};
+use crate::evaluation::trace::evaluate_traced;
+use crate::game_board::board::Board;
+
+// Where each group of parameters starts in the vector
+pub const MATERIAL_START: usize = 0; // Pawn to queen
+pub const PIECE_SQUARE_START: usize = MATERIAL_START + 5; // Pawn to king, 64 squares each from white's side
+pub const PASSED_START: usize = PIECE_SQUARE_START + 6 * 64;
+pub const FREE_PASSER_START: usize = PASSED_START + 8;
+pub const PAWN_TERMS_START: usize = FREE_PASSER_START + 8; // Isolated, doubled, backward, connected, islands
+pub const WEIGHTS_START: usize = PAWN_TERMS_START + 5; // In Term order
+pub const PARAM_COUNT: usize = WEIGHTS_START + Term::COUNT;
+
+const PIECE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+const PAWN_TERM_NAMES: [&str; 5] = ["isolated_pawn", "doubled_pawn", "backward_pawn", "connected_pawn", "pawn_island"];
+
+/// Returns the evaluation's current parameters as one vector
+///
+/// # Arguments
+///
+/// * `weights`: The term weights to include
+///
+/// returns: Vec<Score> PARAM_COUNT midgame and endgame pairs
+pub fn default_params(weights: &EvalWeights) -> Vec<Score> {
+    let mut params = Vec::with_capacity(PARAM_COUNT);
+    params.extend((WP..=WQ).map(|pce| Score::new(MATERIAL_MG[pce as usize], MATERIAL_EG[pce as usize])));
+    for piece in 0..6 {
+        params.extend((0..64).map(|sq| Score::new(PIECE_TABLES[piece][sq], PIECE_ENDGAME_TABLES[piece][sq])));
+    }
+    params.extend(PASSED_PAWN_BONUS);
+    params.extend(FREE_PASSER_BONUS);
+    params.extend([ISOLATED_PAWN, DOUBLED_PAWN, BACKWARD_PAWN, CONNECTED_PAWN, PAWN_ISLAND]);
+    params.extend(weights.terms.iter().map(|term| term.weight));
+    params
+}
+
+/// Names a parameter by its index in the vector
+pub fn param_name(index: usize) -> String {
+    match index {
+        i if i < PIECE_SQUARE_START => format!("{}_value", PIECE_NAMES[i - MATERIAL_START]),
+        i if i < PASSED_START => {
+            let sq = i - PIECE_SQUARE_START;
+            format!("{}_square_{}{}", PIECE_NAMES[sq / 64], (b'a' + (sq % 64 % 8) as u8) as char, sq % 64 / 8 + 1)
+        }
+        i if i < FREE_PASSER_START => format!("passed_pawn_rank_{}", i - PASSED_START + 1),
+        i if i < PAWN_TERMS_START => format!("free_passer_rank_{}", i - FREE_PASSER_START + 1),
+        i if i < WEIGHTS_START => PAWN_TERM_NAMES[i - PAWN_TERMS_START].to_string(),
+        i => Term::ALL[i - WEIGHTS_START].name().to_string(),
+    }
+}
+
+/// A position boiled down for tuning: how often each parameter counts for white minus black,
+/// and the phase and endgame scale that blend the sum
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coefficients {
+    pub entries: Vec<(u16, i16)>, // Parameter index and count, leaving out parameters that cancel
+    pub phase: i32,
+    pub scale: i32,
+}
+
+impl Coefficients {
+    /// Evaluates the position for a parameter vector exactly as `evaluate_position` would
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: PARAM_COUNT midgame and endgame pairs
+    ///
+    /// returns: i32 The score in centipawns from white's point of view
+    pub fn evaluate(&self, params: &[Score]) -> i32 {
+        let score = self.entries.iter().map(|(index, count)| params[*index as usize] * *count as i32).sum();
+        taper(score, self.phase, self.scale)
+    }
+
+    /// How much a midgame and an endgame centipawn count in the blended score
+    pub fn phase_factors(&self) -> (f64, f64) {
+        let mg = self.phase as f64 / MAX_PHASE as f64;
+        (mg, (1.0 - mg) * self.scale as f64 / SCALE_NORMAL as f64)
+    }
+}
+
+/// Counts every parameter of the evaluation for a position. The endgame scale is taken from
+/// the board's current evaluation, as the side it favours can't be known for other
+/// parameters until they are tried.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: Coefficients
+pub fn coefficients(pos: &Board) -> Coefficients {
+    let mut counts = [0i32; PARAM_COUNT];
+
+    for (side, sign, first) in [(WHITE, 1, WP), (BLACK, -1, BP)] {
+        for piece in 0..6 {
+            let pce = first + piece as u8;
+            let num = pos.num_pieces[pce as usize] as usize;
+            if piece < 5 {
+                counts[MATERIAL_START + piece] += sign * num as i32;
+            }
+            for sq in pos.piece_list[pce as usize][..num].iter() {
+                let sq64 = pos.sq64(*sq) as usize;
+                let index = if side == WHITE { sq64 } else { sq64 ^ 56 };
+                counts[PIECE_SQUARE_START + piece * 64 + index] += sign;
+            }
+        }
+
+        let s = side as usize;
+        let (pawns, passed) = pawn_features([pos.bitboards[WHITE_S].board, pos.bitboards[BLACK_S].board], s);
+        for (rank, count) in pawns.passed.iter().enumerate() {
+            counts[PASSED_START + rank] += sign * count;
+        }
+        for (rank, count) in free_passer_ranks(pos, passed, s).iter().enumerate() {
+            counts[FREE_PASSER_START + rank] += sign * count;
+        }
+        for (i, count) in [pawns.isolated, pawns.doubled, pawns.backward, pawns.connected, pawns.islands].iter().enumerate() {
+            counts[PAWN_TERMS_START + i] += sign * count;
+        }
+
+        let features = side_features(pos, side);
+        for term in Term::ALL {
+            if pos.eval_weights[term].enabled {
+                counts[WEIGHTS_START + term as usize] += sign * features[term as usize];
+            }
+        }
+    }
+
+    let trace = evaluate_traced(pos);
+    Coefficients {
+        entries: counts.iter().enumerate()
+            .filter(|(_, count)| **count != 0)
+            .map(|(index, count)| (index as u16, *count as i16))
+            .collect(),
+        phase: trace.phase,
+        scale: trace.scale,
+    }
+}
+
+fn write_table(source: &mut String, declaration: &str, values: &[i32]) {
+    source.push_str(&format!("{} = [\n", declaration));
+    for row in values.chunks(8) {
+        let row: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        source.push_str(&format!("    {},\n", row.join(", ")));
+    }
+    source.push_str("];\n\n");
+}
+
+fn score_list(scores: &[Score]) -> String {
+    scores.iter().map(|score| format!("Score::new({}, {})", score.mg, score.eg)).collect::<Vec<String>>().join(", ")
+}
+
+/// Writes a parameter vector out as the Rust constants it came from, to paste over the
+/// ones in the evaluation modules
+///
+/// # Arguments
+///
+/// * `params`: PARAM_COUNT midgame and endgame pairs
+///
+/// returns: String
+pub fn to_rust_source(params: &[Score]) -> String {
+    let mut source = String::from("// evaluate.rs\n");
+    let material = &params[MATERIAL_START..PIECE_SQUARE_START];
+    for (name, part) in [("MATERIAL_MG", 0), ("MATERIAL_EG", 1)] {
+        let values: Vec<String> = material.iter().map(|score| if part == 0 { score.mg } else { score.eg }.to_string()).collect();
+        source.push_str(&format!("pub(crate) const {}: [i32; 13] = [0, {}, 0, {}, 0];\n", name, values.join(", "), values.join(", ")));
+    }
+    source.push('\n');
+    for (piece, name) in PIECE_NAMES.iter().enumerate() {
+        let table = &params[PIECE_SQUARE_START + piece * 64..PIECE_SQUARE_START + (piece + 1) * 64];
+        let upper = name.to_uppercase();
+        write_table(&mut source, &format!("const {}_TABLE: [i32; 64]", upper), &table.iter().map(|score| score.mg).collect::<Vec<i32>>());
+        write_table(&mut source, &format!("const {}_ENDGAME_TABLE: [i32; 64]", upper), &table.iter().map(|score| score.eg).collect::<Vec<i32>>());
+    }
+
+    source.push_str("// pawns.rs\n");
+    source.push_str(&format!("pub const PASSED_PAWN_BONUS: [Score; 8] = [{}];\n", score_list(&params[PASSED_START..FREE_PASSER_START])));
+    source.push_str(&format!("pub const FREE_PASSER_BONUS: [Score; 8] = [{}];\n", score_list(&params[FREE_PASSER_START..PAWN_TERMS_START])));
+    for (i, name) in PAWN_TERM_NAMES.iter().enumerate() {
+        source.push_str(&format!("pub const {}: Score = {};\n", name.to_uppercase(), score_list(&params[PAWN_TERMS_START + i..=PAWN_TERMS_START + i])));
+    }
+
+    source.push_str("\n// terms.rs\n");
+    source.push_str(&format!("const DEFAULT_WEIGHTS: [Score; Term::COUNT] = [{}];\n", score_list(&params[WEIGHTS_START..PARAM_COUNT])));
+    source
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::pieces::WHITE;
+    use crate::evaluation::evaluate::evaluate_position;
+    use crate::evaluation::params::{*};
+    use crate::evaluation::terms::{EvalWeights, Term};
+    use crate::game_board::board::Board;
+
+    #[test]
+    fn test_coefficients_match_evaluation() {
+        let params = default_params(&EvalWeights::default());
+        assert_eq!(params.len(), PARAM_COUNT);
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            "4k3/8/8/8/8/8/8/3RK1b1 w - - 0 1",
+            "6k1/5ppp/8/3Q2N1/8/8/8/4K3 b - - 0 1",
+        ] {
+            let mut pos = Board::new();
+            pos.set_fen(fen).unwrap();
+            let white_score = coefficients(&pos).evaluate(&params);
+            let expected = evaluate_position(&mut pos);
+            assert_eq!(if pos.side == WHITE { white_score } else { -white_score }, expected, "Differs for {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_param_names() {
+        assert_eq!(param_name(MATERIAL_START + 1), "knight_value");
+        assert_eq!(param_name(PIECE_SQUARE_START + 5 * 64 + 6), "king_square_g1");
+        assert_eq!(param_name(PASSED_START + 6), "passed_pawn_rank_7");
+        assert_eq!(param_name(PAWN_TERMS_START + 4), "pawn_island");
+        assert_eq!(param_name(WEIGHTS_START + Term::BishopPair as usize), "bishop_pair");
+    }
+
+    #[test]
+    fn test_rust_source() {
+        let source = to_rust_source(&default_params(&EvalWeights::default()));
+        assert!(source.contains("pub(crate) const MATERIAL_MG: [i32; 13] = [0, 100, 325, 325, 550, 1000, 0, 100, 325, 325, 550, 1000, 0];"));
+        assert!(source.contains("const KING_ENDGAME_TABLE: [i32; 64] = [\n    -30, -20, -10, -10, -10, -10, -20, -30,\n"));
+        assert!(source.contains("pub const ISOLATED_PAWN: Score = Score::new(-10, -15);"));
+        assert!(source.contains("const DEFAULT_WEIGHTS: [Score; Term::COUNT] = [Score::new(4, 4), "));
+
+        // Every constant written out is declared the same way in its module, and every piece
+        // square table in evaluate.rs is written out
+        let modules = [
+            ("// evaluate.rs", include_str!("evaluate.rs")),
+            ("// pawns.rs", include_str!("pawns.rs")),
+            ("// terms.rs", include_str!("terms.rs")),
+        ];
+        let mut module = "";
+        for line in source.lines() {
+            if let Some((_, text)) = modules.iter().find(|(header, _)| line == *header) {
+                module = text;
+            } else if let Some((declaration, _)) = line.split_once(" = ") {
+                assert!(module.lines().any(|line| line.starts_with(&format!("{} = ", declaration))),
+                        "{} is not declared in its module", declaration);
+            }
+        }
+        for line in include_str!("evaluate.rs").lines().filter(|line| line.contains("_TABLE: [i32; 64] = ")) {
+            let declaration = line.split_once(" = ").unwrap().0;
+            assert!(source.contains(&format!("{} = ", declaration)), "{} is not written out", declaration);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Pawn structure evaluation: passed, isolated, doubled, backward and connected pawns and pawn
+//! islands. The structure only depends on where the pawns stand, so its score is cached in a
+//! hash table keyed by the board's pawn key. Whether a passed pawn's path is free depends on
+//! the other pieces too, so that bonus is added on top of the cached score.
+
+use crate::constants::pieces::{*};
+use crate::constants::squares::OFFBOARD;
+use crate::evaluation::tapered::Score;
+use crate::game_board::board::Board;
+
+const PAWN_TABLE_ENTRIES: usize = 16384;
+
+// Bonuses by rank counted from the pawn's own side, so index 6 is one step from promoting
+pub const PASSED_PAWN_BONUS: [Score; 8] = [
+    Score::new(0, 0), Score::new(5, 10), Score::new(10, 15), Score::new(15, 25),
+    Score::new(25, 45), Score::new(40, 75), Score::new(70, 120), Score::new(0, 0),
+];
+// Nothing stands on the way to promotion
+pub const FREE_PASSER_BONUS: [Score; 8] = [
+    Score::new(0, 0), Score::new(0, 0), Score::new(2, 5), Score::new(5, 10),
+    Score::new(10, 20), Score::new(15, 35), Score::new(25, 60), Score::new(0, 0),
+];
+pub const ISOLATED_PAWN: Score = Score::new(-10, -15);
+pub const DOUBLED_PAWN: Score = Score::new(-10, -20); // For each pawn on a file after the first
+pub const BACKWARD_PAWN: Score = Score::new(-8, -10);
+pub const CONNECTED_PAWN: Score = Score::new(5, 8); // Defended by or side by side with a pawn of its side
+pub const PAWN_ISLAND: Score = Score::new(-5, -10); // For each group of pawns on neighbouring files after the first
+
+const FILE_A_MASK: u64 = 0x0101_0101_0101_0101;
+const FILE_H_MASK: u64 = FILE_A_MASK << 7;
+
+const fn file_mask(file: i32) -> u64 {
+    if file < 0 || file > 7 { 0 } else { FILE_A_MASK << file }
+}
+
+// Ranks above and below a rank, whole board width
+const fn ranks_above(rank: usize) -> u64 {
+    if rank >= 7 { 0 } else { u64::MAX << ((rank + 1) * 8) }
+}
+
+const fn ranks_below(rank: usize) -> u64 {
+    if rank == 0 { 0 } else { u64::MAX >> ((8 - rank) * 8) }
+}
+
+const fn front_masks(adjacent: bool) -> [[u64; 64]; 2] {
+    let mut masks = [[0; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let file = (sq % 8) as i32;
+        let files = if adjacent { file_mask(file - 1) | file_mask(file) | file_mask(file + 1) } else { file_mask(file) };
+        masks[WHITE_S][sq] = files & ranks_above(sq / 8);
+        masks[BLACK_S][sq] = files & ranks_below(sq / 8);
+        sq += 1;
+    }
+    masks
+}
+
+// Squares in front of a pawn where enemy pawns stop it being passed, by side and 64 square number
+const PASSED_MASKS: [[u64; 64]; 2] = front_masks(true);
+// Squares in front of a pawn on its own file
+const FRONT_SPANS: [[u64; 64]; 2] = front_masks(false);
+
+/// The cached evaluation of a pawn structure
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PawnEntry {
+    pub key: u64,
+    pub score: Score, // From white's point of view
+    pub passed: [u64; 2], // Passed pawns of each side, by 64 square number
+}
+
+#[derive(Debug, Clone)]
+pub struct PawnTable {
+    pub entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    pub(crate) fn new() -> PawnTable {
+        // A key of 0 is also the key of a board without pawns, whose entry is all zero anyway
+        PawnTable { entries: vec![PawnEntry { key: 0, score: Score::ZERO, passed: [0; 2] }; PAWN_TABLE_ENTRIES] }
+    }
+
+    #[inline]
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = entry.key as usize % self.entries.len();
+        self.entries[index] = entry;
+    }
+
+    #[inline]
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        let entry = self.entries[key as usize % self.entries.len()];
+        if entry.key == key { Some(entry) } else { None }
+    }
+}
+
+/// Squares attacked by a side's pawns
+#[inline(always)]
+pub(crate) fn pawn_attacks(pawns: u64, side: usize) -> u64 {
+    if side == WHITE_S {
+        ((pawns << 7) & !FILE_H_MASK) | ((pawns << 9) & !FILE_A_MASK)
+    } else {
+        ((pawns >> 9) & !FILE_H_MASK) | ((pawns >> 7) & !FILE_A_MASK)
+    }
+}
+
+/// Squares on the files next to a 64 square number and in front of it from a side's point of
+/// view, where enemy pawns could still come to attack a piece of that side standing there
+#[inline(always)]
+pub(crate) fn attack_span(side: usize, sq64: u8) -> u64 {
+    PASSED_MASKS[side][sq64 as usize] & !FRONT_SPANS[side][sq64 as usize]
+}
+
+/// Counts of each pawn structure feature of a side, which the pawn constants weigh
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PawnFeatures {
+    pub passed: [i32; 8], // By rank counted from the side's own side
+    pub isolated: i32,
+    pub doubled: i32,
+    pub backward: i32,
+    pub connected: i32,
+    pub islands: i32, // Beyond the first
+}
+
+impl PawnFeatures {
+    pub fn score(&self) -> Score {
+        let passed: Score = self.passed.iter().zip(PASSED_PAWN_BONUS.iter()).map(|(count, bonus)| *bonus * *count).sum();
+        passed + ISOLATED_PAWN * self.isolated + DOUBLED_PAWN * self.doubled + BACKWARD_PAWN * self.backward
+            + CONNECTED_PAWN * self.connected + PAWN_ISLAND * self.islands
+    }
+}
+
+/// Counts one side's pawn structure features and finds its passed pawns
+///
+/// # Arguments
+///
+/// * `pawns`: The side's pawns as white and black 64 square bitboards
+/// * `side`: The side to count for
+///
+/// returns: (PawnFeatures, u64) The side's features and its passed pawns
+pub(crate) fn pawn_features(pawns: [u64; 2], side: usize) -> (PawnFeatures, u64) {
+    let own = pawns[side];
+    let enemy = pawns[side ^ 1];
+    let own_attacks = pawn_attacks(own, side);
+    let enemy_attacks = pawn_attacks(enemy, side ^ 1);
+    let mut features = PawnFeatures::default();
+    let mut passed = 0;
+
+    let mut files = 0u8;
+    for file in 0..8 {
+        let count = (own & file_mask(file)).count_ones() as i32;
+        if count > 0 {
+            files |= 1 << file;
+            features.doubled += count - 1;
+        }
+    }
+    let islands = (files & !(files << 1)).count_ones() as i32;
+    features.islands = (islands - 1).max(0);
+
+    let mut remaining = own;
+    while remaining != 0 {
+        let sq = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+        let file = (sq % 8) as i32;
+        let rank = sq / 8;
+        let relative_rank = if side == WHITE_S { rank } else { 7 - rank };
+        let adjacent = file_mask(file - 1) | file_mask(file + 1);
+
+        if enemy & PASSED_MASKS[side][sq] == 0 && own & FRONT_SPANS[side][sq] == 0 {
+            passed |= 1 << sq;
+            features.passed[relative_rank] += 1;
+        }
+
+        let rank_mask = 0xFFu64 << (rank * 8);
+        if own_attacks & (1 << sq) != 0 || own & adjacent & rank_mask != 0 {
+            features.connected += 1;
+        }
+
+        if own & adjacent == 0 {
+            features.isolated += 1;
+        } else if relative_rank < 7 {
+            // No pawn beside or behind it can come up to defend it, and it can't safely step up
+            let behind = if side == WHITE_S { ranks_below(rank + 1) } else { ranks_above(rank - 1) };
+            let stop = if side == WHITE_S { sq + 8 } else { sq - 8 };
+            if own & adjacent & behind == 0 && enemy_attacks & (1 << stop) != 0 {
+                features.backward += 1;
+            }
+        }
+    }
+    (features, passed)
+}
+
+/// Scores one side's pawn structure and finds its passed pawns
+///
+/// # Arguments
+///
+/// * `pawns`: The side's pawns as white and black 64 square bitboards
+/// * `side`: The side to score
+///
+/// returns: (Score, u64) The score from the side's point of view and its passed pawns
+pub(crate) fn side_structure(pawns: [u64; 2], side: usize) -> (Score, u64) {
+    let (features, passed) = pawn_features(pawns, side);
+    (features.score(), passed)
+}
+
+/// Evaluates the pawn structure given by the pawns of both sides
+///
+/// # Arguments
+///
+/// * `key`: The pawn key to store the evaluation under
+/// * `pawns`: White's and black's pawns as 64 square bitboards
+///
+/// returns: PawnEntry
+pub fn pawn_structure(key: u64, pawns: [u64; 2]) -> PawnEntry {
+    let (white_score, white_passed) = side_structure(pawns, WHITE_S);
+    let (black_score, black_passed) = side_structure(pawns, BLACK_S);
+    PawnEntry { key, score: white_score - black_score, passed: [white_passed, black_passed] }
+}
+
+/// Evaluates the position's pawn structure, using the pawn hash table when the structure has
+/// been seen before, and adds the bonus for passed pawns with a free path to promotion
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: Score The score in centipawns from white's point of view
+pub fn evaluate_pawns(pos: &mut Board) -> Score {
+    let entry = match pos.pawn_table.probe(pos.pawn_key) {
+        Some(entry) => entry,
+        None => {
+            let entry = pawn_structure(pos.pawn_key, [pos.bitboards[WHITE_S].board, pos.bitboards[BLACK_S].board]);
+            pos.pawn_table.store(entry);
+            entry
+        }
+    };
+
+    entry.score + free_passers(pos, entry.passed[WHITE_S], WHITE_S) - free_passers(pos, entry.passed[BLACK_S], BLACK_S)
+}
+
+/// Counts a side's passed pawns with nothing in front of them
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `passed`: The side's passed pawns by 64 square number
+/// * `side`: The side the pawns belong to
+///
+/// returns: [i32; 8] The number of free passed pawns by rank counted from the side's own side
+pub(crate) fn free_passer_ranks(pos: &Board, mut passed: u64, side: usize) -> [i32; 8] {
+    let step = if side == WHITE_S { 10 } else { -10 };
+    let mut ranks = [0; 8];
+    while passed != 0 {
+        let sq64 = passed.trailing_zeros() as u8;
+        passed &= passed - 1;
+        let mut sq = pos.sq120(sq64) as i32 + step;
+        while pos.pieces[sq as usize] == EMPTY {
+            sq += step;
+        }
+        if pos.pieces[sq as usize] == OFFBOARD {
+            let relative_rank = if side == WHITE_S { sq64 / 8 } else { 7 - sq64 / 8 };
+            ranks[relative_rank as usize] += 1;
+        }
+    }
+    ranks
+}
+
+/// Adds up the bonus for a side's passed pawns with nothing in front of them
+pub(crate) fn free_passers(pos: &Board, passed: u64, side: usize) -> Score {
+    free_passer_ranks(pos, passed, side).iter().zip(FREE_PASSER_BONUS.iter()).map(|(count, bonus)| *bonus * *count).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::evaluation::pawns::{*};
+
+    fn structure(fen: &str) -> PawnEntry {
+        let pos = Board::from_fen(fen).unwrap();
+        pawn_structure(pos.pawn_key, [pos.bitboards[WHITE_S].board, pos.bitboards[BLACK_S].board])
+    }
+
+    #[test]
+    fn test_symmetric_structure_is_even() {
+        let entry = structure("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(entry.score, Score::ZERO);
+        assert_eq!(entry.passed, [0, 0]);
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        // The a pawn is passed, the d and e pawns stop each other
+        let entry = structure("4k3/4p3/8/P2P4/8/8/8/4K3 w - - 0 1");
+        assert_eq!(entry.passed, [1 << 32, 0]);
+        assert_eq!(entry.score, PASSED_PAWN_BONUS[4] + ISOLATED_PAWN * 2 + PAWN_ISLAND - ISOLATED_PAWN);
+    }
+
+    #[test]
+    fn test_doubled_isolated_and_islands() {
+        let entry = structure("4k3/8/8/8/8/2P5/2P4P/4K3 w - - 0 1");
+        assert_eq!(entry.score, DOUBLED_PAWN + ISOLATED_PAWN * 3 + PAWN_ISLAND
+            + PASSED_PAWN_BONUS[2] + PASSED_PAWN_BONUS[1]);
+    }
+
+    #[test]
+    fn test_connected_pawns() {
+        // d4 defends e5, and d4 and e4 stand side by side
+        let (defended, _) = side_structure([1 << 27 | 1 << 36, 0], WHITE_S);
+        assert_eq!(defended, CONNECTED_PAWN + PASSED_PAWN_BONUS[3] + PASSED_PAWN_BONUS[4]);
+        let (phalanx, _) = side_structure([1 << 27 | 1 << 28, 0], WHITE_S);
+        assert_eq!(phalanx, CONNECTED_PAWN * 2 + PASSED_PAWN_BONUS[3] * 2);
+    }
+
+    #[test]
+    fn test_backward_pawn() {
+        // b2 can't be defended and the a4 pawn covers b3, c3 is defended by b2
+        let (score, passed) = side_structure([1 << 9 | 1 << 18, 1 << 24], WHITE_S);
+        assert_eq!(score, BACKWARD_PAWN + CONNECTED_PAWN + PASSED_PAWN_BONUS[2]);
+        assert_eq!(passed, 1 << 18);
+        let (black, _) = side_structure([1 << 9 | 1 << 18, 1 << 24], BLACK_S);
+        assert_eq!(black, ISOLATED_PAWN, "a4 is isolated, not backward");
+    }
+
+    #[test]
+    fn test_free_passer_and_table() {
+        let mut free = Board::from_fen("4k3/8/8/P7/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut blocked = Board::from_fen("n3k3/8/8/P7/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate_pawns(&mut free) - evaluate_pawns(&mut blocked), FREE_PASSER_BONUS[4]);
+        assert_eq!(free.pawn_table.probe(free.pawn_key).map(|entry| entry.passed[WHITE_S]), Some(1 << 32));
+        assert_eq!(evaluate_pawns(&mut free), evaluate_pawns(&mut free.clone()), "Cached score differs");
+    }
+}
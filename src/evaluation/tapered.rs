@@ -0,0 +1,197 @@
+//! Tapered evaluation. Every term scores the midgame and the endgame separately, and the
+//! final score blends the two by how much non-pawn material is left on the board. Endings
+//! known to be drawish have their endgame score scaled down toward zero.
+
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use crate::constants::pieces::{*};
+use crate::constants::squares::{FILE_SQUARES, RANK_SQUARES};
+use crate::game_board::board::Board;
+use crate::variants::variant::Variant;
+
+/// Phase of a board with all the starting pieces, or more
+pub const MAX_PHASE: i32 = 24;
+/// Scale of an endgame score that isn't drawish
+pub const SCALE_NORMAL: i32 = 64;
+
+// Indexed by piece, kings and pawns don't count toward the phase
+const PHASE_WEIGHT: [i32; 13] = [0, 0, 1, 1, 2, 4, 0, 0, 1, 1, 2, 4, 0];
+
+/// A pair of midgame and endgame values for an evaluation term
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const ZERO: Score = Score { mg: 0, eg: 0 };
+
+    pub const fn new(mg: i32, eg: i32) -> Score {
+        Score { mg, eg }
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+impl Sum for Score {
+    fn sum<I: Iterator<Item = Score>>(iter: I) -> Score {
+        iter.fold(Score::ZERO, |total, score| total + score)
+    }
+}
+
+/// Measures how far the game is from the endgame by the knights, bishops, rooks and queens
+/// left on the board
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: i32 From 0 with only kings and pawns left up to MAX_PHASE
+pub fn game_phase(pos: &Board) -> i32 {
+    let minors = (pos.num_minor_pieces[WHITE_S] + pos.num_minor_pieces[BLACK_S]) as i32;
+    let majors: i32 = [WR, WQ, BR, BQ].iter()
+        .map(|pce| PHASE_WEIGHT[*pce as usize] * pos.num_pieces[*pce as usize] as i32)
+        .sum();
+    (minors * PHASE_WEIGHT[WN as usize] + majors).min(MAX_PHASE)
+}
+
+/// Knights, bishops, rooks and queens of a side, in centipawns
+fn piece_material(pos: &Board, side: u8) -> u32 {
+    let first = if side == WHITE { WN } else { BN };
+    (first..first + 4).map(|pce| VALUE[pce as usize] * pos.num_pieces[pce as usize] as u32).sum()
+}
+
+/// Finds how much of the endgame score the side ahead can hope to turn into a win. Endings
+/// with a lone minor piece, no more than a minor piece up without pawns, or bishops of
+/// opposite colors are scaled toward a draw. Only standard chess is scaled, as other
+/// variants are won in other ways.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `eg`: The endgame score from white's point of view, saying which side is ahead
+///
+/// returns: i32 The scale out of SCALE_NORMAL
+pub fn endgame_scale(pos: &Board, eg: i32) -> i32 {
+    if pos.variant != Variant::Standard {
+        return SCALE_NORMAL;
+    }
+    let strong = if eg > 0 { WHITE } else { BLACK };
+    let weak = strong ^ 1;
+    let strong_pawn = if strong == WHITE { WP } else { BP };
+
+    if pos.num_pieces[strong_pawn as usize] == 0 {
+        let strong_material = piece_material(pos, strong);
+        if strong_material <= VALUE[WB as usize] {
+            return 0;
+        }
+        if strong_material <= piece_material(pos, weak) + VALUE[WB as usize] {
+            return 16;
+        }
+    }
+
+    if pos.num_pieces[WB as usize] == 1 && pos.num_pieces[BB as usize] == 1 {
+        let color = |sq: u8| (FILE_SQUARES[sq as usize] + RANK_SQUARES[sq as usize]) % 2;
+        if color(pos.piece_list[WB as usize][0]) != color(pos.piece_list[BB as usize][0]) {
+            let others = [WN, WR, WQ, BN, BR, BQ].iter().any(|pce| pos.num_pieces[*pce as usize] > 0);
+            return if others { 48 } else { 32 };
+        }
+    }
+    SCALE_NORMAL
+}
+
+/// Blends the midgame and endgame scores by the game phase
+///
+/// # Arguments
+///
+/// * `score`: The midgame and endgame scores
+/// * `phase`: The game phase, MAX_PHASE for a full midgame score
+/// * `scale`: The scale out of SCALE_NORMAL to apply to the endgame score
+///
+/// returns: i32 The score in centipawns
+pub fn taper(score: Score, phase: i32, scale: i32) -> i32 {
+    let eg = score.eg * scale / SCALE_NORMAL;
+    (score.mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+#[cfg(test)]
+mod test {
+    use crate::evaluation::tapered::{*};
+    use crate::game_board::board::Board;
+    use crate::variants::variant::Variant;
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(game_phase(&Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()), MAX_PHASE);
+        assert_eq!(game_phase(&Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap()), 0);
+        assert_eq!(game_phase(&Board::from_fen("r3k3/8/8/8/8/8/8/2B1K2Q w - - 0 1").unwrap()), 2 + 1 + 4);
+    }
+
+    #[test]
+    fn test_taper() {
+        let score = Score::new(100, -20);
+        assert_eq!(taper(score, MAX_PHASE, SCALE_NORMAL), 100);
+        assert_eq!(taper(score, 0, SCALE_NORMAL), -20);
+        assert_eq!(taper(score, MAX_PHASE / 2, SCALE_NORMAL), 40);
+        assert_eq!(taper(score, 0, SCALE_NORMAL / 2), -10);
+    }
+
+    #[test]
+    fn test_drawish_endings() {
+        assert_eq!(endgame_scale(&Board::from_fen("4k3/8/8/8/8/8/8/3RK1b1 w - - 0 1").unwrap(), 200), 16, "Rook against bishop");
+        assert_eq!(endgame_scale(&Board::from_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap(), 300), 0, "Lone knight");
+        assert_eq!(endgame_scale(&Board::from_fen("4k3/p7/8/8/8/8/8/3NK3 b - - 0 1").unwrap(), -50), SCALE_NORMAL, "Black has a pawn");
+        assert_eq!(endgame_scale(&Board::from_fen("4k3/8/8/8/8/8/8/3RK3 w - - 0 1").unwrap(), 500), SCALE_NORMAL, "Rook wins alone");
+        assert_eq!(endgame_scale(&Board::from_fen("2b1k3/p7/8/8/8/8/PP6/2B1K3 w - - 0 1").unwrap(), 100), 32, "Opposite colored bishops");
+        assert_eq!(endgame_scale(&Board::from_fen("2b1k3/p7/8/8/8/8/PP6/2BRK3 w - - 0 1").unwrap(), 100), 48, "With rooks left");
+        assert_eq!(endgame_scale(&Board::from_fen("4kb2/p7/8/8/8/8/PP6/2B1K3 w - - 0 1").unwrap(), 100), SCALE_NORMAL, "Same colored bishops");
+
+        let mut koth = Board::new();
+        koth.variant = Variant::KingOfTheHill;
+        koth.set_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap();
+        assert_eq!(endgame_scale(&koth, 300), SCALE_NORMAL, "Only standard chess is scaled");
+    }
+}
@@ -0,0 +1,334 @@
+//! Evaluation terms on top of material, piece square tables and pawn structure: mobility,
+//! king safety and piece specific bonuses. Each term counts a feature of the position, and
+//! the evaluation adds the count times the term's weight. Terms can be reweighted or switched
+//! off one at a time through the board's `eval_weights`, to measure what each one is worth.
+
+use std::ops::{Index, IndexMut};
+use crate::constants::pieces::{*};
+use crate::constants::squares::{FILE_SQUARES, RANK_SQUARES};
+use crate::evaluation::pawns::{attack_span, pawn_attacks};
+use crate::evaluation::tapered::Score;
+use crate::game_board::board::Board;
+use crate::moves::validate::is_sq_on_board;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Term {
+    KnightMobility, // Squares a knight can safely go to, over 4
+    BishopMobility, // Over 6
+    RookMobility,   // Over 6
+    QueenMobility,  // Over 12
+    KingShelter,    // For each file around the king: 1 for a shelter pawn pushed once, 2 for none
+    PawnStorm,      // Enemy pawns coming up the king's files, 3 for a pawn in front of it down to 1
+    KingAttack,     // Attack units of the pieces hitting the enemy king's squares, with two or more
+    RookOpenFile,
+    RookSemiOpenFile,
+    RookOnSeventh,  // With enemy pawns on the seventh rank or the enemy king on the eighth
+    BishopPair,
+    KnightOutpost,  // On the enemy half, defended by a pawn and out of reach of enemy pawns
+    TrappedPiece,   // On the enemy half without a safe square to go to
+}
+
+impl Term {
+    pub const COUNT: usize = 13;
+    pub const ALL: [Term; Term::COUNT] = [
+        Term::KnightMobility, Term::BishopMobility, Term::RookMobility, Term::QueenMobility,
+        Term::KingShelter, Term::PawnStorm, Term::KingAttack, Term::RookOpenFile, Term::RookSemiOpenFile,
+        Term::RookOnSeventh, Term::BishopPair, Term::KnightOutpost, Term::TrappedPiece,
+    ];
+
+    /// Returns the name the term goes by in options and output
+    pub fn name(self) -> &'static str {
+        match self {
+            Term::KnightMobility => "knight_mobility",
+            Term::BishopMobility => "bishop_mobility",
+            Term::RookMobility => "rook_mobility",
+            Term::QueenMobility => "queen_mobility",
+            Term::KingShelter => "king_shelter",
+            Term::PawnStorm => "pawn_storm",
+            Term::KingAttack => "king_attack",
+            Term::RookOpenFile => "rook_open_file",
+            Term::RookSemiOpenFile => "rook_semi_open_file",
+            Term::RookOnSeventh => "rook_on_seventh",
+            Term::BishopPair => "bishop_pair",
+            Term::KnightOutpost => "knight_outpost",
+            Term::TrappedPiece => "trapped_piece",
+        }
+    }
+
+    /// Looks up a term by its name
+    pub fn from_name(name: &str) -> Option<Term> {
+        Term::ALL.iter().copied().find(|term| term.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EvalTerm {
+    pub enabled: bool,
+    pub weight: Score, // Midgame and endgame centipawns for each unit the term counts
+}
+
+/// The weight of every term, indexed by `Term`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EvalWeights {
+    pub terms: [EvalTerm; Term::COUNT],
+}
+
+// In Term order. King safety matters less as the pieces come off, and mobility, rooks on the
+// seventh and the bishop pair matter more.
+const DEFAULT_WEIGHTS: [Score; Term::COUNT] = [
+    Score::new(4, 4), Score::new(3, 4), Score::new(2, 4), Score::new(1, 2),
+    Score::new(-8, 0), Score::new(-5, 0), Score::new(6, 0), Score::new(20, 10), Score::new(10, 5),
+    Score::new(20, 30), Score::new(30, 50), Score::new(15, 10), Score::new(-50, -50),
+];
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights { terms: DEFAULT_WEIGHTS.map(|weight| EvalTerm { enabled: true, weight }) }
+    }
+}
+
+impl Index<Term> for EvalWeights {
+    type Output = EvalTerm;
+
+    fn index(&self, term: Term) -> &EvalTerm {
+        &self.terms[term as usize]
+    }
+}
+
+impl IndexMut<Term> for EvalWeights {
+    fn index_mut(&mut self, term: Term) -> &mut EvalTerm {
+        &mut self.terms[term as usize]
+    }
+}
+
+impl EvalWeights {
+    /// Adds up the features counted for each term times its weight, skipping disabled terms
+    ///
+    /// # Arguments
+    ///
+    /// * `features`: The count of each term, indexed by `Term`
+    ///
+    /// returns: Score The midgame and endgame scores in centipawns
+    pub fn score(&self, features: &[i32; Term::COUNT]) -> Score {
+        self.terms.iter().zip(features.iter())
+            .filter(|(term, _)| term.enabled)
+            .map(|(term, count)| term.weight * *count)
+            .sum()
+    }
+}
+
+// Indexed by piece
+const MOBILITY_TERM: [Term; 13] = [
+    Term::KnightMobility, Term::KnightMobility, Term::KnightMobility, Term::BishopMobility, Term::RookMobility,
+    Term::QueenMobility, Term::KnightMobility, Term::KnightMobility, Term::KnightMobility, Term::BishopMobility,
+    Term::RookMobility, Term::QueenMobility, Term::KnightMobility,
+];
+const MOBILITY_BASE: [i32; 13] = [0, 0, 4, 6, 6, 12, 0, 0, 4, 6, 6, 12, 0];
+const ATTACK_UNITS: [i32; 13] = [0, 0, 2, 2, 3, 5, 0, 0, 2, 2, 3, 5, 0];
+
+#[inline(always)]
+fn relative_rank(side: u8, sq: u8) -> u8 {
+    if side == WHITE { RANK_SQUARES[sq as usize] } else { 7 - RANK_SQUARES[sq as usize] }
+}
+
+/// Whether a square is the king's square or next to it
+#[inline(always)]
+fn in_king_zone(king_sq: u8, sq: u8) -> bool {
+    FILE_SQUARES[king_sq as usize].abs_diff(FILE_SQUARES[sq as usize]) <= 1
+        && RANK_SQUARES[king_sq as usize].abs_diff(RANK_SQUARES[sq as usize]) <= 1
+}
+
+/// Returns how many ranks in front of the king the nearest pawn on a file stands, from the
+/// king's side's point of view
+fn pawn_distance(pawns: u64, side: u8, file: u8, king_rank: u8) -> Option<u8> {
+    (1..8 - king_rank).find(|distance| {
+        let rank = if side == WHITE { king_rank + distance } else { 7 - (king_rank + distance) };
+        pawns & 1 << (rank * 8 + file) != 0
+    })
+}
+
+/// Counts the terms for one side's pieces
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `side`: The side to count for
+///
+/// returns: [i32; Term::COUNT] The count of each term, indexed by `Term`
+pub fn side_features(pos: &Board, side: u8) -> [i32; Term::COUNT] {
+    let mut features = [0; Term::COUNT];
+    let s = side as usize;
+    let own_pawns = pos.bitboards[s].board;
+    let enemy_pawns = pos.bitboards[s ^ 1].board;
+    let unsafe_squares = pawn_attacks(enemy_pawns, s ^ 1);
+    let color_offset = if side == WHITE { 0 } else { BP - WP };
+    let enemy_king = BK - color_offset;
+    let enemy_king_sq = if pos.num_pieces[enemy_king as usize] > 0 { Some(pos.king_sq[s ^ 1]) } else { None };
+
+    let mut attackers = 0;
+    let mut attack_units = 0;
+    for pce in WN + color_offset..=WQ + color_offset {
+        let piece = pce as usize;
+        for i in 0..pos.num_pieces[piece] as usize {
+            let sq = pos.piece_list[piece][i] as i32;
+            let mut mobility = 0;
+            let mut hits_zone = false;
+
+            for dir in PIECE_DIR[piece][..NUM_DIR[piece]].iter().copied() {
+                let mut t_sq = sq + dir;
+                while is_sq_on_board(t_sq as u8) {
+                    let t_pce = pos.pieces[t_sq as usize];
+                    hits_zone |= enemy_king_sq.is_some_and(|king_sq| in_king_zone(king_sq, t_sq as u8));
+                    if PIECE_COLOR[t_pce as usize] != side && unsafe_squares & 1 << pos.sq64(t_sq as u8) == 0 {
+                        mobility += 1;
+                    }
+                    if t_pce != EMPTY || !SLIDES[piece] {
+                        break;
+                    }
+                    t_sq += dir;
+                }
+            }
+
+            let sq = sq as u8;
+            let sq64 = pos.sq64(sq);
+            features[MOBILITY_TERM[piece] as usize] += mobility - MOBILITY_BASE[piece];
+            if mobility == 0 && relative_rank(side, sq) >= 4 {
+                features[Term::TrappedPiece as usize] += 1;
+            }
+            if hits_zone {
+                attackers += 1;
+                attack_units += ATTACK_UNITS[piece];
+            }
+
+            if pce == WN + color_offset && (3..=5).contains(&relative_rank(side, sq))
+                && pawn_attacks(own_pawns, s) & 1 << sq64 != 0 && enemy_pawns & attack_span(s, sq64) == 0 {
+                features[Term::KnightOutpost as usize] += 1;
+            }
+
+            if pce == WR + color_offset {
+                let file = 0x0101_0101_0101_0101u64 << FILE_SQUARES[sq as usize];
+                if (own_pawns | enemy_pawns) & file == 0 {
+                    features[Term::RookOpenFile as usize] += 1;
+                } else if own_pawns & file == 0 {
+                    features[Term::RookSemiOpenFile as usize] += 1;
+                }
+                let rank = 0xFFu64 << (RANK_SQUARES[sq as usize] * 8);
+                if relative_rank(side, sq) == 6 && (enemy_pawns & rank != 0
+                    || enemy_king_sq.is_some_and(|king_sq| relative_rank(side, king_sq) == 7)) {
+                    features[Term::RookOnSeventh as usize] += 1;
+                }
+            }
+        }
+    }
+    if attackers >= 2 {
+        features[Term::KingAttack as usize] = attack_units;
+    }
+
+    if pos.num_pieces[(WB + color_offset) as usize] >= 2 {
+        features[Term::BishopPair as usize] = 1;
+    }
+
+    if pos.num_pieces[(WK + color_offset) as usize] > 0 {
+        let king_sq = pos.king_sq[s];
+        let king_file = FILE_SQUARES[king_sq as usize];
+        let king_rank = relative_rank(side, king_sq);
+        for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+            features[Term::KingShelter as usize] += match pawn_distance(own_pawns, side, file, king_rank) {
+                Some(1) => 0,
+                Some(2) => 1,
+                _ => 2,
+            };
+            if let Some(distance) = pawn_distance(enemy_pawns, side, file, king_rank).filter(|distance| *distance <= 3) {
+                features[Term::PawnStorm as usize] += 4 - distance as i32;
+            }
+        }
+    }
+    features
+}
+
+/// Counts every term for the position
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: [i32; Term::COUNT] White's count minus black's for each term, indexed by `Term`
+pub fn term_features(pos: &Board) -> [i32; Term::COUNT] {
+    let white = side_features(pos, WHITE);
+    let black = side_features(pos, BLACK);
+    std::array::from_fn(|i| white[i] - black[i])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::pieces::WHITE;
+    use crate::evaluation::terms::{side_features, term_features, EvalWeights, Term};
+    use crate::game_board::board::Board;
+
+    fn white(fen: &str, term: Term) -> i32 {
+        side_features(&Board::from_fen(fen).unwrap(), WHITE)[term as usize]
+    }
+
+    #[test]
+    fn test_start_position_is_even() {
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(term_features(&board), [0; Term::COUNT]);
+        assert_eq!(side_features(&board, WHITE)[Term::KnightMobility as usize], -4, "Knights reach 2 squares each");
+        assert_eq!(side_features(&board, WHITE)[Term::BishopPair as usize], 1);
+    }
+
+    #[test]
+    fn test_mobility_avoids_pawn_attacks() {
+        // The knight can't go to d6 or f6, which the e7 pawn covers
+        assert_eq!(white("4k3/4p3/8/8/4N3/8/8/4K3 w - - 0 1", Term::KnightMobility), 8 - 2 - 4);
+        assert_eq!(white("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Term::RookMobility), 10 - 6);
+    }
+
+    #[test]
+    fn test_rook_files_and_seventh() {
+        let fen = "4k3/p2R3p/8/8/8/8/P7/R3K2R w - - 0 1";
+        assert_eq!(white(fen, Term::RookOpenFile), 1);
+        assert_eq!(white(fen, Term::RookSemiOpenFile), 1);
+        assert_eq!(white(fen, Term::RookOnSeventh), 1);
+    }
+
+    #[test]
+    fn test_knight_outpost() {
+        assert_eq!(white("4k3/8/8/4N3/3P4/8/8/4K3 w - - 0 1", Term::KnightOutpost), 1);
+        assert_eq!(white("4k3/5p2/8/4N3/3P4/8/8/4K3 w - - 0 1", Term::KnightOutpost), 0, "The f pawn can chase it");
+        assert_eq!(white("4k3/8/8/4N3/8/8/8/4K3 w - - 0 1", Term::KnightOutpost), 0, "Not defended by a pawn");
+    }
+
+    #[test]
+    fn test_trapped_piece() {
+        assert_eq!(white("N3k3/p1P5/1p6/8/8/8/8/4K3 w - - 0 1", Term::TrappedPiece), 1);
+        assert_eq!(white("N3k3/2P5/1p6/8/8/8/8/4K3 w - - 0 1", Term::TrappedPiece), 0, "It can take on b6");
+    }
+
+    #[test]
+    fn test_king_shelter_and_storm() {
+        assert_eq!(white("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1", Term::KingShelter), 0);
+        let fen = "4k3/8/8/8/6p1/5P1p/6P1/6K1 w - - 0 1";
+        assert_eq!(white(fen, Term::KingShelter), 3, "f3 was pushed once and the h pawn is gone");
+        assert_eq!(white(fen, Term::PawnStorm), 1 + 2, "The g4 pawn is 3 ranks up and h3 2 ranks up");
+    }
+
+    #[test]
+    fn test_king_attack() {
+        assert_eq!(white("6k1/5ppp/8/3Q2N1/8/8/8/4K3 w - - 0 1", Term::KingAttack), 5 + 2);
+        assert_eq!(white("6k1/5ppp/8/3Q4/8/8/8/4K3 w - - 0 1", Term::KingAttack), 0, "A lone attacker doesn't count");
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut weights = EvalWeights::default();
+        let mut features = [0; Term::COUNT];
+        features[Term::BishopPair as usize] = 1;
+        features[Term::TrappedPiece as usize] = -1;
+        assert_eq!(weights.score(&features), weights[Term::BishopPair].weight - weights[Term::TrappedPiece].weight);
+        weights[Term::TrappedPiece].enabled = false;
+        assert_eq!(weights.score(&features), weights[Term::BishopPair].weight);
+        assert_eq!(Term::from_name("Bishop_Pair"), Some(Term::BishopPair));
+        assert!(Term::ALL.iter().enumerate().all(|(i, term)| *term as usize == i));
+    }
+}
@@ -0,0 +1,189 @@
+//! Evaluation trace, explaining a static evaluation term by term. It adds up the same terms
+//! as `evaluate_position`, but keeps each side's midgame and endgame score for every one of
+//! them along with the phase and scale used to blend them.
+
+use std::fmt;
+use crate::constants::pieces::{*};
+use crate::evaluation::evaluate::{evaluate_position, material_score, placement_score};
+use crate::evaluation::pawns::{free_passers, side_structure};
+use crate::evaluation::tapered::{endgame_scale, game_phase, taper, Score, MAX_PHASE, SCALE_NORMAL};
+use crate::evaluation::terms::{side_features, Term};
+use crate::game_board::board::Board;
+
+/// Each side's score for one evaluation term
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TraceRow {
+    pub name: &'static str,
+    pub enabled: bool,
+    pub white: Score,
+    pub black: Score,
+}
+
+/// The breakdown of a static evaluation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub rows: Vec<TraceRow>, // Material, piece squares and pawns, then every Term in order
+    pub phase: i32,
+    pub scale: i32,
+    pub total: Score, // White's scores minus black's
+    pub score: i32, // Final score from white's point of view
+    pub side: u8,
+}
+
+impl EvalTrace {
+    /// Looks up the row of a term by name
+    pub fn row(&self, name: &str) -> Option<&TraceRow> {
+        self.rows.iter().find(|row| row.name == name)
+    }
+
+    /// Returns the final score from the point of view of the side to move, as
+    /// `evaluate_position` gives it
+    pub fn side_to_move_score(&self) -> i32 {
+        if self.side == WHITE { self.score } else { -self.score }
+    }
+
+    /// Writes the trace as a JSON object
+    pub fn to_json(&self) -> String {
+        let score = |score: Score| format!("{{\"mg\":{},\"eg\":{}}}", score.mg, score.eg);
+        let rows: Vec<String> = self.rows.iter()
+            .map(|row| format!("{{\"name\":\"{}\",\"enabled\":{},\"white\":{},\"black\":{}}}",
+                               row.name, row.enabled, score(row.white), score(row.black)))
+            .collect();
+        format!("{{\"terms\":[{}],\"phase\":{},\"max_phase\":{},\"scale\":{},\"scale_normal\":{},\"total\":{},\"score\":{},\"side_to_move_score\":{}}}",
+                rows.join(","), self.phase, MAX_PHASE, self.scale, SCALE_NORMAL, score(self.total), self.score,
+                self.side_to_move_score())
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<22}{:>16}{:>16}{:>16}", "Term", "White mg  eg", "Black mg  eg", "Total mg  eg")?;
+        writeln!(f, "{}", "-".repeat(70))?;
+        for row in self.rows.iter() {
+            let name = if row.enabled { row.name.to_string() } else { format!("{} (off)", row.name) };
+            let total = row.white - row.black;
+            writeln!(f, "{:<22}{:>8}{:>8}{:>8}{:>8}{:>8}{:>8}", name, row.white.mg, row.white.eg,
+                     row.black.mg, row.black.eg, total.mg, total.eg)?;
+        }
+        writeln!(f, "{}", "-".repeat(70))?;
+        writeln!(f, "{:<54}{:>8}{:>8}", "Total", self.total.mg, self.total.eg)?;
+        writeln!(f, "Phase {}/{}, endgame scale {}/{}", self.phase, MAX_PHASE, self.scale, SCALE_NORMAL)?;
+        write!(f, "Score {} for white, {} for the side to move", self.score, self.side_to_move_score())
+    }
+}
+
+/// Evaluates the position like `evaluate_position`, keeping the score of every term. The
+/// pawn hash table isn't used, so the board doesn't need to be mutable.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: EvalTrace
+pub fn evaluate_traced(pos: &Board) -> EvalTrace {
+    let pawns = [pos.bitboards[WHITE_S].board, pos.bitboards[BLACK_S].board];
+    let pawn_score = |side: usize| {
+        let (score, passed) = side_structure(pawns, side);
+        score + free_passers(pos, passed, side)
+    };
+
+    let mut rows = vec![
+        TraceRow { name: "material", enabled: true, white: material_score(pos, WHITE), black: material_score(pos, BLACK) },
+        TraceRow { name: "piece_squares", enabled: true, white: placement_score(pos, WHITE), black: placement_score(pos, BLACK) },
+        TraceRow { name: "pawns", enabled: true, white: pawn_score(WHITE_S), black: pawn_score(BLACK_S) },
+    ];
+
+    let features = [side_features(pos, WHITE), side_features(pos, BLACK)];
+    for term in Term::ALL {
+        let weight = pos.eval_weights[term];
+        let score = |side: usize| if weight.enabled { weight.weight * features[side][term as usize] } else { Score::ZERO };
+        rows.push(TraceRow { name: term.name(), enabled: weight.enabled, white: score(WHITE_S), black: score(BLACK_S) });
+    }
+
+    let total = rows.iter().map(|row| row.white - row.black).sum::<Score>();
+    let phase = game_phase(pos);
+    let scale = endgame_scale(pos, total.eg);
+    EvalTrace { rows, phase, scale, total, score: taper(total, phase, scale), side: pos.side }
+}
+
+/// Explains the evaluation of a position for the `eval` and `eval json` debug commands. With a
+/// network loaded the network's score is given, as there are no terms to break it down into.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `json`: Whether to write JSON rather than a table
+///
+/// returns: String
+pub fn describe_evaluation(pos: &mut Board, json: bool) -> String {
+    if pos.nnue.is_some() {
+        let score = evaluate_position(pos);
+        return if json {
+            format!("{{\"network\":true,\"side_to_move_score\":{}}}", score)
+        } else {
+            format!("Network score {} for the side to move", score)
+        };
+    }
+    let trace = evaluate_traced(pos);
+    if json { trace.to_json() } else { trace.to_string() }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use crate::evaluation::evaluate::evaluate_position;
+    use crate::evaluation::tapered::Score;
+    use crate::evaluation::terms::Term;
+    use crate::evaluation::nnue::Network;
+    use crate::evaluation::trace::{describe_evaluation, evaluate_traced};
+    use crate::game_board::board::Board;
+
+    #[test]
+    fn test_trace_matches_evaluation() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            "4k3/8/8/8/8/8/8/3RK1b1 w - - 0 1",
+            "2b1k3/p7/8/8/8/8/PP6/2B1K3 b - - 0 1",
+        ] {
+            let mut pos = Board::from_fen(fen).unwrap();
+            let trace = evaluate_traced(&pos);
+            assert_eq!(trace.side_to_move_score(), evaluate_position(&mut pos), "Trace differs for {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_disabled_term() {
+        let mut pos = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+        pos.eval_weights[Term::BishopPair].enabled = false;
+        let trace = evaluate_traced(&pos);
+        let row = trace.row("bishop_pair").unwrap();
+        assert!(!row.enabled);
+        assert_eq!(row.white, Score::ZERO);
+        assert_eq!(trace.side_to_move_score(), evaluate_position(&mut pos));
+    }
+
+    #[test]
+    fn test_trace_output() {
+        let trace = evaluate_traced(&Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap());
+        let table = trace.to_string();
+        assert!(table.contains("material") && table.contains("trapped_piece") && table.contains("Phase 0/24"));
+        let json = trace.to_json();
+        assert!(json.starts_with("{\"terms\":[{\"name\":\"material\",\"enabled\":true,\"white\":{\"mg\":100,\"eg\":120}"));
+        assert!(json.ends_with(&format!("\"score\":{},\"side_to_move_score\":{}}}", trace.score, trace.score)));
+    }
+
+    #[test]
+    fn test_describe_evaluation() {
+        let mut pos = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(describe_evaluation(&mut pos, true), evaluate_traced(&pos).to_json());
+        assert!(describe_evaluation(&mut pos, false).contains("Phase 0/24"));
+
+        // A network's score is given in place of the handcrafted terms
+        pos.set_network(Some(Arc::new(Network::random(1))));
+        let score = evaluate_position(&mut pos);
+        assert_eq!(describe_evaluation(&mut pos, false), format!("Network score {} for the side to move", score));
+        assert_eq!(describe_evaluation(&mut pos, true), format!("{{\"network\":true,\"side_to_move_score\":{}}}", score));
+    }
+}
@@ -0,0 +1,147 @@
+//! Fuzz targets for the text parsers. Each target takes raw bytes and panics only when it finds
+//! a bug, so they can be driven by cargo-fuzz (see `fuzz/`) or by the offline corpus runner in
+//! `tests/fuzz_test.rs`.
+
+use crate::game_board::board::Board;
+use crate::game_board::invariants::validate_board;
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::generate_all_moves;
+use crate::utils::io::{parse_move, validate_move};
+use crate::variants::variant::Variant;
+
+/// Positions the move parser is fuzzed against, covering promotions, en passant, castling,
+/// Chess960 castling and drops
+const MOVE_POSITIONS: [(Variant, bool, &str); 4] = [
+    (Variant::Standard, false, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    (Variant::Standard, false, "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"),
+    (Variant::Standard, true, "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9"),
+    (Variant::Crazyhouse, false, "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[Pn] b KQkq - 0 1"),
+];
+
+fn assert_valid(board: &Board, context: &str) {
+    if let Err(violation) = validate_board(board) {
+        panic!("{} left the board inconsistent: {}", context, violation);
+    }
+}
+
+/// Makes and undoes every legal move of the position, checking the board is consistent after
+/// each move and exactly restored after each undo
+fn check_moves(board: &mut Board) {
+    let key = board.pos_key;
+    let fen = board.to_fen();
+
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(board, &mut move_list);
+    for mov in move_list {
+        if board.make_move(mov) {
+            assert_valid(board, "make_move");
+            board.undo_move();
+        }
+        assert_eq!(board.pos_key, key, "Undoing {} did not restore the position key", mov);
+        assert_eq!(board.to_fen(), fen, "Undoing {} did not restore the position", mov);
+    }
+}
+
+/// Fuzz target for the FEN parser. The first byte picks the variant and Chess960 mode and the
+/// rest is the FEN. Any input must either be rejected or give a consistent board whose
+/// written FEN parses back to the same FEN, and whose moves can all be made and undone.
+///
+/// # Arguments
+///
+/// * `data`: The fuzzer's input
+///
+/// returns: ()
+pub fn fuzz_fen(data: &[u8]) {
+    let (selector, text) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let fen = match std::str::from_utf8(text) {
+        Ok(fen) => fen,
+        Err(_) => return,
+    };
+
+    let new_board = || {
+        let mut board = Board::new();
+        board.variant = Variant::ALL[*selector as usize % Variant::ALL.len()];
+        board.chess960 = selector & 0x80 != 0;
+        board
+    };
+
+    let mut board = new_board();
+    if board.set_fen(fen).is_err() {
+        return;
+    }
+    assert_valid(&board, "set_fen");
+
+    let written = board.to_fen();
+    let mut reparsed = new_board();
+    if let Err(error) = reparsed.set_fen(&written) {
+        panic!("Could not parse written FEN {}: {}", written, error);
+    }
+    assert_eq!(reparsed.to_fen(), written, "FEN did not round trip");
+
+    check_moves(&mut board);
+}
+
+/// Fuzz target for the move parser. The first byte picks one of a few positions and the rest
+/// is the move text. Any move the parser accepts has to be one the board can make and undo.
+///
+/// # Arguments
+///
+/// * `data`: The fuzzer's input
+///
+/// returns: ()
+pub fn fuzz_move(data: &[u8]) {
+    let (selector, text) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let text = match std::str::from_utf8(text) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+
+    let (variant, chess960, fen) = MOVE_POSITIONS[*selector as usize % MOVE_POSITIONS.len()];
+    let mut board = Board::new();
+    board.variant = variant;
+    board.chess960 = chess960;
+    board.set_fen(fen).expect("Fuzzing position did not parse");
+    let key = board.pos_key;
+
+    let mov = match validate_move(&board, text).ok().and_then(|(from, to, prom)| parse_move(&board, from, to, prom)) {
+        Some(mov) => mov,
+        None => return,
+    };
+    if board.make_move(mov) {
+        assert_valid(&board, "make_move");
+        board.undo_move();
+    }
+    assert_eq!(board.pos_key, key, "Undoing parsed move {} did not restore the position key", text);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fuzz::targets::{fuzz_fen, fuzz_move};
+
+    #[test]
+    fn test_fuzz_fen_seeds() {
+        fuzz_fen(b"\x00rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        fuzz_fen(b"\x07rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Qn] w KQkq - 0 1");
+        fuzz_fen(b"\x00rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0");
+        fuzz_fen(b"\x00rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq");
+        fuzz_fen(b"\x00rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        fuzz_fen(b"");
+    }
+
+    #[test]
+    fn test_fuzz_move_seeds() {
+        for text in ["e2a6", "a7b8q", "e1h1", "P@e4", "N@a1", "e2", "z9z9", "e1g1", "\u{e9}@e4"] {
+            for selector in 0..4u8 {
+                let mut data = vec![selector];
+                data.extend_from_slice(text.as_bytes());
+                fuzz_move(&data);
+            }
+        }
+    }
+}
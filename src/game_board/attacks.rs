@@ -0,0 +1,309 @@
+//! Attack maps for analysis: the pieces attacking a square, every square a side attacks and
+//! how often, pinned pieces, discovered attack candidates and the pieces giving check
+
+use crate::constants::pieces::{*};
+use crate::constants::squares::OFFBOARD;
+use crate::game_board::bitboard::BitBoard;
+use crate::game_board::board::Board;
+
+/// A piece that can't leave the line between its king and an enemy slider without
+/// exposing the king
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub pinned: u8, // 120 square board numbers
+    pub pinner: u8,
+}
+
+/// A piece standing between a slider of its own side and an enemy piece, so moving it away
+/// uncovers an attack. If the target is the king, moving it gives discovered check.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DiscoveredAttack {
+    pub blocker: u8, // 120 square board numbers
+    pub attacker: u8,
+    pub target: u8,
+}
+
+/// Every square a side attacks, along with the number of its pieces attacking each square
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AttackMap {
+    pub squares: BitBoard, // 64 square numbering
+    pub counts: [u8; 64],
+}
+
+#[inline(always)]
+fn offset(sq: u8, dir: i32) -> u8 {
+    (sq as i32 + dir) as u8
+}
+
+/// Whether a slider moves along a direction, given as an index into the queen's directions
+/// where the first four are the rook's
+#[inline(always)]
+fn slides_along(pce: u8, queen_dir: usize) -> bool {
+    match pce {
+        WQ | BQ => true,
+        WR | BR => queen_dir < 4,
+        WB | BB => queen_dir >= 4,
+        _ => false,
+    }
+}
+
+impl Board {
+    /// Returns the first two pieces met walking from a square in a direction, with their
+    /// squares, stopping at the edge of the board
+    fn pieces_along(&self, sq: u8, dir: i32) -> [Option<u8>; 2] {
+        let mut found = [None; 2];
+        let mut current = offset(sq, dir);
+        for slot in found.iter_mut() {
+            while self.pieces[current as usize] == EMPTY {
+                current = offset(current, dir);
+            }
+            if self.pieces[current as usize] == OFFBOARD {
+                break;
+            }
+            *slot = Some(current);
+            current = offset(current, dir);
+        }
+        found
+    }
+
+    /// Finds every piece of a side attacking a square, whatever stands on it
+    ///
+    /// # Arguments
+    ///
+    /// * `sq`: The 120 square board number that is attacked
+    /// * `side`: The side to find attackers for
+    ///
+    /// returns: Vec<u8> The 120 square board numbers of the attackers
+    pub fn attackers_of(&self, sq: u8, side: u8) -> Vec<u8> {
+        let mut attackers = Vec::new();
+        let color_offset = if side == WHITE { 0 } else { BP - WP };
+
+        let pawn_dirs = if side == WHITE { [-9, -11] } else { [9, 11] };
+        for dir in pawn_dirs {
+            if self.pieces[offset(sq, dir) as usize] == WP + color_offset {
+                attackers.push(offset(sq, dir));
+            }
+        }
+        for (pce, dirs) in [(WN, PIECE_DIR[WN as usize]), (WK, PIECE_DIR[WK as usize])] {
+            for dir in dirs {
+                if self.pieces[offset(sq, dir) as usize] == pce + color_offset {
+                    attackers.push(offset(sq, dir));
+                }
+            }
+        }
+        for (i, dir) in PIECE_DIR[WQ as usize].iter().enumerate() {
+            if let [Some(from), _] = self.pieces_along(sq, *dir) {
+                let pce = self.pieces[from as usize];
+                if PIECE_COLOR[pce as usize] == side && slides_along(pce, i) {
+                    attackers.push(from);
+                }
+            }
+        }
+        attackers
+    }
+
+    /// Same as `attackers_of`, as a bitboard of 64 square numbers
+    pub fn attackers_bitboard(&self, sq: u8, side: u8) -> BitBoard {
+        let mut attackers = BitBoard::new(0);
+        for from in self.attackers_of(sq, side) {
+            attackers.set_bit(self.sq64(from));
+        }
+        attackers
+    }
+
+    /// Returns the squares the piece on a square attacks, including squares holding pieces
+    /// of its own side
+    ///
+    /// # Arguments
+    ///
+    /// * `sq`: The 120 square board number of the piece
+    ///
+    /// returns: Vec<u8> The attacked 120 square board numbers, empty for an empty square
+    pub fn attacks_from(&self, sq: u8) -> Vec<u8> {
+        let pce = self.pieces[sq as usize];
+        let mut attacked = Vec::new();
+        match pce {
+            EMPTY | OFFBOARD => (),
+            WP | BP => {
+                let dirs = if pce == WP { [9, 11] } else { [-9, -11] };
+                attacked.extend(dirs.iter().map(|dir| offset(sq, *dir)));
+            }
+            _ if SLIDES[pce as usize] => {
+                for dir in PIECE_DIR[pce as usize][..NUM_DIR[pce as usize]].iter() {
+                    let mut current = offset(sq, *dir);
+                    while self.pieces[current as usize] != OFFBOARD {
+                        attacked.push(current);
+                        if self.pieces[current as usize] != EMPTY {
+                            break;
+                        }
+                        current = offset(current, *dir);
+                    }
+                }
+            }
+            _ => attacked.extend(PIECE_DIR[pce as usize][..NUM_DIR[pce as usize]].iter().map(|dir| offset(sq, *dir))),
+        }
+        attacked.retain(|to| self.pieces[*to as usize] != OFFBOARD);
+        attacked
+    }
+
+    /// Builds the map of every square a side attacks
+    ///
+    /// # Arguments
+    ///
+    /// * `side`: The attacking side
+    ///
+    /// returns: AttackMap
+    pub fn attack_map(&self, side: u8) -> AttackMap {
+        let mut map = AttackMap { squares: BitBoard::new(0), counts: [0; 64] };
+        let first = if side == WHITE { WP } else { BP };
+        for pce in first..first + 6 {
+            for from in self.piece_list[pce as usize][..self.num_pieces[pce as usize] as usize].iter() {
+                for to in self.attacks_from(*from) {
+                    let to64 = self.sq64(to);
+                    map.squares.set_bit(to64);
+                    map.counts[to64 as usize] += 1;
+                }
+            }
+        }
+        map
+    }
+
+    /// Counts the pieces attacking and defending the piece on a square
+    ///
+    /// # Arguments
+    ///
+    /// * `sq`: The 120 square board number of the piece
+    ///
+    /// returns: (usize, usize) The number of enemy attackers and of defenders of its own
+    /// color, or (0, 0) for an empty square
+    pub fn attackers_and_defenders(&self, sq: u8) -> (usize, usize) {
+        let pce = self.pieces[sq as usize];
+        if pce == EMPTY || pce == OFFBOARD {
+            return (0, 0);
+        }
+        let side = PIECE_COLOR[pce as usize];
+        (self.attackers_of(sq, side ^ 1).len(), self.attackers_of(sq, side).len())
+    }
+
+    /// Finds the pieces of a side pinned against its king by enemy sliders
+    ///
+    /// # Arguments
+    ///
+    /// * `side`: The side whose pieces might be pinned
+    ///
+    /// returns: Vec<Pin>
+    pub fn pins(&self, side: u8) -> Vec<Pin> {
+        let king = if side == WHITE { WK } else { BK };
+        if self.num_pieces[king as usize] == 0 {
+            return Vec::new();
+        }
+
+        let mut pins = Vec::new();
+        for (i, dir) in PIECE_DIR[WQ as usize].iter().enumerate() {
+            if let [Some(pinned), Some(pinner)] = self.pieces_along(self.king_sq[side as usize], *dir) {
+                let pinner_pce = self.pieces[pinner as usize];
+                if PIECE_COLOR[self.pieces[pinned as usize] as usize] == side
+                    && PIECE_COLOR[pinner_pce as usize] == side ^ 1 && slides_along(pinner_pce, i) {
+                    pins.push(Pin { pinned, pinner });
+                }
+            }
+        }
+        pins
+    }
+
+    /// Finds the pieces of a side that uncover an attack on an enemy piece by moving, as
+    /// they block one of their own side's sliders
+    ///
+    /// # Arguments
+    ///
+    /// * `side`: The side that would make the discovered attack
+    ///
+    /// returns: Vec<DiscoveredAttack>
+    pub fn discovered_attacks(&self, side: u8) -> Vec<DiscoveredAttack> {
+        let mut discovered = Vec::new();
+        let first = if side == WHITE { WP } else { BP };
+        for pce in first..first + 6 {
+            if !SLIDES[pce as usize] {
+                continue;
+            }
+            for attacker in self.piece_list[pce as usize][..self.num_pieces[pce as usize] as usize].iter() {
+                for (i, dir) in PIECE_DIR[WQ as usize].iter().enumerate() {
+                    if !slides_along(pce, i) {
+                        continue;
+                    }
+                    if let [Some(blocker), Some(target)] = self.pieces_along(*attacker, *dir) {
+                        if PIECE_COLOR[self.pieces[blocker as usize] as usize] == side
+                            && PIECE_COLOR[self.pieces[target as usize] as usize] == side ^ 1 {
+                            discovered.push(DiscoveredAttack { blocker, attacker: *attacker, target });
+                        }
+                    }
+                }
+            }
+        }
+        discovered
+    }
+
+    /// Returns the squares of the pieces giving check to the side to move
+    pub fn checkers(&self) -> Vec<u8> {
+        let king = if self.side == WHITE { WK } else { BK };
+        if self.num_pieces[king as usize] == 0 {
+            return Vec::new();
+        }
+        self.attackers_of(self.king_sq[self.side as usize], self.side ^ 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::pieces::{BLACK, WHITE};
+    use crate::constants::squares::{*};
+    use crate::game_board::attacks::{DiscoveredAttack, Pin};
+    use crate::game_board::board::Board;
+
+    fn sorted(mut squares: Vec<u8>) -> Vec<u8> {
+        squares.sort_unstable();
+        squares
+    }
+
+    #[test]
+    fn test_attackers_of() {
+        let board = Board::from_fen("4k3/8/2n5/3p4/2P5/4N3/3Q4/3RK3 w - - 0 1").unwrap();
+        assert_eq!(sorted(board.attackers_of(D5, WHITE)), vec![D2, E3, C4], "Queen, pawn and knight attack d5");
+        assert_eq!(sorted(board.attackers_of(E4, BLACK)), vec![D5]);
+        assert_eq!(board.attackers_bitboard(D5, WHITE).board.count_ones(), 3);
+        // The rook sees through nothing, the queen blocks it
+        assert_eq!(sorted(board.attackers_of(D4, WHITE)), vec![D2]);
+    }
+
+    #[test]
+    fn test_attack_map() {
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let map = board.attack_map(WHITE);
+        assert_eq!(map.squares.board.count_ones(), 22, "White attacks ranks 2 and 3 and six back rank squares");
+        assert_eq!(map.counts[board.sq64(F3) as usize], 3, "Pawns on e2 and g2 and the knight on g1 attack f3");
+        assert_eq!(map.counts[board.sq64(A1) as usize], 0);
+        assert_eq!(board.attackers_and_defenders(E2), (0, 4));
+    }
+
+    #[test]
+    fn test_pins() {
+        let board = Board::from_fen("4k3/4r3/8/8/1b2N3/8/3B4/4K2r w - - 0 1").unwrap();
+        assert_eq!(board.pins(WHITE), vec![Pin { pinned: E4, pinner: E7 }, Pin { pinned: D2, pinner: B4 }]);
+        assert!(board.pins(BLACK).is_empty());
+    }
+
+    #[test]
+    fn test_discovered_attacks() {
+        let board = Board::from_fen("4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1").unwrap();
+        assert_eq!(board.discovered_attacks(WHITE), vec![DiscoveredAttack { blocker: E4, attacker: E1, target: E8 }]);
+        assert!(board.discovered_attacks(BLACK).is_empty());
+    }
+
+    #[test]
+    fn test_checkers() {
+        let board = Board::from_fen("4k3/8/5N2/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(sorted(board.checkers()), vec![E1, F6], "Double check from the rook and knight");
+        let quiet = self::Board::from_fen("4k3/8/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert!(quiet.checkers().is_empty());
+    }
+}
@@ -1,25 +1,26 @@
+use std::sync::Arc;
 use crate::alphabeta::pvtable::PVTable;
+use crate::evaluation::nnue::{Network, Nnue};
+use crate::evaluation::pawns::PawnTable;
+use crate::evaluation::terms::EvalWeights;
 use crate::constants::pieces::{*};
-use crate::constants::squares::{A1, A8, C1, C8, D1, D8, F1, F8, G1, G8, H1, H8, NO_SQ};
+use crate::constants::squares::{A1, A8, CASTLE_KING_TO, CASTLE_ROOK_TO, FILE_A, FILE_H, FILE_SQUARES, H1, H8, NO_SQ, RANK_1, RANK_8, RANK_SQUARES};
 use crate::constants::{pieces, squares};
 use crate::game_board::bitboard::BitBoard;
+use crate::game_board::invariants::validate_board;
+#[cfg(feature = "invariants")]
+use crate::game_board::invariants::{assert_invariants, validate_pieces};
 use crate::moves::gamemove::{GameMove, MFLAG_EP, MFLAG_PS};
-use crate::moves::movegen::{generate_all_moves, square_is_attacked};
+use crate::moves::movegen::generate_all_moves;
+use crate::variants::variant::{move_was_illegal, side_in_check, Variant, CHECKS_TO_WIN, EXPLOSION_DIR};
 use crate::moves::validate::is_sq_on_board;
 use crate::utils::hashkeys::BoardHasher;
+use crate::utils::io::{validate_fen, ParseFenError};
 use crate::utils::piece_utils::{piece_is_king, piece_is_pawn};
 use crate::utils::square_utils::fr2sq;
 
 /// Code used for storing the general state of the board
 
-const CASTLE_PERM: [u8; 120] = [
-    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 13, 15, 15,
-    15, 12, 15, 15, 14, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
-    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
-    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 7, 15, 15, 15, 3,
-    15, 15, 11, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
-]; // For bitwise and with castle perm because castle perm is kept as 4 bits
-
 #[derive(Debug, Copy, Clone)]
 pub struct PastMove {
     game_move: GameMove,
@@ -28,6 +29,11 @@ pub struct PastMove {
     //Castle permission
     fifty_move: u64,
     pub pos_key: u64,
+    checks_given: [u8; 2],
+    exploded: [u8; 9], // Pieces removed by an atomic explosion, in EXPLOSION_DIR order then the capturer
+    promoted: u64,
+    hand_piece: u8, // Piece a capture put in hand in a drop variant
+    king_sq: [u8; 2], // A promotion to king in antichess leaves the old king's square behind
 }
 
 #[derive(Debug, Clone)]
@@ -43,8 +49,17 @@ pub struct Board {
     pub history_ply: u64,
 
     pub castle_perm: u8, //Castle permission
+    pub castle_rooks: [u8; 4], // Starting square of the rook for each castle right
+    castle_perm_mask: [u8; 120], // For bitwise and with castle perm when a piece leaves or lands on a square
+    pub chess960: bool, // Use king-takes-rook castling notation and X-FEN castling fields
+    pub variant: Variant,
+    pub checks_given: [u8; 2], // Checks each side has given, for three-check
+    pub pockets: [u8; 13], // Count of each piece held in hand, for drop variants
+    pub promoted: u64, // 64 square mask of promoted pieces, which go back to hand as pawns
 
     pub pos_key: u64,
+    pub pawn_key: u64, // Key of the pawns alone, for the pawn structure hash table
+    fen_ply: u64, // Game ply of the position the board was set up from
 
     pub num_pieces: [u8; 13],
     pub num_big_pieces: [u8; 2],
@@ -58,11 +73,14 @@ pub struct Board {
     // Array to convert 10x12 square numbers to 8x8 square numbers
     pub sq64_to_sq120: [u8; 64], //Array to convert 8x8 square numbers to 10x12 square numbers
 
-    pub piece_list: [[u8; 18]; 13],
+    pub piece_list: [[u8; 64]; 13], // Room for a piece on every square, so no position can overflow a list
 
     hasher: BoardHasher,
 
     pub pvtable: PVTable,
+    pub pawn_table: PawnTable,
+    pub eval_weights: EvalWeights,
+    pub nnue: Option<Nnue>, // Neural network evaluation, used instead of the handcrafted one when set
     pub pvarray: [GameMove; 64],
     pub search_history:[[u64; 64]; 13],
     pub search_killers:[[u64; 64]; 2] // Good moves that ended alpha beta search previously
@@ -70,6 +88,40 @@ pub struct Board {
 
 impl Board {
     pub fn new() -> Board {
+        Board::with_hasher(BoardHasher::new())
+    }
+
+    /// Creates a board set up from a FEN string, see `set_fen`
+    pub fn from_fen(fen: &str) -> Result<Board, ParseFenError> {
+        Board::from_variant_fen(Variant::Standard, fen)
+    }
+
+    /// Creates a board for a variant set up from a FEN string, see `set_fen`
+    pub fn from_variant_fen(variant: Variant, fen: &str) -> Result<Board, ParseFenError> {
+        let mut board = Board::new();
+        board.variant = variant;
+        board.set_fen(fen)?;
+        Ok(board)
+    }
+
+    /// Switches between the neural network evaluation and the handcrafted one
+    ///
+    /// # Arguments
+    ///
+    /// * `network`: The network to evaluate with, or None for the handcrafted evaluation
+    ///
+    /// returns: ()
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network.map(Nnue::new);
+    }
+
+    /// Creates a board whose position keys come from a seed of its own rather than the
+    /// default one, so that searches on it are still repeatable but collide differently
+    pub fn with_seed(seed: u64) -> Board {
+        Board::with_hasher(BoardHasher::from_seed(seed))
+    }
+
+    fn with_hasher(hasher: BoardHasher) -> Board {
         let mut sq120_to_sq64: [u8; 120] = [65; 120];
         let mut sq64_to_sq120: [u8; 64] = [120; 64];
 
@@ -93,7 +145,16 @@ impl Board {
             ply: 0,
             history_ply: 0,
             castle_perm: 0,
+            castle_rooks: [H1, A1, H8, A8],
+            castle_perm_mask: [15; 120],
+            chess960: false,
+            variant: Variant::Standard,
+            checks_given: [0; 2],
+            pockets: [0; 13],
+            promoted: 0,
             pos_key: 0,
+            pawn_key: 0,
+            fen_ply: 0,
             num_pieces: [0; 13],
             num_big_pieces: [0; 2],
             num_major_pieces: [0; 2],
@@ -102,9 +163,12 @@ impl Board {
             history: vec![],
             sq120_to_sq64,
             sq64_to_sq120,
-            piece_list: [[0; 18]; 13],
-            hasher: BoardHasher::new(),
+            piece_list: [[0; 64]; 13],
+            hasher,
             pvtable: PVTable::new(),
+            pawn_table: PawnTable::new(),
+            eval_weights: EvalWeights::default(),
+            nnue: None,
             pvarray: [GameMove::new(0,0,0,0,0); 64],
             search_history: [[0; 64]; 13],
             search_killers: [[0; 64]; 2]
@@ -156,8 +220,15 @@ impl Board {
         self.history_ply = 0;
 
         self.castle_perm = 0;
+        self.castle_rooks = [H1, A1, H8, A8];
+        self.castle_perm_mask = [15; 120];
+        self.checks_given = [0; 2];
+        self.pockets = [0; 13];
+        self.promoted = 0;
 
         self.pos_key = 0;
+        self.pawn_key = 0;
+        self.fen_ply = 0;
     }
 
     /// Updates the rest of the board's state with regards to pieces to match the current piece list
@@ -197,6 +268,39 @@ impl Board {
         }
     }
 
+    /// Safely sets up the board from a FEN string, checking its format before parsing it and
+    /// rejecting positions move generation can't handle: an en passant square without a pawn
+    /// that just double pushed, or the side that just moved being in check. The board's
+    /// state is unspecified after an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `fen`: The FEN string, read for the board's current variant
+    ///
+    /// returns: Result<(), ParseFenError>
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), ParseFenError> {
+        let fen = fen.trim();
+        validate_fen(fen)?;
+        unsafe { self.parse_fen(fen) };
+        self.update_material_list();
+
+        if self.en_passant != NO_SQ {
+            let (rank, pawn_sq, pawn) = if self.side == WHITE {
+                (squares::RANK_6, self.en_passant - 10, BP)
+            } else {
+                (squares::RANK_3, self.en_passant + 10, WP)
+            };
+            if RANK_SQUARES[self.en_passant as usize] != rank || self.pieces[self.en_passant as usize] != EMPTY
+                || self.pieces[pawn_sq as usize] != pawn {
+                return Err(ParseFenError { reason: "en passant square without a double pushed pawn" });
+            }
+        }
+        if side_in_check(self, self.side ^ 1) {
+            return Err(ParseFenError { reason: "side that just moved is in check" });
+        }
+        Ok(())
+    }
+
     /// Parses a string containing a Forsyth–Edwards Notation position and sets
     /// the board's state to match the string.
     /// # Panic
@@ -242,6 +346,23 @@ impl Board {
                     c = c.add(1);
                     continue;
                 }
+                '~' => {
+                    // The piece before was promoted
+                    self.promoted |= 1 << (rank * 8 + file - 1);
+                    c = c.add(1);
+                    i += 1;
+                    continue;
+                }
+                '[' => {
+                    c = c.add(1);
+                    while *c as char != ']' {
+                        self.add_fen_holding(*c as char);
+                        c = c.add(1);
+                    }
+                    c = c.add(1);
+                    i += 1;
+                    continue;
+                }
                 _ => panic!("Inorrectly formatted string"),
             }
 
@@ -259,25 +380,26 @@ impl Board {
             i += 1;
         } // end of while
 
+        // Holdings can also be written as a ninth rank after the last slash
+        if *c.sub(1) as char == '/' {
+            while *c as char != ' ' {
+                self.add_fen_holding(*c as char);
+                c = c.add(1);
+            }
+            c = c.add(1);
+        }
+
         assert!(*c as char == 'w' || *c as char == 'b');
 
         self.side = if *c as char == 'w' { 0 } else { 1 };
 
         c = c.add(2);
 
-        // Castle permission
-        for _ in 0..4 {
-
-            if *c as char == ' ' {
-                break;
-            }
-
+        // Castle permission. Accepts standard KQkq, X-FEN and Shredder-FEN file letters
+        while *c as char != ' ' {
             match *c as char {
-                'K' => self.castle_perm |= pieces::WK_CASTLE,
-                'Q' => self.castle_perm |= pieces::WQ_CASTLE,
-                'k' => self.castle_perm |= pieces::BK_CASTLE,
-                'q' => self.castle_perm |= pieces::BQ_CASTLE,
                 '-' => self.castle_perm = 0,
+                ch @ ('K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h') => self.parse_castle_right(ch),
                 _ => break,
             }
 
@@ -304,11 +426,225 @@ impl Board {
             self.en_passant = fr2sq(file as u8, rank as u8);
         }
 
-        //self.ply =
+        // Three-check counters, halfmove clock and fullmove number
+        let mut counters: Vec<u64> = Vec::new();
+        for field in fen[c.offset_from(fen.as_ptr()) as usize..].split_whitespace().skip(1) {
+            if field.contains('+') {
+                self.parse_check_counts(field);
+            } else if let Ok(num) = field.parse::<u64>() {
+                counters.push(num);
+            }
+        }
+        self.fifty_move = counters.first().copied().unwrap_or(0);
+        let full_move = counters.get(1).copied().unwrap_or(1);
+        self.fen_ply = full_move.saturating_sub(1).saturating_mul(2).saturating_add(self.side as u64);
+
+        self.pos_key = self.compute_pos_key();
+        self.pawn_key = self.compute_pawn_key();
+        if let Some(nnue) = self.nnue.as_mut() {
+            nnue.reset();
+        }
+    }
+
+    /// Generates the position key from scratch, as opposed to the incremental updates made
+    /// while moves are made and undone
+    pub fn compute_pos_key(&self) -> u64 {
+        let mut key = self.hasher.generate_key(self.pieces, self.side, self.en_passant, self.castle_perm);
+        for side in [WHITE_S, BLACK_S] {
+            key ^= self.hasher.check_keys[side][self.checks_given[side] as usize];
+        }
+        for pce in WP..=BK {
+            key ^= self.hasher.pocket_keys[pce as usize][self.pockets[pce as usize].min(63) as usize];
+        }
+        for sq64 in 0..64 {
+            if self.promoted & 1 << sq64 != 0 {
+                key ^= self.hasher.promoted_keys[sq64];
+            }
+        }
+        key
+    }
+
+    /// Generates the pawn key from scratch. It hashes the pawns with the same keys as the
+    /// position key, so pawn structures can be cached however the other pieces stand.
+    pub fn compute_pawn_key(&self) -> u64 {
+        let mut key = 0;
+        for (sq, pce) in self.pieces.iter().enumerate() {
+            if *pce == WP || *pce == BP {
+                key ^= self.hasher.piece_keys[*pce as usize][sq];
+            }
+        }
+        key
+    }
+
+    /// Adds a piece from a FEN holdings field to its side's pocket. The counts are hashed
+    /// once the rest of the key has been generated.
+    fn add_fen_holding(&mut self, ch: char) {
+        match PIECE_CHARS.iter().position(|p| *p == ch) {
+            Some(pce) if pce != EMPTY as usize => self.pockets[pce] += 1,
+            _ => panic!("Incorrectly formatted holdings"),
+        }
+    }
+
+    /// Reads a three-check counter field, either the remaining checks as in `3+3` or the
+    /// checks already given as in `+0+0`
+    fn parse_check_counts(&mut self, field: &str) {
+        let (given, counts) = match field.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, field),
+        };
+        if let Some((white, black)) = counts.split_once('+') {
+            for (side, count) in [white, black].iter().enumerate() {
+                let count = count.parse::<u8>().unwrap_or(0).min(CHECKS_TO_WIN);
+                self.checks_given[side] = if given { count } else { CHECKS_TO_WIN - count };
+            }
+        }
+    }
+
+    /// Adds the castle right for a single character of a FEN castling field. Standard `KQkq`
+    /// letters (also used by X-FEN) refer to the outermost rook on that side of the king, while
+    /// Shredder-FEN and X-FEN file letters give the rook's file directly so that any Chess960
+    /// starting position can be described.
+    ///
+    /// # Arguments
+    ///
+    /// * `ch`: The castling field character. Uppercase is white, lowercase is black
+    ///
+    /// returns: ()
+    fn parse_castle_right(&mut self, ch: char) {
+        let side = if ch.is_ascii_uppercase() { WHITE } else { BLACK };
+        let rank = if side == WHITE { RANK_1 } else { RANK_8 };
+        let (king, rook) = if side == WHITE { (WK, WR) } else { (BK, BR) };
+        let piece_on = |file: u8| self.pieces[fr2sq(file, rank) as usize];
+
+        let king_file = match (FILE_A..=FILE_H).find(|f| piece_on(*f) == king) {
+            Some(f) => f,
+            None => return,
+        };
+
+        let rook_file = match ch.to_ascii_lowercase() {
+            'k' => (king_file + 1..=FILE_H).rev().find(|f| piece_on(*f) == rook),
+            'q' => (FILE_A..king_file).find(|f| piece_on(*f) == rook),
+            f => Some(f as u8 - b'a').filter(|f| *f != king_file && piece_on(*f) == rook),
+        };
+
+        let rook_file = match rook_file {
+            Some(f) => f,
+            None => return,
+        };
+
+        let index = side as usize * 2 + if rook_file > king_file { 0 } else { 1 };
+        let rook_sq = fr2sq(rook_file, rank);
+
+        self.castle_perm |= CASTLE_RIGHTS[index];
+        self.castle_rooks[index] = rook_sq;
+        self.castle_perm_mask[rook_sq as usize] &= !CASTLE_RIGHTS[index];
+        self.castle_perm_mask[fr2sq(king_file, rank) as usize] &= !CASTLE_RIGHTS[index];
+    }
+
+    /// Returns the square the rook starts on for a castling move
+    #[inline(always)]
+    pub fn castle_rook_square(&self, mov: GameMove) -> u8 {
+        self.castle_rooks[castle_index(mov.destination())]
+    }
 
-        self.pos_key =
-            self.hasher
-                .generate_key(self.pieces, self.side, self.en_passant, self.castle_perm);
+    /// The move number as written in a FEN, starting from 1 and going up after black moves
+    pub fn full_move(&self) -> u32 {
+        (self.fen_ply.saturating_add(self.history_ply) / 2 + 1) as u32
+    }
+
+    /// Writes the position as a Forsyth–Edwards Notation string. Castle rights use X-FEN, where
+    /// a file letter is only given when the rook isn't the outermost one, which never happens
+    /// in standard chess.
+    pub fn to_fen(&self) -> String {
+        self.fen_string(false)
+    }
+
+    /// Writes the position as a Shredder-FEN string, which always gives castle rights
+    /// as the files of the castling rooks
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen_string(true)
+    }
+
+    fn fen_string(&self, shredder: bool) -> String {
+        let mut fen = String::new();
+
+        for rank in (RANK_1..=RANK_8).rev() {
+            let mut empty = 0;
+            for file in FILE_A..=FILE_H {
+                let piece = self.pieces[fr2sq(file, rank) as usize];
+                if piece == EMPTY {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(PIECE_CHARS[piece as usize]);
+                if self.promoted & 1 << (rank * 8 + file) != 0 {
+                    fen.push('~');
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank != RANK_1 {
+                fen.push('/');
+            }
+        }
+
+        if self.variant.has_drops() {
+            fen.push('[');
+            for pce in [WQ, WR, WB, WN, WP, BQ, BR, BB, BN, BP] {
+                for _ in 0..self.pockets[pce as usize] {
+                    fen.push(PIECE_CHARS[pce as usize]);
+                }
+            }
+            fen.push(']');
+        }
+
+        fen.push_str(if self.side == WHITE { " w " } else { " b " });
+
+        if self.castle_perm == 0 {
+            fen.push('-');
+        }
+        for (index, right) in CASTLE_RIGHTS.iter().enumerate() {
+            if self.castle_perm & right == 0 {
+                continue;
+            }
+            let rook_sq = self.castle_rooks[index];
+            let rank = RANK_SQUARES[rook_sq as usize];
+            let rook = self.pieces[rook_sq as usize];
+            let rook_file = FILE_SQUARES[rook_sq as usize];
+            let outermost = !(FILE_A..=FILE_H)
+                .filter(|f| if index % 2 == 0 { *f > rook_file } else { *f < rook_file })
+                .any(|f| self.pieces[fr2sq(f, rank) as usize] == rook);
+
+            let ch = if shredder || !outermost {
+                (b'A' + rook_file) as char
+            } else if index % 2 == 0 {
+                'K'
+            } else {
+                'Q'
+            };
+            fen.push(if index < 2 { ch } else { ch.to_ascii_lowercase() });
+        }
+
+        if self.en_passant == NO_SQ {
+            fen.push_str(" -");
+        } else {
+            fen.push(' ');
+            fen.push((b'a' + FILE_SQUARES[self.en_passant as usize]) as char);
+            fen.push((b'1' + RANK_SQUARES[self.en_passant as usize]) as char);
+        }
+
+        if self.variant == Variant::ThreeCheck {
+            fen.push_str(&format!(" {}+{}", CHECKS_TO_WIN - self.checks_given[WHITE_S],
+                                  CHECKS_TO_WIN - self.checks_given[BLACK_S]));
+        }
+
+        fen.push_str(&format!(" {} {}", self.fifty_move, self.full_move()));
+        fen
     }
 
     // Functions for making a move on the board
@@ -318,6 +654,11 @@ impl Board {
         self.pos_key ^= self.hasher.piece_keys[pce as usize][sq as usize];
     }
 
+    #[inline(always)]
+    pub fn hash_pawn(&mut self, pce: u8, sq: u8) {
+        self.pawn_key ^= self.hasher.piece_keys[pce as usize][sq as usize];
+    }
+
     #[inline(always)]
     pub fn hash_castle(&mut self) {
         self.pos_key ^= self.hasher.castle_keys[self.castle_perm as usize];
@@ -328,6 +669,57 @@ impl Board {
         self.pos_key ^= self.hasher.side_key;
     }
 
+    #[inline(always)]
+    pub fn hash_checks(&mut self, side: u8) {
+        self.pos_key ^= self.hasher.check_keys[side as usize][self.checks_given[side as usize] as usize];
+    }
+
+    #[inline(always)]
+    pub fn hash_pocket(&mut self, pce: u8) {
+        self.pos_key ^= self.hasher.pocket_keys[pce as usize][self.pockets[pce as usize].min(63) as usize];
+    }
+
+    /// Replaces the mask of promoted pieces, hashing each square whose mark changed
+    #[inline]
+    pub fn set_promoted(&mut self, promoted: u64) {
+        let mut changed = self.promoted ^ promoted;
+        while changed != 0 {
+            self.pos_key ^= self.hasher.promoted_keys[changed.trailing_zeros() as usize];
+            changed &= changed - 1;
+        }
+        self.promoted = promoted;
+    }
+
+    /// Puts a piece into its side's pocket so that it can be dropped later. In bughouse this
+    /// is how a piece captured on the partner's board is handed over.
+    ///
+    /// # Arguments
+    ///
+    /// * `pce`: The piece, colored for the side that will drop it
+    ///
+    /// returns: ()
+    #[inline]
+    pub fn add_to_pocket(&mut self, pce: u8) {
+        self.hash_pocket(pce);
+        self.pockets[pce as usize] += 1;
+        self.hash_pocket(pce);
+    }
+
+    /// Takes a piece out of its side's pocket
+    #[inline]
+    pub fn remove_from_pocket(&mut self, pce: u8) {
+        self.hash_pocket(pce);
+        self.pockets[pce as usize] -= 1;
+        self.hash_pocket(pce);
+    }
+
+    /// Returns the piece the last move captured for a drop variant hand: the captured piece,
+    /// turned back into a pawn if it had been promoted. It is colored for the side that was
+    /// captured, which in bughouse is the color the capturer's partner plays.
+    pub fn last_hand_piece(&self) -> Option<u8> {
+        self.history.last().map(|past| past.hand_piece).filter(|pce| *pce != EMPTY)
+    }
+
     #[inline(always)]
     pub fn hash_en_passant(&mut self) {
         self.pos_key ^= self.hasher.piece_keys[EMPTY as usize][self.en_passant as usize];
@@ -348,6 +740,10 @@ impl Board {
         let mut t_pce_num = -1;
 
         self.hash_piece(self.pieces[sq as usize], sq);
+        let sq64 = self.sq64(sq);
+        if let Some(nnue) = self.nnue.as_mut() {
+            nnue.clear_piece(pce as u8, sq64);
+        }
 
         self.pieces[sq as usize] = EMPTY;
         self.material[col] -= VALUE[pce];
@@ -360,6 +756,7 @@ impl Board {
                 self.num_minor_pieces[col] -= 1;
             }
         } else {
+            self.hash_pawn(pce as u8, sq);
             self.bitboards[col].clear_bit(self.sq64(sq));
             self.bitboards[BOTH as usize].clear_bit(self.sq64(sq));
         }
@@ -378,6 +775,9 @@ impl Board {
         self.num_pieces[pce] -= 1;
         self.piece_list[pce][t_pce_num as usize] =
             self.piece_list[pce][self.num_pieces[pce] as usize]; // Replace the removed piece with the last piece in the list, after decrementing the max index
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_pieces(self), "clear_piece");
     }
 
     /// Adds a piece to the board state
@@ -393,6 +793,10 @@ impl Board {
     pub fn add_piece(&mut self, sq: u8, pce: u8) {
         let col = PIECE_COLOR[pce as usize] as usize;
         self.hash_piece(pce, sq);
+        let sq64 = self.sq64(sq);
+        if let Some(nnue) = self.nnue.as_mut() {
+            nnue.add_piece(pce, sq64);
+        }
 
         self.pieces[sq as usize] = pce;
 
@@ -406,12 +810,16 @@ impl Board {
                 self.num_minor_pieces[col] += 1;
             }
         } else {
+            self.hash_pawn(pce, sq);
             self.bitboards[col].set_bit(self.sq64(sq));
             self.bitboards[BOTH as usize].set_bit(self.sq64(sq));
         }
         self.material[col] += VALUE[t_pce];
         self.piece_list[t_pce][self.num_pieces[t_pce] as usize] = sq;
         self.num_pieces[t_pce] += 1;
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_pieces(self), "add_piece");
     }
 
     /// Moves a piece from one square on the board to another square on the board.
@@ -441,23 +849,44 @@ impl Board {
         self.hash_piece(pce, to);
         self.pieces[to_idx] = pce;
 
+        let (from64, to64) = (self.sq64(from), self.sq64(to));
+        if let Some(nnue) = self.nnue.as_mut() {
+            nnue.move_piece(pce, from64, to64);
+        }
+
         if !BIG_PIECE[pce as usize] {
+            self.hash_pawn(pce, from);
+            self.hash_pawn(pce, to);
             self.bitboards[col].move_bit(self.sq64(from), self.sq64(to));
             self.bitboards[BOTH as usize].move_bit(self.sq64(from), self.sq64(to));
         }
 
-        for pces in self.piece_list.iter_mut() {
-            for pce in pces.iter_mut() {
-                if *pce == from {
-                    *pce = to;
-                    break;
-                }
+        let t_pce = pce as usize;
+        for sq in self.piece_list[t_pce][..self.num_pieces[t_pce] as usize].iter_mut() {
+            if *sq == from {
+                *sq = to;
+                break;
             }
         }
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_pieces(self), "move_piece");
     }
 
+    /// Takes back the last move made
     #[inline]
     pub fn undo_move(&mut self) {
+        // The network's accumulator is put back as it was rather than updated piece by piece
+        let nnue = self.nnue.take();
+        self.take_back_move();
+        self.nnue = nnue.map(|mut nnue| {
+            nnue.pop();
+            nnue
+        });
+    }
+
+    #[inline]
+    fn take_back_move(&mut self) {
         self.history_ply -= 1;
         self.ply -= 1;
 
@@ -487,6 +916,33 @@ impl Board {
         self.side ^= 1;
         self.hash_side();
 
+        if self.checks_given != past_move.checks_given {
+            self.hash_checks(WHITE);
+            self.hash_checks(BLACK);
+            self.checks_given = past_move.checks_given;
+            self.hash_checks(WHITE);
+            self.hash_checks(BLACK);
+        }
+        self.king_sq = past_move.king_sq;
+
+        if past_move.exploded[EXPLOSION_DIR.len()] != EMPTY {
+            self.restore_explosion(to, past_move.exploded);
+        }
+
+        if past_move.game_move.is_drop() {
+            self.clear_piece(to);
+            self.add_to_pocket(past_move.game_move.dropped_piece());
+
+            #[cfg(feature = "invariants")]
+            assert_invariants(validate_board(self), "undo_move");
+            return;
+        }
+
+        self.set_promoted(past_move.promoted);
+        if self.variant == Variant::Crazyhouse && past_move.hand_piece != EMPTY {
+            self.remove_from_pocket(FLIP_COLOR[past_move.hand_piece as usize]);
+        }
+
         if past_move.game_move.is_en_passant() {
             if self.side == WHITE {
                 self.add_piece(to - 10, BP);
@@ -494,19 +950,14 @@ impl Board {
                 self.add_piece(to + 10, WP);
             }
         } else if past_move.game_move.is_castle_move() {
-            match to {
-                C1 => self.move_piece(D1, A1),
-                G1 => self.move_piece(F1, H1),
-                C8 => self.move_piece(D8, A8),
-                G8 => self.move_piece(F8, H8),
-                _ => panic!("Invalid castling move"),
-            }
+            self.clear_piece(CASTLE_ROOK_TO[castle_index(to)]);
         }
 
         self.move_piece(to, from);
 
-        if piece_is_king(self.pieces[from as usize]) {
-            self.king_sq[self.side as usize] = from;
+        if past_move.game_move.is_castle_move() {
+            let rook = if self.side == WHITE { WR } else { BR };
+            self.add_piece(self.castle_rook_square(past_move.game_move), rook);
         }
 
         let captured = past_move.game_move.capture();
@@ -527,6 +978,9 @@ impl Board {
                 },
             );
         }
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_board(self), "undo_move");
     }
 
     ///
@@ -549,6 +1003,9 @@ impl Board {
         let side = self.side;
 
         let his_pos_key = self.pos_key;
+        if let Some(nnue) = self.nnue.as_mut() {
+            nnue.push();
+        }
 
         // Handle en passant capture
         if mov.is_en_passant() {
@@ -558,13 +1015,9 @@ impl Board {
                 self.clear_piece(to + 10);
             }
         } else if mov.is_castle_move() {
-            match to {
-                C1 => self.move_piece(A1, D1), // Moving the rook as part of castling
-                C8 => self.move_piece(A8, D8),
-                G1 => self.move_piece(H1, F1),
-                G8 => self.move_piece(H8, F8),
-                _ => panic!("Invalid castling move"),
-            }
+            // The rook is lifted off the board and put back once the king has moved since
+            // in Chess960 the king and rook can land on each other's starting squares
+            self.clear_piece(self.castle_rook_square(mov));
         }
 
         if self.en_passant != NO_SQ {
@@ -579,19 +1032,31 @@ impl Board {
             fifty_move: self.fifty_move,
             en_passant: self.en_passant,
             castle_perm: self.castle_perm,
+            checks_given: self.checks_given,
+            exploded: [EMPTY; 9],
+            promoted: self.promoted,
+            hand_piece: EMPTY,
+            king_sq: self.king_sq,
         });
 
-        self.castle_perm &= CASTLE_PERM[from as usize];
-        self.castle_perm &= CASTLE_PERM[to as usize];
+        self.castle_perm &= self.castle_perm_mask[from as usize];
+        self.castle_perm &= self.castle_perm_mask[to as usize];
         self.en_passant = NO_SQ;
 
         self.hash_castle();
 
         let captured = mov.capture();
 
+        if self.variant.has_drops() && (captured != EMPTY || mov.is_en_passant()) {
+            self.take_in_hand(mov);
+        }
+
         if captured != EMPTY {
             self.clear_piece(to);
             self.fifty_move = 0;
+            if piece_is_king(captured) {
+                self.find_remaining_king(captured);
+            }
         } else {
             self.fifty_move += 1;
         }
@@ -610,7 +1075,16 @@ impl Board {
             }
         }
 
-        self.move_piece(from, to);
+        if mov.is_drop() {
+            self.remove_from_pocket(mov.dropped_piece());
+            self.add_piece(to, mov.dropped_piece());
+        } else {
+            self.move_piece(from, to);
+        }
+
+        if mov.is_castle_move() {
+            self.add_piece(CASTLE_ROOK_TO[castle_index(to)], if side == WHITE { WR } else { BR });
+        }
 
         let promoted_pce = mov.promoted_piece();
 
@@ -619,20 +1093,189 @@ impl Board {
             self.add_piece(to, promoted_pce);
         }
 
+        if self.promoted != 0 || (promoted_pce != EMPTY && self.variant.has_drops()) {
+            let (from64, to64) = (self.sq64(from), self.sq64(to));
+            let mut promoted = self.promoted;
+            if promoted & 1 << from64 != 0 && !mov.is_drop() {
+                promoted ^= 1 << from64 | 1 << to64;
+            }
+            if promoted_pce != EMPTY {
+                promoted |= 1 << to64;
+            }
+            self.set_promoted(promoted);
+        }
+
         if piece_is_king(self.pieces[to as usize]) {
             self.king_sq[self.side as usize] = to;
         }
 
+        if self.variant == Variant::Atomic && (captured != EMPTY || mov.is_en_passant()) {
+            let exploded = self.explode(to);
+            if let Some(past_move) = self.history.last_mut() {
+                past_move.exploded = exploded;
+            }
+        }
+
         self.side ^= 1;
         self.hash_side();
 
-        if square_is_attacked(self.king_sq[side as usize], self.side, &self.pieces) {
+        if move_was_illegal(self, side) {
             self.undo_move();
             return false;
         }
 
+        if self.variant == Variant::ThreeCheck && side_in_check(self, self.side) {
+            self.hash_checks(side);
+            self.checks_given[side as usize] += 1;
+            self.hash_checks(side);
+        }
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_board(self), "make_move");
         true
     }
+
+    /// Passes the turn to the other side without moving a piece, for null move pruning. Any
+    /// en passant square is cleared. Must be taken back with `undo_null_move`.
+    #[inline]
+    pub fn make_null_move(&mut self) {
+        self.history.push(PastMove {
+            pos_key: self.pos_key,
+            game_move: GameMove::new(0, 0, 0, 0, 0),
+            fifty_move: self.fifty_move,
+            en_passant: self.en_passant,
+            castle_perm: self.castle_perm,
+            checks_given: self.checks_given,
+            exploded: [EMPTY; 9],
+            promoted: self.promoted,
+            hand_piece: EMPTY,
+            king_sq: self.king_sq,
+        });
+
+        if self.en_passant != NO_SQ {
+            self.hash_en_passant();
+            self.en_passant = NO_SQ;
+        }
+
+        self.side ^= 1;
+        self.hash_side();
+        self.history_ply += 1;
+        self.ply += 1;
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_board(self), "make_null_move");
+    }
+
+    /// Takes back a move made with `make_null_move`
+    #[inline]
+    pub fn undo_null_move(&mut self) {
+        self.history_ply -= 1;
+        self.ply -= 1;
+
+        let past_move = match self.history.pop() {
+            Some(m) => m,
+            None => panic!("Undo was called with no previous moves"),
+        };
+
+        self.side ^= 1;
+        self.hash_side();
+
+        self.en_passant = past_move.en_passant;
+        if self.en_passant != NO_SQ {
+            self.hash_en_passant();
+        }
+
+        #[cfg(feature = "invariants")]
+        assert_invariants(validate_board(self), "undo_null_move");
+    }
+
+    /// Records the piece a capture wins for a drop variant hand and clears the captured
+    /// square's promotion mark. In crazyhouse the piece goes straight into the mover's pocket,
+    /// in bughouse it is left for the partner's board to pick up with `last_hand_piece`.
+    fn take_in_hand(&mut self, mov: GameMove) {
+        let to = mov.destination();
+        let to64 = self.sq64(to);
+        let captured = if mov.is_en_passant() {
+            if self.side == WHITE { BP } else { WP }
+        } else if self.promoted & 1 << to64 != 0 {
+            self.set_promoted(self.promoted & !(1 << to64));
+            if self.side == WHITE { BP } else { WP }
+        } else {
+            mov.capture()
+        };
+
+        if let Some(past_move) = self.history.last_mut() {
+            past_move.hand_piece = captured;
+        }
+        if self.variant == Variant::Crazyhouse {
+            self.add_to_pocket(FLIP_COLOR[captured as usize]);
+        }
+    }
+
+    /// Removes the capturing piece and every piece other than pawns next to the capture
+    /// square, as happens after every capture in atomic chess
+    ///
+    /// # Arguments
+    ///
+    /// * `center`: The square the capture happened on
+    ///
+    /// returns: [u8; 9] The exploded pieces, in EXPLOSION_DIR order followed by the capturer
+    fn explode(&mut self, center: u8) -> [u8; 9] {
+        let mut exploded = [EMPTY; 9];
+        let mut castle_mask = self.castle_perm_mask[center as usize];
+
+        for (i, dir) in EXPLOSION_DIR.iter().enumerate() {
+            let sq = (center as i32 + dir) as u8;
+            let pce = self.pieces[sq as usize];
+            if pce != EMPTY && pce != squares::OFFBOARD && !piece_is_pawn(pce) {
+                exploded[i] = pce;
+                self.clear_piece(sq);
+                castle_mask &= self.castle_perm_mask[sq as usize];
+            }
+        }
+
+        exploded[EXPLOSION_DIR.len()] = self.pieces[center as usize];
+        self.clear_piece(center);
+        self.find_remaining_king(WK);
+        self.find_remaining_king(BK);
+
+        self.hash_castle();
+        self.castle_perm &= castle_mask;
+        self.hash_castle();
+        exploded
+    }
+
+    /// Points the king square at another king of the same color if the one it pointed at was
+    /// removed. Only positions set up with several kings of one color can have one left.
+    fn find_remaining_king(&mut self, king: u8) {
+        let side = PIECE_COLOR[king as usize] as usize;
+        if self.num_pieces[king as usize] > 0 && self.pieces[self.king_sq[side] as usize] != king {
+            self.king_sq[side] = self.piece_list[king as usize][0];
+        }
+    }
+
+    /// Puts back the pieces removed by `explode`. Castle permissions are restored separately
+    fn restore_explosion(&mut self, center: u8, exploded: [u8; 9]) {
+        for (i, pce) in exploded.iter().enumerate() {
+            if *pce == EMPTY {
+                continue;
+            }
+            let sq = if i == EXPLOSION_DIR.len() { center } else { (center as i32 + EXPLOSION_DIR[i]) as u8 };
+            self.add_piece(sq, *pce);
+        }
+    }
+}
+
+/// Returns the index into the castling tables for a castling move's king destination
+///
+/// # Panic
+/// Will panic if the square isn't a castling destination
+#[inline(always)]
+pub fn castle_index(king_to: u8) -> usize {
+    match CASTLE_KING_TO.iter().position(|sq| *sq == king_to) {
+        Some(i) => i,
+        None => panic!("Invalid castling move"),
+    }
 }
 
 /// Prints the board
@@ -658,119 +1301,13 @@ impl std::fmt::Display for Board {
     }
 }
 
-/// Giant function to check if there is anything obviously wrong with the board's state
-/// TODO: need to add an assert for a valid hash key
+/// Checks if there is anything wrong with the board's state, see `validate_board`
 /// # Panic
-/// Will panic if any of the board's state seems to be incorrect
+/// Will panic with the broken invariant if any of the board's state seems to be incorrect
 pub fn check_board(board: &Board) -> bool {
-    let mut num_pieces = [0; 13];
-    let mut big_pieces = [0; 2];
-    let mut major_pieces = [0; 2];
-    let mut minor_pieces = [0; 2];
-    let mut material = [0; 2];
-
-    let mut pawns = [BitBoard::new(0); 3];
-
-    pawns[pieces::WHITE_S] = board.bitboards[pieces::WHITE_S];
-    pawns[pieces::BLACK_S] = board.bitboards[pieces::BLACK_S];
-    pawns[pieces::BOTH_S] = board.bitboards[pieces::BOTH_S];
-
-    // check piece list
-
-    for piece in pieces::WP..=pieces::BK {
-        for piece_num in 0..num_pieces[piece as usize] {
-            let sq120 = board.piece_list[piece as usize][piece_num] as usize;
-            assert_eq!(board.pieces[sq120], piece);
-        }
-    }
-
-    // check piece counts
-
-    for sq64 in 0..64 {
-        let sq120 = board.sq120(sq64) as usize;
-        let piece = board.pieces[sq120] as usize;
-        if piece as u8 != pieces::EMPTY {
-            num_pieces[piece] += 1;
-            let color = pieces::PIECE_COLOR[piece] as usize;
-            if pieces::BIG_PIECE[piece] {
-                big_pieces[color] += 1;
-            }
-            if pieces::MAJOR_PIECE[piece] {
-                major_pieces[color] += 1;
-            }
-            if pieces::MINOR_PIECE[piece] {
-                minor_pieces[color] += 1;
-            }
-
-            material[color] += pieces::VALUE[piece];
-        }
-    }
-
-    // check bitboards
-
-    let mut pcount = pawns[pieces::WHITE_S].count_bits();
-    assert_eq!(pcount, board.num_pieces[pieces::WP as usize]);
-    pcount = pawns[pieces::BLACK_S].count_bits();
-    assert_eq!(pcount, board.num_pieces[pieces::BP as usize]);
-    pcount = pawns[pieces::BOTH_S].count_bits();
-    assert_eq!(
-        pcount,
-        board.num_pieces[pieces::BP as usize] + board.num_pieces[pieces::WP as usize]
-    );
-
-    // check bitboards squares
-
-    while pawns[pieces::WHITE_S].board > 0 {
-        let sq64 = pawns[pieces::WHITE_S].pop_bit();
-        assert_eq!(board.pieces[board.sq120(sq64) as usize], pieces::WP);
-    }
-
-    while pawns[pieces::BLACK_S].board > 0 {
-        let sq64 = pawns[pieces::BLACK_S].pop_bit();
-        assert_eq!(board.pieces[board.sq120(sq64) as usize], pieces::BP);
-    }
-
-    while pawns[pieces::BOTH_S].board > 0 {
-        let sq64 = pawns[pieces::BOTH_S].pop_bit();
-        assert!(
-            board.pieces[board.sq120(sq64) as usize] == pieces::BP
-                || board.pieces[board.sq120(sq64) as usize] == pieces::WP
-        );
+    if let Err(violation) = validate_board(board) {
+        panic!("{}", violation);
     }
-
-    assert!(
-        material[pieces::WHITE_S] == board.material[pieces::WHITE_S]
-            && material[pieces::BLACK_S] == board.material[pieces::BLACK_S]
-    );
-    assert!(
-        minor_pieces[pieces::WHITE_S] == board.num_minor_pieces[pieces::WHITE_S]
-            && minor_pieces[pieces::BLACK_S] == board.num_minor_pieces[pieces::BLACK_S]
-    );
-    assert!(
-        major_pieces[pieces::WHITE_S] == board.num_major_pieces[pieces::WHITE_S]
-            && major_pieces[pieces::BLACK_S] == board.num_major_pieces[pieces::BLACK_S]
-    );
-    assert!(
-        big_pieces[pieces::WHITE_S] == board.num_big_pieces[pieces::WHITE_S]
-            && big_pieces[pieces::BLACK_S] == board.num_big_pieces[pieces::BLACK_S]
-    );
-
-    assert!(board.side == pieces::WHITE || board.side == pieces::BLACK);
-
-    // assert!(board.en_passant == squares::NO_SQ ||
-    //     ( board.ranks_squares[board.en_passant as usize] == squares::RANK_6 &&
-    //         board.side == pieces::WHITE) ||
-    //     (board.ranks_squares[board.en_passant as usize] == squares::RANK_3 &&
-    //         board.side == pieces::BLACK));
-
-    assert_eq!(
-        board.pieces[board.king_sq[pieces::WHITE_S] as usize],
-        pieces::WK
-    );
-    assert_eq!(
-        board.pieces[board.king_sq[pieces::BLACK_S] as usize],
-        pieces::BK
-    );
     true
 }
 
@@ -783,7 +1320,10 @@ mod test {
     use crate::constants::{pieces, squares};
     use crate::game_board::board::PastMove;
     use crate::game_board::board::{check_board, Board, GameMove};
+    use crate::moves::gamemove::MFLAG_CA;
+    use crate::moves::movegen::generate_all_moves;
     use crate::moves::validate::is_sq_on_board;
+    use crate::utils::piece_utils::piece_is_pawn;
     use crate::utils::square_utils::fr2sq;
 
     #[test]
@@ -1213,6 +1753,11 @@ mod test {
             castle_perm: board1.castle_perm,
             fifty_move: board1.fifty_move,
             pos_key: board1.pos_key,
+            checks_given: [0; 2],
+            exploded: [EMPTY; 9],
+            promoted: 0,
+            hand_piece: EMPTY,
+            king_sq: board1.king_sq,
         });
         board2.ply = 1;
         board2.history_ply = 1;
@@ -1229,6 +1774,68 @@ mod test {
         assert_eq!(board2.side, WHITE, "Did not change side");
     }
 
+    #[test]
+    fn test_to_fen() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "4k3/8/8/8/8/8/8/4K2R b K - 0 40",
+        ];
+        for fen in fens {
+            let mut board = Board::new();
+            unsafe { board.parse_fen(fen) };
+            assert_eq!(board.to_fen(), fen, "Did not write the same FEN that was parsed");
+        }
+    }
+
+    #[test]
+    fn test_parse_shredder_fen() {
+        let mut board = Board::new();
+        unsafe { board.parse_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9") };
+        board.update_material_list();
+        assert_eq!(board.castle_perm, 15, "Did not set castle rights from file letters");
+        assert_eq!(board.castle_rooks, [squares::H1, squares::F1, squares::H8, squares::F8],
+                   "Did not find the castling rooks");
+
+        board.chess960 = true;
+        assert_eq!(board.to_shredder_fen(), "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9");
+        assert_eq!(board.to_fen(), "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+                   "Did not write X-FEN letters for outermost rooks");
+    }
+
+    #[test]
+    fn test_parse_xfen_inner_rook() {
+        let mut board = Board::new();
+        unsafe { board.parse_fen("4k3/8/8/8/8/8/8/RR2K3 w B - 0 1") };
+        board.update_material_list();
+        assert_eq!(board.castle_perm, WQ_CASTLE, "Did not set queen side castle right");
+        assert_eq!(board.castle_rooks[1], squares::B1, "Did not use the rook on the given file");
+        board.chess960 = true;
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/RR2K3 w B - 0 1", "Did not write a file letter for an inner rook");
+    }
+
+    #[test]
+    fn test_chess960_castle_swap() {
+        // King and rook swap squares when castling king side
+        let fen = "4k3/8/8/8/8/8/8/5KR1 w G - 0 1";
+        let mut board = Board::new();
+        unsafe { board.parse_fen(fen) };
+        board.update_material_list();
+        let before = board.clone();
+        let mov = GameMove::new(squares::F1, squares::G1, 0, 0, MFLAG_CA);
+
+        assert!(board.make_move(mov), "Castling move was incorrectly found illegal");
+        assert_eq!(board.pieces[squares::G1 as usize], pieces::WK, "Did not move king");
+        assert_eq!(board.pieces[squares::F1 as usize], WR, "Did not move rook");
+        assert_eq!(board.king_sq[WHITE_S], squares::G1, "Did not update king square");
+        assert_eq!(board.castle_perm, 0, "Did not remove castle permission");
+
+        board.undo_move();
+        assert_eq!(board.pieces, before.pieces, "Did not restore pieces");
+        assert_eq!(board.pos_key, before.pos_key, "Did not restore position key");
+        assert_eq!(board.castle_perm, before.castle_perm, "Did not restore castle permission");
+    }
+
     #[test]
     fn test_make_move_into_check() {
         let fen1 = "rnb1kbnr/pp1p1pPp/8/2p1pPq1/1P1P4/2NP4/2P1P3/R1BQKBNR w KQkq - 2 11";
@@ -1237,6 +1844,7 @@ mod test {
         unsafe {
             board1.parse_fen(fen1);
         }
+        board1.update_material_list();
         let board2 = board1.clone();
         board1.make_move(mov);
         assert_eq!(
@@ -1248,7 +1856,7 @@ mod test {
             "Changed bitboard with invalid move"
         );
         assert_eq!(
-            board1.fifty_move, 0,
+            board1.fifty_move, board2.fifty_move,
             "Updated fifty moves with invalid move"
         );
         assert_eq!(board1.side, WHITE, "Changed side with invalid move");
@@ -1346,4 +1954,45 @@ mod test {
         assert_eq!(board1.side, WHITE, "Did not undo side");
         assert_eq!(board1.history.len(), 0, "Didn't wind back history");
     }
+
+    #[test]
+    fn test_null_move() {
+        let mut board = Board::new();
+        unsafe { board.parse_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3") };
+        board.update_material_list();
+        let key = board.pos_key;
+
+        board.make_null_move();
+        assert_eq!(board.side, WHITE, "Did not pass the turn");
+        assert_eq!(board.en_passant, NO_SQ, "Did not clear the en passant square");
+        assert_eq!(board.pos_key, board.compute_pos_key(), "Did not update the position key");
+
+        board.undo_null_move();
+        assert_eq!(board.side, BLACK, "Did not give the turn back");
+        assert_eq!(board.en_passant, fr2sq(FILE_E, RANK_3), "Did not restore the en passant square");
+        assert_eq!(board.pos_key, key, "Did not restore the position key");
+        assert_eq!(board.history.len(), 0, "Didn't wind back history");
+    }
+
+    #[test]
+    fn test_pawn_key() {
+        let mut board = Board::new();
+        board.set_fen("rnbqkbnr/pPp1pppp/8/8/3pP3/8/PP1P1PPP/RNBQKBNR b KQkq e3 0 3").unwrap();
+        let key = board.pawn_key;
+        assert_eq!(key, board.compute_pawn_key());
+
+        let mut move_list: Vec<GameMove> = Vec::new();
+        generate_all_moves(&mut board, &mut move_list);
+        for mov in move_list {
+            if board.make_move(mov) {
+                assert_eq!(board.pawn_key, board.compute_pawn_key(), "Did not update the pawn key for {}", mov);
+                let moved = board.pieces[mov.destination() as usize];
+                if !piece_is_pawn(moved) && mov.capture() == EMPTY && mov.promoted_piece() == EMPTY {
+                    assert_eq!(board.pawn_key, key, "Changed the pawn key without moving a pawn");
+                }
+                board.undo_move();
+            }
+            assert_eq!(board.pawn_key, key, "Did not restore the pawn key after {}", mov);
+        }
+    }
 }
@@ -0,0 +1,214 @@
+//! Consistency checks for the board's redundant state. Piece lists, counts, material, pawn
+//! bitboards and the position keys all duplicate what is on the squares, so a bug in make or
+//! undo move shows up as a disagreement between them.
+//!
+//! Building with the `invariants` feature runs the checks after every `make_move`,
+//! `undo_move`, `add_piece`, `clear_piece` and `move_piece`, panicking with the diagnostics
+//! of the first broken invariant.
+
+use std::error::Error;
+use std::fmt;
+use crate::constants::pieces::{*};
+use crate::constants::squares::{NO_SQ, RANK_3, RANK_6, RANK_SQUARES};
+use crate::game_board::board::Board;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Invariant {
+    PieceList,   // A piece list entry doesn't point at a square holding that piece
+    PieceCount,  // Number of a piece on the board vs num_pieces
+    BigPieces,
+    MajorPieces,
+    MinorPieces,
+    Material,
+    PawnBitboard, // Pawns on the board vs the side's pawn bitboard
+    KingSquare,   // Square of a side's only king vs king_sq
+    Side,
+    EnPassant,
+    CastlePerm,
+    PosKey,       // Key generated from scratch vs the incrementally updated pos_key
+    PawnKey,      // Same for pawn_key
+}
+
+/// Describes the first invariant found to be broken. `index` is the piece, side or square
+/// the values belong to, whichever applies to the invariant. For the side to move and the
+/// castle permissions, `expected` is the highest value allowed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InvariantViolation {
+    pub invariant: Invariant,
+    pub index: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} invariant broken at index {}: expected {}, found {}",
+               self.invariant, self.index, self.expected, self.actual)
+    }
+}
+
+impl Error for InvariantViolation {}
+
+#[inline(always)]
+fn expect(invariant: Invariant, index: usize, expected: u64, actual: u64) -> Result<(), InvariantViolation> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(InvariantViolation { invariant, index, expected, actual })
+    }
+}
+
+#[inline(always)]
+fn expect_at_most(invariant: Invariant, index: usize, bound: u64, actual: u64) -> Result<(), InvariantViolation> {
+    if actual <= bound {
+        Ok(())
+    } else {
+        Err(InvariantViolation { invariant, index, expected: bound, actual })
+    }
+}
+
+/// Checks the state that has to agree with the squares after every single piece is added,
+/// removed or moved: piece lists, counts, material, pawn bitboards and the position keys
+///
+/// # Arguments
+///
+/// * `board`: The board to check
+///
+/// returns: Result<(), InvariantViolation>
+pub fn validate_pieces(board: &Board) -> Result<(), InvariantViolation> {
+    let mut num_pieces = [0u64; 13];
+    let mut big_pieces = [0u64; 2];
+    let mut major_pieces = [0u64; 2];
+    let mut minor_pieces = [0u64; 2];
+    let mut material = [0u64; 2];
+    let mut pawns = [0u64; 3];
+
+    for sq64 in 0..64 {
+        let sq120 = board.sq120(sq64);
+        let piece = board.pieces[sq120 as usize] as usize;
+        if piece == EMPTY as usize {
+            continue;
+        }
+        let color = PIECE_COLOR[piece] as usize;
+        num_pieces[piece] += 1;
+        big_pieces[color] += BIG_PIECE[piece] as u64;
+        major_pieces[color] += MAJOR_PIECE[piece] as u64;
+        minor_pieces[color] += MINOR_PIECE[piece] as u64;
+        material[color] += VALUE[piece] as u64;
+        if !BIG_PIECE[piece] {
+            pawns[color] |= 1 << sq64;
+            pawns[BOTH_S] |= 1 << sq64;
+        }
+    }
+
+    for piece in WP..=BK {
+        let pce = piece as usize;
+        expect(Invariant::PieceCount, pce, num_pieces[pce], board.num_pieces[pce] as u64)?;
+        for sq in board.piece_list[pce][..board.num_pieces[pce] as usize].iter() {
+            expect(Invariant::PieceList, *sq as usize, piece as u64, board.pieces[*sq as usize] as u64)?;
+        }
+    }
+
+    for side in [WHITE_S, BLACK_S] {
+        expect(Invariant::BigPieces, side, big_pieces[side], board.num_big_pieces[side] as u64)?;
+        expect(Invariant::MajorPieces, side, major_pieces[side], board.num_major_pieces[side] as u64)?;
+        expect(Invariant::MinorPieces, side, minor_pieces[side], board.num_minor_pieces[side] as u64)?;
+        expect(Invariant::Material, side, material[side], board.material[side] as u64)?;
+    }
+    for side in [WHITE_S, BLACK_S, BOTH_S] {
+        expect(Invariant::PawnBitboard, side, pawns[side], board.bitboards[side].board)?;
+    }
+
+    expect(Invariant::PosKey, 0, board.compute_pos_key(), board.pos_key)?;
+    expect(Invariant::PawnKey, 0, board.compute_pawn_key(), board.pawn_key)
+}
+
+/// Checks every invariant of a position between moves. On top of `validate_pieces` this
+/// covers the king squares, the side to move, the en passant square and castle permissions.
+///
+/// # Arguments
+///
+/// * `board`: The board to check
+///
+/// returns: Result<(), InvariantViolation>
+pub fn validate_board(board: &Board) -> Result<(), InvariantViolation> {
+    expect_at_most(Invariant::Side, 0, BLACK as u64, board.side as u64)?;
+    expect_at_most(Invariant::CastlePerm, 0, 15, board.castle_perm as u64)?;
+
+    if board.en_passant != NO_SQ {
+        let rank = if board.side == WHITE { RANK_6 } else { RANK_3 };
+        expect(Invariant::EnPassant, board.en_passant as usize, rank as u64,
+               RANK_SQUARES[board.en_passant as usize] as u64)?;
+    }
+
+    validate_pieces(board)?;
+
+    // Variants can leave a side without its king, and horde can't have one
+    for (side, king) in [(WHITE_S, WK), (BLACK_S, BK)] {
+        if board.num_pieces[king as usize] == 1 {
+            let king_sq = board.piece_list[king as usize][0];
+            expect(Invariant::KingSquare, side, king_sq as u64, board.king_sq[side] as u64)?;
+        }
+    }
+    Ok(())
+}
+
+/// Panics with the diagnostics if the check failed, naming the operation that broke it
+#[cfg(feature = "invariants")]
+#[inline(always)]
+pub(crate) fn assert_invariants(result: Result<(), InvariantViolation>, operation: &str) {
+    if let Err(violation) = result {
+        panic!("Board invariant broken after {}: {}", operation, violation);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::pieces::{WHITE_S, WN};
+    use crate::constants::squares::{E4, G1};
+    use crate::game_board::board::Board;
+    use crate::game_board::invariants::{validate_board, Invariant};
+
+    fn start_board() -> Board {
+        let mut board = Board::new();
+        unsafe { board.parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1") };
+        board.update_material_list();
+        board
+    }
+
+    #[test]
+    fn test_valid_board() {
+        assert_eq!(validate_board(&start_board()), Ok(()));
+    }
+
+    #[test]
+    fn test_broken_material() {
+        let mut board = start_board();
+        board.material[WHITE_S] += 1;
+        let violation = validate_board(&board).unwrap_err();
+        assert_eq!(violation.invariant, Invariant::Material);
+        assert_eq!(violation.expected + 1, violation.actual);
+    }
+
+    #[test]
+    fn test_broken_side_and_castle_perm() {
+        let mut board = start_board();
+        board.side = 2;
+        let violation = validate_board(&board).unwrap_err();
+        assert_eq!((violation.invariant, violation.expected, violation.actual), (Invariant::Side, 1, 2));
+
+        let mut board = start_board();
+        board.castle_perm = 16;
+        let violation = validate_board(&board).unwrap_err();
+        assert_eq!((violation.invariant, violation.expected, violation.actual), (Invariant::CastlePerm, 15, 16));
+    }
+
+    #[test]
+    fn test_broken_pos_key() {
+        let mut board = start_board();
+        board.pieces[E4 as usize] = WN;
+        board.pieces[G1 as usize] = 0;
+        board.piece_list[WN as usize][1] = E4;
+        assert_eq!(validate_board(&board).unwrap_err().invariant, Invariant::PosKey);
+    }
+}
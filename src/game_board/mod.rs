@@ -1,2 +1,5 @@
 pub mod board;
 pub mod bitboard;
+pub mod invariants;
+pub mod outcome;
+pub mod attacks;
\ No newline at end of file
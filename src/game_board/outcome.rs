@@ -0,0 +1,181 @@
+//! Module for deciding whether a game has ended and how
+
+use crate::constants::pieces::{*};
+use crate::game_board::board::Board;
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::generate_all_moves;
+use crate::variants::variant::{no_moves_outcome, variant_outcome, Variant};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMove,
+    InsufficientMaterial,
+    VariantEnd, // A win or draw from a variant specific rule
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub result: GameResult,
+    pub termination: Termination,
+}
+
+impl GameResult {
+    /// Returns the result as written in PGN
+    pub fn pgn(self) -> &'static str {
+        match self {
+            GameResult::WhiteWin => "1-0",
+            GameResult::BlackWin => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// Counts how many times the current position has occurred before, only looking back as
+/// far as the last capture or pawn move
+///
+/// # Arguments
+///
+/// * `pos`: The current position
+///
+/// returns: u64
+pub fn repetition_count(pos: &Board) -> u64 {
+    let start = pos.history.len().saturating_sub(pos.fifty_move as usize);
+    pos.history[start..]
+        .iter()
+        .filter(|past| past.pos_key == pos.pos_key)
+        .count() as u64
+}
+
+/// Determines if neither side has enough material left to checkmate: bare kings, a single
+/// minor piece, or only bishops that all stand on the same square color
+///
+/// # Arguments
+///
+/// * `pos`: The current position
+///
+/// returns: bool
+pub fn is_insufficient_material(pos: &Board) -> bool {
+    if [WP, WR, WQ, BP, BR, BQ].iter().any(|pce| pos.num_pieces[*pce as usize] > 0) {
+        return false;
+    }
+
+    let knights = pos.num_pieces[WN as usize] + pos.num_pieces[BN as usize];
+    let bishops = pos.num_pieces[WB as usize] + pos.num_pieces[BB as usize];
+    if knights + bishops <= 1 {
+        return true;
+    }
+    if knights > 0 {
+        return false;
+    }
+
+    let mut square_colors = [WB, BB].iter().flat_map(|pce| {
+        pos.piece_list[*pce as usize][..pos.num_pieces[*pce as usize] as usize].iter()
+    }).map(|sq| (sq / 10 + sq % 10) % 2);
+    let first = square_colors.next();
+    square_colors.all(|color| Some(color) == first)
+}
+
+/// Determines if the game is over and returns how it ended. Checks the variant's own rules
+/// first, then checkmate and stalemate, then threefold repetition, the fifty move rule and
+/// insufficient material.
+///
+/// # Arguments
+///
+/// * `pos`: The current position. It is only used to try moves and is left unchanged
+///
+/// returns: Option<Outcome> None while the game is still in progress
+pub fn outcome(pos: &mut Board) -> Option<Outcome> {
+    if let Some(outcome) = variant_outcome(pos) {
+        return Some(outcome);
+    }
+
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    let has_legal_move = move_list.into_iter().any(|mov| {
+        let legal = pos.make_move(mov);
+        if legal {
+            pos.undo_move();
+        }
+        legal
+    });
+    if !has_legal_move {
+        return Some(no_moves_outcome(pos));
+    }
+
+    let draw = |termination| Some(Outcome { result: GameResult::Draw, termination });
+    if repetition_count(pos) >= 2 {
+        return draw(Termination::Repetition);
+    }
+    if pos.fifty_move >= 100 {
+        return draw(Termination::FiftyMove);
+    }
+    if pos.variant == Variant::Standard && is_insufficient_material(pos) {
+        return draw(Termination::InsufficientMaterial);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game_board::board::Board;
+    use crate::game_board::outcome::{is_insufficient_material, outcome, GameResult, Termination};
+    use crate::moves::gamemove::GameMove;
+    use crate::constants::squares::{*};
+
+    fn board_from(fen: &str) -> Board {
+        let mut board = Board::new();
+        unsafe { board.parse_fen(fen) };
+        board.update_material_list();
+        board
+    }
+
+    #[test]
+    fn test_checkmate() {
+        let mut board = board_from("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        let result = outcome(&mut board).expect("Did not find that the game was over");
+        assert_eq!(result.result, GameResult::BlackWin);
+        assert_eq!(result.termination, Termination::Checkmate);
+    }
+
+    #[test]
+    fn test_stalemate() {
+        let mut board = board_from("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(outcome(&mut board).unwrap().termination, Termination::Stalemate);
+    }
+
+    #[test]
+    fn test_game_in_progress() {
+        let mut board = board_from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(outcome(&mut board).is_none(), "Found the starting position to be over");
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut board = board_from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        for _ in 0..2 {
+            board.make_move(GameMove::new(G1, F3, 0, 0, 0));
+            board.make_move(GameMove::new(G8, F6, 0, 0, 0));
+            board.make_move(GameMove::new(F3, G1, 0, 0, 0));
+            board.make_move(GameMove::new(F6, G8, 0, 0, 0));
+        }
+        assert_eq!(outcome(&mut board).unwrap().termination, Termination::Repetition);
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        assert!(is_insufficient_material(&board_from("8/8/4k3/8/8/3NK3/8/8 w - - 0 1")));
+        assert!(is_insufficient_material(&board_from("8/3b4/4k3/8/8/3BK3/8/8 w - - 0 1")));
+        assert!(!is_insufficient_material(&board_from("8/2b5/4k3/8/8/3BK3/8/8 w - - 0 1")));
+        assert!(!is_insufficient_material(&board_from("8/8/4k3/8/8/3NK3/6P1/8 w - - 0 1")));
+    }
+}
@@ -2,4 +2,11 @@ pub mod game_board;
 pub mod constants;
 pub mod moves;
 pub mod utils;
-pub mod alphabeta;
\ No newline at end of file
+pub mod alphabeta;
+pub mod variants;
+pub mod perft;
+pub mod fuzz;
+pub mod evaluation;
+pub mod tuning;
+pub mod protocol;
+pub mod tournament;
@@ -1,9 +1,27 @@
-use std::io;
+use std::{env, io, process};
 use std::io::prelude::*;
+use std::sync::Arc;
+use chess::alphabeta::bench::{print_bench, BENCH_DEPTH};
+use chess::evaluation::nnue::Network;
+use chess::evaluation::trace::describe_evaluation;
 use chess::game_board::board::Board;
+use chess::protocol::{cecp, uci};
 use chess::utils::io::{parse_move, validate_move};
 
 fn main() {
+    // `bench [depth]` prints the node count signature of the search and exits
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bench") {
+        match args.get(1).map_or(Ok(BENCH_DEPTH), |depth| depth.parse()) {
+            Ok(depth) => print_bench(depth),
+            Err(_) => {
+                eprintln!("Usage: chess bench [depth]");
+                process::exit(2);
+            }
+        };
+        return;
+    }
+
     let mut board = Board::new();
     unsafe {board.parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");}
     board.update_material_list();
@@ -13,8 +31,38 @@ fn main() {
     let mut from:u8;
     let mut to:u8;
     let mut prom_char:char;
+    let mut protocol = None;
     for line in stdin.lock().lines() {
         let text = line.as_ref().expect("Problem reading from command line: {:?}").as_str();
+        // Debug commands explaining the evaluation of the current position
+        match text.trim() {
+            // A GUI starts a protocol session with its first command
+            command @ ("uci" | "xboard") => {
+                protocol = Some(command.to_string());
+                break;
+            }
+            command @ ("eval" | "eval json") => {
+                println!("{}", describe_evaluation(&mut board, command == "eval json"));
+                continue;
+            }
+            // Switches between a neural network loaded from a weight file and the handcrafted evaluation
+            "nnue off" => {
+                board.set_network(None);
+                println!("Using the handcrafted evaluation");
+                continue;
+            }
+            command if command.starts_with("nnue ") => {
+                match Network::load(command["nnue ".len()..].trim()) {
+                    Ok(network) => {
+                        board.set_network(Some(Arc::new(network)));
+                        println!("Using the network evaluation");
+                    }
+                    Err(err) => println!("{}", err),
+                }
+                continue;
+            }
+            _ => (),
+        }
         let (from, to, prom_char) = match validate_move(&board, text) {
             Ok(T) => T,
             Err(E) => {
@@ -34,4 +82,11 @@ fn main() {
         println!("{}", board);
         println!("Enter next move");
     }
+
+    // The protocol loops read the input on their own thread, so the lock above must be gone
+    match protocol.as_deref() {
+        Some("uci") => uci::run(),
+        Some("xboard") => cecp::run(),
+        _ => (),
+    }
 }
\ No newline at end of file
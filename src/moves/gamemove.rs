@@ -8,6 +8,10 @@
     0000 0000 1000 0000 0000 0000 0000 -> Pawn start 0x80000
     0000 1111 0000 0000 0000 0000 0000 -> Promoted piece >> 20 0xF
     0001 0000 0000 0000 0000 0000 0000 -> Castle 0x1000000
+  1 1110 0000 0000 0000 0000 0000 0000 -> Dropped piece >> 25 0xF
+
+    A drop move (crazyhouse and bughouse) uses the destination square as its origin too
+    and has no captured or promoted piece.
  */
 
 use crate::constants::squares::{FILE_SQUARES,RANK_SQUARES};
@@ -18,11 +22,12 @@ pub const MFLAG_PS:u32 = 0x80000; // Pawn start
 pub const MFLAG_CA:u32 = 0x1000000; // Castle
 pub const MFLAG_CAP:u32 = 0x7C000; // Capture
 pub const MFLAG_PROM:u32 = 0xF00000; // Promotion
+pub const MFLAG_DROP:u32 = 0x1E000000; // Drop
 
 #[derive(Debug, Copy, Clone)]
 pub struct GameMove {
     pub move_int: u32,
-    pub score: u8,
+    pub score: u32, // Ordering score given by move generation, highest searched first
 }
 
 impl GameMove {
@@ -36,6 +41,12 @@ impl GameMove {
         }
     }
 
+    /// Creates a move that drops a piece from the side's pocket onto an empty square
+    #[inline(always)]
+    pub fn new_drop(pce:u8, to:u8) -> GameMove {
+        GameMove::new(to, to, EMPTY, EMPTY, (pce as u32) << 25)
+    }
+
     /// Returns the starting square for the move
     #[inline(always)]
     pub fn origin(self) -> u8 { (self.move_int & 0x7F) as u8 }
@@ -63,6 +74,14 @@ impl GameMove {
     /// Returns whether or not this was a castling move
     #[inline(always)]
     pub fn is_castle_move(&self) -> bool { (self.move_int & MFLAG_CA) > 0}
+
+    /// Returns whether or not the move drops a piece from a pocket
+    #[inline(always)]
+    pub fn is_drop(&self) -> bool { (self.move_int & MFLAG_DROP) > 0}
+
+    /// Returns the piece dropped onto the board (if applicable)
+    #[inline(always)]
+    pub fn dropped_piece(self) -> u8 { (self.move_int >> 25 & 0xF) as u8 }
 }
 
 /// Prints the board
@@ -83,6 +102,10 @@ impl std::fmt::Display for GameMove {
 
         let mut output = String::from("");
 
+        if self.is_drop() {
+            const PIECES:[&str; 13] = ["", "P", "N", "B", "R", "Q", "K", "P", "N", "B", "R", "Q", "K"];
+            return write!(f, "{}@{}{}", PIECES[self.dropped_piece() as usize], end_file, end_rank);
+        }
 
         output.push_str(start_file);
         output.push_str(start_rank);
@@ -1,3 +1,4 @@
 pub mod gamemove;
 pub mod movegen;
 pub mod validate;
+pub mod see;
\ No newline at end of file
@@ -3,6 +3,7 @@ use crate::moves::gamemove::{GameMove, MFLAG_CA, MFLAG_EP, MFLAG_PS};
 use crate::moves::validate::{*};
 use crate::utils::square_utils::{fr2sq, init_file_rank_arrays};
 use crate::game_board::board::{Board, check_board};
+use crate::variants::variant::{filter_moves, variant_outcome, Variant};
 
 const MAX_POSITION_MOVES: u32 = 256;
 
@@ -100,13 +101,46 @@ fn squares_are_empty(squares: &[usize], pces: &[u8; 120]) -> bool {
     true
 }
 
+/// Ordering scores, so the search tries the principal variation move, then captures, then
+/// killer moves and then the other quiet moves by their history score
+pub const PV_MOVE_SCORE: u32 = 2_000_000;
+pub const CAPTURE_SCORE: u32 = 1_000_000;
+pub const FIRST_KILLER_SCORE: u32 = 900_000;
+pub const SECOND_KILLER_SCORE: u32 = 800_000;
+
+// Most valuable victim first, then least valuable attacker
+const VICTIM_SCORE: [u32; 13] = [0, 100, 200, 300, 400, 500, 600, 100, 200, 300, 400, 500, 600];
+
+/// Returns the capture ordering score of a piece taking a victim
 #[inline(always)]
-fn add_quiet_move(pos: &Board, mve: GameMove, list: &mut Vec<GameMove>) {
+pub fn mvv_lva(victim: u8, attacker: u8) -> u32 {
+    VICTIM_SCORE[victim as usize] + 6 - VICTIM_SCORE[attacker as usize] / 100
+}
+
+#[inline(always)]
+fn add_quiet_move(pos: &Board, mut mve: GameMove, list: &mut Vec<GameMove>) {
+    let ply = pos.ply as usize;
+    let killers = if ply < pos.search_killers[0].len() {
+        [pos.search_killers[0][ply], pos.search_killers[1][ply]]
+    } else {
+        [0; 2]
+    };
+
+    mve.score = if killers[0] == mve.move_int as u64 {
+        FIRST_KILLER_SCORE
+    } else if killers[1] == mve.move_int as u64 {
+        SECOND_KILLER_SCORE
+    } else {
+        let pce = if mve.is_drop() { mve.dropped_piece() } else { pos.pieces[mve.origin() as usize] };
+        pos.search_history[pce as usize][pos.sq64(mve.destination()) as usize].min(SECOND_KILLER_SCORE as u64 - 1) as u32
+    };
     list.push(mve);
 }
 
 #[inline(always)]
-fn add_capture_move(pos: &Board, mve: GameMove, list: &mut Vec<GameMove>) {
+fn add_capture_move(pos: &Board, mut mve: GameMove, list: &mut Vec<GameMove>) {
+    let victim = if mve.is_en_passant() { WP } else { mve.capture() };
+    mve.score = CAPTURE_SCORE + mvv_lva(victim, pos.pieces[mve.origin() as usize]);
     list.push(mve);
 }
 
@@ -116,6 +150,9 @@ fn add_wp_capture_move(pos: &Board, from: u8, to: u8, cap: u8, list: &mut Vec<Ga
         add_capture_move(pos, GameMove::new(from, to, cap, WR, 0), list);
         add_capture_move(pos, GameMove::new(from, to, cap, WB, 0), list);
         add_capture_move(pos, GameMove::new(from, to, cap, WN, 0), list);
+        if pos.variant == Variant::Antichess {
+            add_capture_move(pos, GameMove::new(from, to, cap, WK, 0), list);
+        }
     } else {
         add_capture_move(pos, GameMove::new(from, to, cap, EMPTY, 0), list);
     }
@@ -127,6 +164,9 @@ fn add_wp_move(pos: &Board, from: u8, to: u8, list: &mut Vec<GameMove>) {
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, WR, 0), list);
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, WB, 0), list);
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, WN, 0), list);
+        if pos.variant == Variant::Antichess {
+            add_quiet_move(pos, GameMove::new(from, to, EMPTY, WK, 0), list);
+        }
     } else {
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, EMPTY, 0), list);
     }
@@ -138,6 +178,9 @@ fn add_bp_capture_move(pos: &Board, from: u8, to: u8, cap: u8, list: &mut Vec<Ga
         add_capture_move(pos, GameMove::new(from, to, cap, BR, 0), list);
         add_capture_move(pos, GameMove::new(from, to, cap, BB, 0), list);
         add_capture_move(pos, GameMove::new(from, to, cap, BN, 0), list);
+        if pos.variant == Variant::Antichess {
+            add_capture_move(pos, GameMove::new(from, to, cap, BK, 0), list);
+        }
     } else {
         add_capture_move(pos, GameMove::new(from, to, cap, EMPTY, 0), list);
     }
@@ -149,6 +192,9 @@ fn add_bp_move(pos: &Board, from: u8, to: u8, list: &mut Vec<GameMove>) {
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, BR, 0), list);
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, BB, 0), list);
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, BN, 0), list);
+        if pos.variant == Variant::Antichess {
+            add_quiet_move(pos, GameMove::new(from, to, EMPTY, BK, 0), list);
+        }
     } else {
         add_quiet_move(pos, GameMove::new(from, to, EMPTY, EMPTY, 0), list);
     }
@@ -179,6 +225,10 @@ fn generate_wp_moves(pos: &Board, list: &mut Vec<GameMove>) {
                     GameMove::new(sq, sq + 20, EMPTY, EMPTY, MFLAG_PS),
                     list,
                 )
+            } else if RANK_SQUARES[sqi] == RANK_1 && pos.pieces[sqi + 20] == EMPTY {
+                // Horde pawns on the first rank can also move two squares, without
+                // allowing en passant
+                add_quiet_move(pos, GameMove::new(sq, sq + 20, EMPTY, EMPTY, 0), list)
             }
         }
 
@@ -354,14 +404,75 @@ fn generate_nonsliding_moves(pos: &Board, list: &mut Vec<GameMove>, side: u8) {
     }
 }
 
+/// Generates castling moves for the specified side. Works for both standard chess and Chess960
+/// where the king and rooks can start on any file. Every square between the king or rook and
+/// its destination has to be empty apart from the castling king and rook themselves, and
+/// the king can't start on, pass over or land on an attacked square.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `list`: The vec all moves will be added to
+/// * `side`: The side to generate moves for (0 = white, 1 = black)
+///
+/// returns: ()
 #[inline(always)]
-fn generate_castle_move(pos: &Board, side: u8, sqs: &[u8], from: u8, to: u8, list: &mut Vec<GameMove>) {
-    if !sqs.iter().any(|x| pos.pieces[*x as usize] != EMPTY) && !sqs.split_last().unwrap().1.iter().any(|x| square_is_attacked(*x, side, &pos.pieces)) {
-        add_quiet_move(
-            pos,
-            GameMove::new(from, to, EMPTY, EMPTY, MFLAG_CA),
-            list,
-        );
+fn generate_castle_moves(pos: &Board, list: &mut Vec<GameMove>, side: u8) {
+    let king_from = pos.king_sq[side as usize];
+
+    for index in (side as usize * 2)..(side as usize * 2 + 2) {
+        if pos.castle_perm & CASTLE_RIGHTS[index] == 0 {
+            continue;
+        }
+
+        let rook_from = pos.castle_rooks[index];
+        let king_to = CASTLE_KING_TO[index];
+        let rook_to = CASTLE_ROOK_TO[index];
+
+        let lowest = king_from.min(king_to).min(rook_from).min(rook_to);
+        let highest = king_from.max(king_to).max(rook_from).max(rook_to);
+        let path_clear = (lowest..=highest)
+            .all(|sq| sq == king_from || sq == rook_from || pos.pieces[sq as usize] == EMPTY);
+        if !path_clear {
+            continue;
+        }
+
+        let king_path_safe = (king_from.min(king_to)..=king_from.max(king_to))
+            .all(|sq| !square_is_attacked(sq, side ^ 1, &pos.pieces));
+        if king_path_safe {
+            add_quiet_move(
+                pos,
+                GameMove::new(king_from, king_to, EMPTY, EMPTY, MFLAG_CA),
+                list,
+            );
+        }
+    }
+}
+
+/// Generates moves that drop a piece from the side's pocket onto an empty square, for
+/// crazyhouse and bughouse. Pawns can't be dropped on the first or last rank.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `list`: The vec all moves will be added to
+/// * `side`: The side to generate moves for (0 = white, 1 = black)
+///
+/// returns: ()
+#[inline]
+fn generate_drop_moves(pos: &Board, list: &mut Vec<GameMove>, side: u8) {
+    let pawn = if side == WHITE { WP } else { BP };
+    for pce in pawn..pawn + 5 {
+        if pos.pockets[pce as usize] == 0 {
+            continue;
+        }
+        for sq64 in 0..64 {
+            let sq = pos.sq120(sq64);
+            let back_rank = RANK_SQUARES[sq as usize] == RANK_1 || RANK_SQUARES[sq as usize] == RANK_8;
+            if pos.pieces[sq as usize] == EMPTY && !(pce == pawn && back_rank) {
+                add_quiet_move(pos, GameMove::new_drop(pce, sq), list);
+            }
+        }
     }
 }
 
@@ -382,37 +493,31 @@ fn generate_castle_move(pos: &Board, side: u8, sqs: &[u8], from: u8, to: u8, lis
 ///
 /// ```
 pub fn generate_all_moves(pos: &Board, list: &mut Vec<GameMove>) {
+    if variant_outcome(pos).is_some() {
+        return;
+    }
+
     if pos.side == WHITE {
         generate_wp_moves(pos, list); // Pawns have a lot of special rules for movement, best to write specific functions
 
-        if !square_is_attacked(E1, BLACK, &pos.pieces) {
-            if pos.castle_perm & WK_CASTLE != 0 {
-                generate_castle_move(&pos, BLACK, &vec!(F1, G1), E1, G1, list);
-            }
-
-            if pos.castle_perm & WQ_CASTLE != 0  {
-                generate_castle_move(&pos, BLACK, &vec!(D1, C1, B1), E1, C1, list);
-            }
-        }
+        generate_castle_moves(pos, list, WHITE);
 
         generate_sliding_moves(pos, list, WHITE);
         generate_nonsliding_moves(pos, list, WHITE);
     } else {
         generate_bp_moves(pos, list);
 
-        if !square_is_attacked(E8, WHITE, &pos.pieces) {
-            if pos.castle_perm & BK_CASTLE != 0 {
-                generate_castle_move(&pos, WHITE, &vec!(F8, G8), E8, G8, list);
-            }
-
-            if pos.castle_perm & BQ_CASTLE != 0 {
-                generate_castle_move(&pos, WHITE, &vec!(D8, C8, B8), E8, C8, list);
-            }
-        }
+        generate_castle_moves(pos, list, BLACK);
 
         generate_sliding_moves(pos, list, BLACK);
         generate_nonsliding_moves(pos, list, BLACK);
     }
+
+    if pos.variant.has_drops() {
+        generate_drop_moves(pos, list, pos.side);
+    }
+
+    filter_moves(pos, list);
 }
 
 #[inline]
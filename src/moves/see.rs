@@ -0,0 +1,231 @@
+//! Static exchange evaluation, which works out the material won or lost by a capture once
+//! every capture back and forth on its square has been played out
+
+use crate::constants::pieces::{*};
+use crate::constants::squares::{OFFBOARD, RANK_1, RANK_8, RANK_SQUARES};
+use crate::game_board::board::Board;
+use crate::moves::gamemove::GameMove;
+use crate::utils::piece_utils::piece_is_pawn;
+
+// Longest exchange that can be played out on one square
+const MAX_EXCHANGE: usize = 32;
+
+#[inline(always)]
+fn value(pce: u8) -> i32 {
+    VALUE[pce as usize] as i32
+}
+
+/// Finds the least valuable piece of a side attacking a square. Pieces removed from `pces`
+/// during an exchange uncover the sliders behind them, so x-ray attacks are found too.
+///
+/// # Arguments
+///
+/// * `pces`: The pieces on the board, in 120 square numbering
+/// * `sq`: The 120 square board number that is attacked
+/// * `side`: The side to find an attacker for
+///
+/// returns: Option<(u8, u8)> The attacker's square and piece
+fn least_valuable_attacker(pces: &[u8; 120], sq: u8, side: u8) -> Option<(u8, u8)> {
+    let (pawn, pawn_dirs) = if side == WHITE { (WP, [-9, -11]) } else { (BP, [9, 11]) };
+    for dir in pawn_dirs {
+        let from = (sq as i32 + dir) as u8;
+        if pces[from as usize] == pawn {
+            return Some((from, pawn));
+        }
+    }
+
+    let offset = if side == WHITE { 0 } else { BP - WP };
+    let mut best: Option<(u8, u8)> = None;
+    let mut consider = |from: u8, pce: u8| {
+        if best.is_none_or(|(_, best_pce)| VALUE[pce as usize] < VALUE[best_pce as usize]) {
+            best = Some((from, pce));
+        }
+    };
+
+    for dir in PIECE_DIR[WN as usize] {
+        let from = (sq as i32 + dir) as u8;
+        if pces[from as usize] == WN + offset {
+            consider(from, WN + offset);
+        }
+    }
+
+    // Queen directions start with the four rook directions, followed by the bishop ones
+    for (i, dir) in PIECE_DIR[WQ as usize].iter().enumerate() {
+        let slider = if i < 4 { WR + offset } else { WB + offset };
+        let mut from = (sq as i32 + dir) as u8;
+        while pces[from as usize] == EMPTY {
+            from = (from as i32 + dir) as u8;
+        }
+        let pce = pces[from as usize];
+        if pce == slider || pce == WQ + offset {
+            consider(from, pce);
+        }
+    }
+
+    for dir in PIECE_DIR[WK as usize] {
+        let from = (sq as i32 + dir) as u8;
+        if pces[from as usize] == WK + offset {
+            consider(from, WK + offset);
+        }
+    }
+    best
+}
+
+/// Works out the material a move wins or loses if both sides keep capturing on its
+/// destination square with their least valuable piece, each stopping as soon as carrying on
+/// would lose more. Pins are not taken into account.
+///
+/// # Arguments
+///
+/// * `pos`: The board state the move is played on
+/// * `mov`: The move, usually a capture. A quiet move scores what is lost if it is captured.
+///
+/// returns: i32 The material balance in centipawns for the side making the move
+pub fn see(pos: &Board, mov: GameMove) -> i32 {
+    if mov.is_drop() || mov.is_castle_move() {
+        return 0;
+    }
+    let from = mov.origin();
+    let to = mov.destination();
+    let promotion_rank = RANK_SQUARES[to as usize] == RANK_1 || RANK_SQUARES[to as usize] == RANK_8;
+
+    let mut pces = pos.pieces;
+    let mut gain = [0; MAX_EXCHANGE];
+    let mut on_square = pces[from as usize];
+    let mut side = PIECE_COLOR[on_square as usize];
+
+    gain[0] = if mov.is_en_passant() {
+        let captured_sq = if side == WHITE { to - 10 } else { to + 10 };
+        pces[captured_sq as usize] = EMPTY;
+        value(WP)
+    } else {
+        value(pces[to as usize])
+    };
+    if mov.promoted_piece() != EMPTY {
+        gain[0] += value(mov.promoted_piece()) - value(on_square);
+        on_square = mov.promoted_piece();
+    }
+    pces[from as usize] = EMPTY;
+
+    let mut depth = 0;
+    while depth < MAX_EXCHANGE - 1 {
+        pces[to as usize] = on_square;
+        side ^= 1;
+        let (attacker_sq, mut attacker) = match least_valuable_attacker(&pces, to, side) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+
+        depth += 1;
+        gain[depth] = value(on_square) - gain[depth - 1];
+        if piece_is_pawn(attacker) && promotion_rank {
+            attacker = if side == WHITE { WQ } else { BQ };
+            gain[depth] += value(attacker) - value(WP);
+        }
+        pces[attacker_sq as usize] = EMPTY;
+        on_square = attacker;
+    }
+
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+    gain[0]
+}
+
+/// Finds the pieces of a side that the opponent wins material by capturing, going by static
+/// exchange evaluation. Kings are left out.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `side`: The side whose pieces might be hanging
+///
+/// returns: Vec<u8> The 120 square board numbers of the hanging pieces
+pub fn hanging_pieces(pos: &Board, side: u8) -> Vec<u8> {
+    let mut hanging = Vec::new();
+    for sq64 in 0..64 {
+        let sq = pos.sq120(sq64);
+        let pce = pos.pieces[sq as usize];
+        if pce == EMPTY || pce == OFFBOARD || PIECE_COLOR[pce as usize] != side || KING_NUMBER[pce as usize] {
+            continue;
+        }
+
+        if let Some((from, attacker)) = least_valuable_attacker(&pos.pieces, sq, side ^ 1) {
+            let promotion_rank = RANK_SQUARES[sq as usize] == RANK_1 || RANK_SQUARES[sq as usize] == RANK_8;
+            let promoted = if piece_is_pawn(attacker) && promotion_rank {
+                if side == WHITE { BQ } else { WQ }
+            } else {
+                EMPTY
+            };
+            if see(pos, GameMove::new(from, sq, pce, promoted, 0)) > 0 {
+                hanging.push(sq);
+            }
+        }
+    }
+    hanging
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::pieces::{BLACK, WHITE};
+    use crate::constants::squares::{*};
+    use crate::game_board::board::Board;
+    use crate::moves::see::{hanging_pieces, see};
+    use crate::utils::io::{parse_move, validate_move};
+
+    fn see_of(fen: &str, text: &str) -> i32 {
+        let mut board = Board::new();
+        board.set_fen(fen).unwrap();
+        let (from, to, prom) = validate_move(&board, text).unwrap();
+        let mov = parse_move(&board, from, to, prom).expect("Move is not legal");
+        see(&board, mov)
+    }
+
+    #[test]
+    fn test_undefended_pawn() {
+        assert_eq!(see_of("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+    }
+
+    #[test]
+    fn test_defended_pawn() {
+        assert_eq!(see_of("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -225);
+        assert_eq!(see_of("4k3/8/4p3/3p4/8/1B6/8/4K3 w - - 0 1", "b3d5"), -225);
+    }
+
+    #[test]
+    fn test_battery() {
+        // The queen behind the bishop recaptures, the rook behind the rook keeps the exchange going
+        assert_eq!(see_of("4k3/8/4p3/3p4/8/1B6/Q7/4K3 w - - 0 1", "b3d5"), -125);
+        assert_eq!(see_of("4k3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see_of("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), -450);
+    }
+
+    #[test]
+    fn test_en_passant() {
+        assert_eq!(see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see_of("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 0);
+    }
+
+    #[test]
+    fn test_promotion() {
+        assert_eq!(see_of("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 900);
+        assert_eq!(see_of("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), -100);
+        assert_eq!(see_of("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7a8q"), 1450);
+        assert_eq!(see_of("1r2k3/P7/8/8/8/8/8/4K3 b - - 0 1", "b8b1"), 0);
+    }
+
+    #[test]
+    fn test_king_cannot_recapture_defended_piece() {
+        assert_eq!(see_of("4k3/8/8/8/8/8/3r4/3QK3 b - - 0 1", "d2d1"), 1000 - 550);
+        assert_eq!(see_of("4k3/8/8/8/8/8/3r4/R2QK3 b - - 0 1", "d2d1"), 1000 - 550);
+    }
+
+    #[test]
+    fn test_hanging_pieces() {
+        let mut board = Board::new();
+        board.set_fen("4k3/8/4p2r/3n4/8/1B6/8/2Q1K3 w - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, BLACK), vec![H6], "The knight is defended, the rook is not");
+        assert_eq!(hanging_pieces(&board, WHITE), vec![]);
+    }
+}
@@ -0,0 +1,96 @@
+//! Move generation testing by counting the leaf nodes of the legal move tree to a fixed depth
+
+use crate::game_board::board::Board;
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::generate_all_moves;
+use crate::perft::table::PerftTable;
+
+/// Counts the legal move sequences of the given length from the position
+///
+/// # Arguments
+///
+/// * `pos`: The position to count from. It is left unchanged
+/// * `depth`: The number of plies to look ahead
+/// * `table`: An optional table to reuse counts of positions reached by transposition
+///
+/// returns: u64
+pub fn perft(pos: &mut Board, depth: u8, mut table: Option<&mut PerftTable>) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    if let Some(nodes) = table.as_ref().and_then(|t| t.probe(pos.pos_key, depth)) {
+        return nodes;
+    }
+
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+
+    let mut nodes = 0;
+    for mov in move_list {
+        if !pos.make_move(mov) {
+            continue;
+        }
+        nodes += if depth == 1 { 1 } else { perft(pos, depth - 1, table.as_deref_mut()) };
+        pos.undo_move();
+    }
+
+    if let Some(t) = table {
+        t.store(pos.pos_key, depth, nodes);
+    }
+    nodes
+}
+
+/// Runs perft separately below each legal root move, which narrows down where a move
+/// generation bug is when the totals disagree with another engine
+///
+/// # Arguments
+///
+/// * `pos`: The position to count from. It is left unchanged
+/// * `depth`: The number of plies to look ahead, including the root move
+/// * `table`: An optional table to reuse counts of positions reached by transposition
+///
+/// returns: Vec<(GameMove, u64)> Each legal root move with the count below it
+pub fn divide(pos: &mut Board, depth: u8, mut table: Option<&mut PerftTable>) -> Vec<(GameMove, u64)> {
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+
+    let mut counts = Vec::new();
+    for mov in move_list {
+        if !pos.make_move(mov) {
+            continue;
+        }
+        counts.push((mov, perft(pos, depth.saturating_sub(1), table.as_deref_mut())));
+        pos.undo_move();
+    }
+    counts
+}
+
+#[cfg(test)]
+mod test {
+    use crate::perft::counting::{divide, perft};
+    use crate::perft::suite::SuitePosition;
+    use crate::perft::table::PerftTable;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let position: SuitePosition = format!("{} ;D1 48 ;D2 2039 ;D3 97862", KIWIPETE).parse().unwrap();
+        let mut board = position.board(false).unwrap();
+        let counts = divide(&mut board, 3, None);
+        assert_eq!(counts.len(), 48);
+        assert_eq!(counts.iter().map(|(_, nodes)| nodes).sum::<u64>(), position.counts[2]);
+    }
+
+    #[test]
+    fn test_hashed_perft() {
+        let position: SuitePosition = format!("{} ;D1 48", KIWIPETE).parse().unwrap();
+        let mut board = position.board(false).unwrap();
+        let key = board.pos_key;
+        let mut table = PerftTable::new(1);
+        assert_eq!(perft(&mut board, 3, Some(&mut table)), 97862);
+        assert_eq!(perft(&mut board, 3, Some(&mut table)), 97862, "Hashed count changed on the second run");
+        assert_eq!(board.pos_key, key, "Perft did not leave the board unchanged");
+    }
+}
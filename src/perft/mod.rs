@@ -0,0 +1,4 @@
+pub mod counting;
+pub mod parallel;
+pub mod suite;
+pub mod table;
@@ -0,0 +1,204 @@
+//! Perft split across worker threads. The tree is cut at a split depth and every move
+//! sequence of that length is handed out as a separate job, so the totals are exactly the
+//! ones the serial perft would find.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use crate::game_board::board::Board;
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::generate_all_moves;
+use crate::perft::counting::perft;
+use crate::perft::table::PerftTable;
+
+/// Settings for a parallel perft run
+#[derive(Debug, Copy, Clone)]
+pub struct ParallelOptions {
+    pub threads: usize,
+    pub split_depth: u8, // Plies made before the tree is handed out to the workers
+    pub hash_mb: usize, // Total size of the workers' perft tables, 0 for none
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        ParallelOptions {
+            threads: default_threads(),
+            split_depth: 1,
+            hash_mb: 0,
+        }
+    }
+}
+
+/// Returns the number of threads the machine can run at once
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Collects every legal move sequence of the given length in move generation order
+fn collect_paths(pos: &mut Board, depth: u8, path: &mut Vec<GameMove>, paths: &mut Vec<Vec<GameMove>>) {
+    if depth == 0 {
+        paths.push(path.clone());
+        return;
+    }
+
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    for mov in move_list {
+        if !pos.make_move(mov) {
+            continue;
+        }
+        path.push(mov);
+        collect_paths(pos, depth - 1, path, paths);
+        path.pop();
+        pos.undo_move();
+    }
+}
+
+/// Runs perft below each legal root move using several threads. Each worker counts on its
+/// own copy of the board and its own perft table.
+///
+/// # Arguments
+///
+/// * `pos`: The position to count from
+/// * `depth`: The number of plies to look ahead, including the root move
+/// * `options`: Thread count, split depth and table size
+///
+/// returns: Vec<(GameMove, u64)> Each legal root move with the count below it, in move
+/// generation order
+pub fn parallel_divide(pos: &Board, depth: u8, options: ParallelOptions) -> Vec<(GameMove, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let split_depth = options.split_depth.clamp(1, depth);
+
+    // Root moves that mate or stalemate have no paths below them, so the divide starts from
+    // the legal root moves and each path is counted under the root move it starts with
+    let mut board = pos.clone();
+    let mut divided: Vec<(GameMove, u64)> = Vec::new();
+    let mut paths: Vec<(usize, Vec<GameMove>)> = Vec::new();
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(&board, &mut move_list);
+    for mov in move_list {
+        if !board.make_move(mov) {
+            continue;
+        }
+        let mut below = Vec::new();
+        collect_paths(&mut board, split_depth - 1, &mut vec![mov], &mut below);
+        paths.extend(below.into_iter().map(|path| (divided.len(), path)));
+        divided.push((mov, 0));
+        board.undo_move();
+    }
+
+    let threads = options.threads.clamp(1, paths.len().max(1));
+    let next_job = AtomicUsize::new(0);
+    let mut counts = vec![0; paths.len()];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| {
+            let paths = &paths;
+            let next_job = &next_job;
+            scope.spawn(move || {
+                let mut board = pos.clone();
+                let mut table = if options.hash_mb > 0 {
+                    Some(PerftTable::new((options.hash_mb / threads).max(1)))
+                } else {
+                    None
+                };
+                let mut results = Vec::new();
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    let path = match paths.get(job) {
+                        Some((_, path)) => path,
+                        None => break,
+                    };
+                    for mov in path {
+                        board.make_move(*mov);
+                    }
+                    results.push((job, perft(&mut board, depth - split_depth, table.as_mut())));
+                    for _ in path {
+                        board.undo_move();
+                    }
+                }
+                results
+            })
+        }).collect();
+
+        for worker in workers {
+            for (job, nodes) in worker.join().expect("Perft worker panicked") {
+                counts[job] = nodes;
+            }
+        }
+    });
+
+    for ((root, _), nodes) in paths.iter().zip(counts) {
+        divided[*root].1 += nodes;
+    }
+    divided
+}
+
+/// Counts the legal move sequences of the given length from the position using several
+/// threads
+///
+/// # Arguments
+///
+/// * `pos`: The position to count from
+/// * `depth`: The number of plies to look ahead
+/// * `options`: Thread count, split depth and table size
+///
+/// returns: u64
+pub fn parallel_perft(pos: &Board, depth: u8, options: ParallelOptions) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    parallel_divide(pos, depth, options).iter().map(|(_, nodes)| nodes).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::moves::gamemove::GameMove;
+    use crate::perft::counting::{divide, perft};
+    use crate::perft::parallel::{parallel_divide, parallel_perft, ParallelOptions};
+    use crate::perft::suite::SuitePosition;
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let position: SuitePosition = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 ;D1 48"
+            .parse().unwrap();
+        let mut board = position.board(false).unwrap();
+        // Making and undoing moves reorders the piece lists, so compare in a fixed order
+        let sorted = |mut counts: Vec<(GameMove, u64)>| {
+            counts.sort_by_key(|(mov, _)| mov.move_int);
+            counts
+        };
+        let serial = sorted(divide(&mut board, 3, None));
+
+        for split_depth in 1..=3 {
+            let options = ParallelOptions { threads: 4, split_depth, hash_mb: 1 };
+            assert_eq!(sorted(parallel_divide(&board, 3, options)), serial, "Divide differed with split depth {}", split_depth);
+        }
+        assert_eq!(parallel_perft(&board, 3, ParallelOptions::default()), perft(&mut board, 3, None));
+    }
+
+    #[test]
+    fn test_parallel_keeps_mating_and_stalemating_moves() {
+        // Ra8 mates, Qc7 stalemates, and the Chess960 position has root moves that do both
+        let positions = [
+            ("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1 ;D1 20", false),
+            ("k7/8/1Q6/8/8/8/8/7K w - - 0 1 ;D1 26", false),
+            ("8/8/8/4B2b/6nN/8/5P2/2R1K2k w Q - 0 1 ;D1 34", true),
+        ];
+        for (line, chess960) in positions {
+            let position: SuitePosition = line.parse().unwrap();
+            let mut board = position.board(chess960).unwrap();
+            let sorted = |mut counts: Vec<(GameMove, u64)>| {
+                counts.sort_by_key(|(mov, _)| mov.move_int);
+                counts
+            };
+            let serial = sorted(divide(&mut board, 3, None));
+            assert_eq!(serial.len() as u64, position.counts[0]);
+            for split_depth in 1..=3 {
+                let options = ParallelOptions { threads: 2, split_depth, hash_mb: 0 };
+                assert_eq!(sorted(parallel_divide(&board, 3, options)), serial, "Divide of {} differed with split depth {}", position.fen, split_depth);
+            }
+        }
+    }
+}
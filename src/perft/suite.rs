@@ -0,0 +1,75 @@
+//! Reading perft suite files, where each line is a position followed by its expected counts
+
+use std::str::FromStr;
+use crate::game_board::board::Board;
+use crate::utils::io::ParseFenError;
+use crate::variants::variant::Variant;
+
+/// A position from a perft suite file along with its expected counts
+#[derive(Debug, Clone)]
+pub struct SuitePosition {
+    pub variant: Variant,
+    pub fen: String,
+    pub counts: Vec<u64>, // Expected node counts from depth 1 up
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseSuiteError;
+
+impl FromStr for SuitePosition {
+    type Err = ParseSuiteError;
+
+    /// Reads a suite line such as `<fen> ;D1 20 ;D2 400`, optionally starting with the
+    /// name of the position's variant
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (fen, counts) = s.split_once(';').ok_or(ParseSuiteError)?;
+        let fen = fen.trim();
+        let (variant, fen) = match fen.split_once(' ') {
+            Some((name, rest)) if Variant::from_name(name).is_some() => (Variant::from_name(name).unwrap(), rest),
+            _ => (Variant::Standard, fen),
+        };
+
+        let counts = counts.split(';')
+            .map(|field| field.split_whitespace().nth(1).and_then(|n| n.parse::<u64>().ok()).ok_or(ParseSuiteError))
+            .collect::<Result<Vec<u64>, ParseSuiteError>>()?;
+
+        Ok(SuitePosition {
+            variant,
+            fen: fen.to_string(),
+            counts,
+        })
+    }
+}
+
+impl SuitePosition {
+    /// Sets up a board with the suite position
+    ///
+    /// # Arguments
+    ///
+    /// * `chess960`: Whether castling moves are written the Chess960 way
+    ///
+    /// returns: Result<Board, ParseFenError> An error if the suite's FEN is invalid
+    pub fn board(&self, chess960: bool) -> Result<Board, ParseFenError> {
+        let mut board = Board::from_variant_fen(self.variant, &self.fen)?;
+        board.chess960 = chess960;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::perft::suite::SuitePosition;
+    use crate::variants::variant::Variant;
+
+    #[test]
+    fn test_parse_suite_variant() {
+        let position: SuitePosition = "horde rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1 ;D1 8 ;D2 128"
+            .parse().unwrap();
+        assert_eq!(position.variant, Variant::Horde);
+        assert_eq!(position.counts, vec![8, 128]);
+        assert!("8/8/8/8/8/8/8/8 w - - 0 1".parse::<SuitePosition>().is_err());
+
+        let invalid: SuitePosition = "4k3/8/8/8/8/8/8/4RK2 w - - 0 1 ;D1 0".parse().unwrap();
+        assert!(invalid.board(false).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Transposition table for perft
+
+#[derive(Debug, Copy, Clone, Default)]
+struct PerftEntry {
+    key: u64,
+    depth: u8,
+    nodes: u64,
+}
+
+/// Transposition table for perft that remembers the node count below a position, keyed on
+/// the board's `pos_key` and the remaining depth. Colliding entries are always replaced.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+    mask: usize,
+}
+
+impl PerftTable {
+    /// Creates a table using roughly the given number of megabytes, rounded down to a power
+    /// of two number of entries
+    pub fn new(size_mb: usize) -> PerftTable {
+        let wanted = (size_mb * 1024 * 1024 / std::mem::size_of::<PerftEntry>()).max(1);
+        let size = if wanted.is_power_of_two() { wanted } else { wanted.next_power_of_two() / 2 };
+        PerftTable {
+            entries: vec![PerftEntry::default(); size],
+            mask: size - 1,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn probe(&self, key: u64, depth: u8) -> Option<u64> {
+        let entry = &self.entries[key as usize & self.mask];
+        if entry.key == key && entry.depth == depth { Some(entry.nodes) } else { None }
+    }
+
+    #[inline(always)]
+    pub(crate) fn store(&mut self, key: u64, depth: u8, nodes: u64) {
+        self.entries[key as usize & self.mask] = PerftEntry { key, depth, nodes };
+    }
+}
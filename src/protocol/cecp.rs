@@ -0,0 +1,431 @@
+//! The Chess Engine Communication Protocol used by XBoard and WinBoard. Unlike UCI the engine
+//! keeps the game itself and decides when to ponder: after moving, in `hard` mode, it plays
+//! the reply it expects and thinks on the position behind it. If the opponent plays that
+//! reply the ponder search carries on as the search for the engine's move, otherwise it is
+//! dropped and a new search started.
+
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use crate::alphabeta::search::SearhInfo;
+use crate::alphabeta::search::MAX_DEPTH;
+use crate::alphabeta::transposition::TranspositionTable;
+use crate::evaluation::trace::describe_evaluation;
+use crate::game_board::board::Board;
+use crate::game_board::outcome::outcome;
+use crate::moves::gamemove::GameMove;
+use crate::protocol::search_thread::{spawn_input_reader, Event, SearchThread};
+use crate::protocol::uci::ENGINE_NAME;
+use crate::utils::io::{move_from_uci, move_to_uci};
+use crate::utils::time::allocate_time;
+use crate::variants::variant::Variant;
+
+pub struct Cecp {
+    board: Board,
+    info: SearhInfo, // Options copied into each search
+    force: bool, // Only play the moves given, without thinking
+    ponder: bool, // Think on the opponent's time, set by `hard` and cleared by `easy`
+    moves_per_session: u64, // Moves in each time control, 0 for the whole game
+    increment: u64, // Time added after each move in milliseconds
+    move_time: Option<u64>, // Fixed time for each move set by `st`
+    time_left: Option<u64>, // The engine's clock in milliseconds
+    search: Option<SearchThread>,
+    guess: Option<GameMove>, // The opponent's move the running ponder search expects
+    searches: u64, // Searches started, each one's id
+    events: Sender<Event>,
+}
+
+impl Cecp {
+    pub fn new(events: Sender<Event>) -> Cecp {
+        let mut board = Board::new();
+        board.set_fen(Variant::Standard.starting_fen()).expect("Starting position is valid");
+        let info = SearhInfo { post_thinking: false, ..SearhInfo::default() };
+        Cecp {
+            board, info, force: false, ponder: false, moves_per_session: 0, increment: 0, move_time: None,
+            time_left: None, search: None, guess: None, searches: 0, events,
+        }
+    }
+
+    /// Handles one line from the GUI
+    ///
+    /// returns: bool false once the GUI quits
+    pub fn command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        match command {
+            "protover" => {
+                let variants: Vec<&str> = Variant::ALL.iter().map(|variant| match variant {
+                    Variant::Standard => "normal",
+                    Variant::Antichess => "giveaway",
+                    variant => variant.name(),
+                }).collect();
+                println!("feature myname=\"{}\" ping=1 setboard=1 usermove=1 colors=0 sigint=0 sigterm=0 \
+                          memory=1 smp=1 variants=\"{},fischerandom\" done=1", ENGINE_NAME, variants.join(","));
+            }
+            "new" => {
+                self.cancel_search();
+                self.new_game(Variant::Standard, false);
+            }
+            "variant" if args == "fischerandom" => {
+                self.cancel_search();
+                self.new_game(Variant::Standard, true);
+            }
+            "variant" => match Variant::from_name(args).or((args == "normal").then_some(Variant::Standard)) {
+                Some(variant) => {
+                    self.cancel_search();
+                    self.new_game(variant, false);
+                }
+                None => println!("Error (unknown variant): {}", args),
+            },
+            "setboard" => {
+                self.cancel_search();
+                if let Err(err) = self.board.set_fen(args) {
+                    println!("tellusererror Illegal position: {}", err);
+                }
+            }
+            "force" => {
+                self.cancel_search();
+                self.force = true;
+            }
+            "go" => {
+                self.cancel_search();
+                self.force = false;
+                self.think();
+            }
+            "usermove" => self.user_move(args),
+            "?" => {
+                // Move now, unless the search is still waiting for the opponent
+                if let Some(mut search) = self.search.take_if(|search| !search.is_pondering()) {
+                    let best = search.stop().and_then(|result| result.best);
+                    self.play(best, None);
+                }
+            }
+            "level" => self.set_level(args),
+            "st" => self.move_time = args.parse::<f64>().ok().map(|seconds| (seconds * 1000.0) as u64),
+            "sd" => {
+                if let Ok(depth) = args.parse::<u64>() {
+                    self.info.depthset = depth.clamp(1, MAX_DEPTH as u64 - 1);
+                }
+            }
+            "time" => self.time_left = args.parse::<u64>().ok().map(|centiseconds| centiseconds * 10),
+            "hard" => self.ponder = true,
+            "easy" => {
+                self.ponder = false;
+                if self.guess.is_some() {
+                    self.cancel_search();
+                }
+            }
+            "memory" => {
+                if let Ok(megabytes) = args.parse::<usize>() {
+                    self.info.hash = Arc::new(TranspositionTable::new(megabytes.max(1)));
+                }
+            }
+            "cores" => {
+                if let Ok(threads) = args.parse::<usize>() {
+                    self.info.threads = threads.max(1);
+                }
+            }
+            "ping" => println!("pong {}", args),
+            // Not part of CECP, explains the static evaluation as debug output the GUI ignores
+            "eval" => {
+                for line in describe_evaluation(&mut self.board, args == "json").lines() {
+                    println!("# {}", line);
+                }
+            }
+            "undo" | "remove" => {
+                self.cancel_search();
+                for _ in 0..if command == "undo" { 1 } else { 2 } {
+                    if self.board.history_ply > 0 {
+                        self.board.undo_move();
+                    }
+                }
+            }
+            "quit" => {
+                self.cancel_search();
+                return false;
+            }
+            // Commands needing nothing from this engine
+            "xboard" | "accepted" | "rejected" | "otim" | "post" | "nopost" | "random" | "computer" | "name"
+            | "rating" | "ics" | "result" | "draw" | "easy_off" | "" => (),
+            // Moves may come without `usermove` if the GUI ignored the feature
+            _ if move_from_uci(&self.board, command).is_some() => self.user_move(command),
+            _ => println!("Error (unknown command): {}", command),
+        }
+        true
+    }
+
+    /// Plays the move of a search that finished by itself
+    pub fn finished(&mut self, id: u64) {
+        if self.search.as_ref().map(|search| search.id) != Some(id) {
+            return;
+        }
+        let result = self.search.take().and_then(|mut search| search.finish());
+        self.play(result.and_then(|result| result.best), result.and_then(|result| result.ponder));
+    }
+
+    /// Starts a game of a variant. A Chess960 game's position comes with the `setboard` that
+    /// follows, so the board is left as it is until then.
+    fn new_game(&mut self, variant: Variant, chess960: bool) {
+        self.board.variant = variant;
+        self.board.chess960 = chess960;
+        if !chess960 {
+            self.board.set_fen(variant.starting_fen()).expect("Starting position is valid");
+        }
+        self.force = false;
+        self.move_time = None;
+        self.info.depthset = MAX_DEPTH as u64;
+        self.info.hash.clear();
+    }
+
+    /// Reads `level <moves> <base> <increment>`, with the base time in minutes or as
+    /// minutes:seconds and the increment in seconds
+    fn set_level(&mut self, args: &str) {
+        let fields: Vec<&str> = args.split_whitespace().collect();
+        let [moves, base, increment] = fields[..] else {
+            println!("Error (bad level): {}", args);
+            return;
+        };
+        let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+        let base = minutes.parse::<u64>().unwrap_or(0) * 60000 + seconds.parse::<u64>().unwrap_or(0) * 1000;
+        self.moves_per_session = moves.parse().unwrap_or(0);
+        self.increment = (increment.parse::<f64>().unwrap_or(0.0) * 1000.0) as u64;
+        self.move_time = None;
+        self.time_left = Some(base);
+    }
+
+    /// Works out how long to think on the engine's move, None when there is no time control
+    fn allocated(&self) -> Option<u64> {
+        if self.move_time.is_some() {
+            return self.move_time;
+        }
+        let moves_to_go = (self.moves_per_session > 0)
+            .then(|| self.moves_per_session - (self.board.full_move() as u64 - 1) % self.moves_per_session);
+        self.time_left.map(|time| allocate_time(time, self.increment, moves_to_go))
+    }
+
+    fn user_move(&mut self, text: &str) {
+        let Some(mov) = move_from_uci(&self.board, text) else {
+            println!("Illegal move: {}", text);
+            return;
+        };
+        // A ponder hit turns the ponder search into the search for the engine's reply, timed
+        // from the clock the GUI sent with the move
+        if let Some(guess) = self.guess.take() {
+            if mov == guess && self.board.make_move(mov) {
+                if let Some(search) = &self.search {
+                    search.ponder_hit_with_time(self.allocated());
+                }
+                return;
+            }
+            self.cancel_search();
+        }
+        if !self.board.make_move(mov) {
+            println!("Illegal move: {}", text);
+            return;
+        }
+        if !self.force && !self.report_game_end() {
+            self.think();
+        }
+    }
+
+    fn think(&mut self) {
+        self.start_search(self.board.clone(), false);
+    }
+
+    /// Plays the engine's move, then ponders on the expected reply if pondering is on
+    fn play(&mut self, best: Option<GameMove>, expected: Option<GameMove>) {
+        let Some(best) = best else { return };
+        println!("move {}", move_to_uci(&self.board, best));
+        self.board.make_move(best);
+        if self.report_game_end() || !self.ponder || self.force {
+            return;
+        }
+        if let Some(guess) = expected {
+            let mut board = self.board.clone();
+            if board.make_move(guess) {
+                self.start_search(board, true);
+                self.guess = Some(guess);
+            }
+        }
+    }
+
+    fn start_search(&mut self, board: Board, ponder: bool) {
+        self.searches += 1;
+        self.search = Some(SearchThread::start(self.searches, board, self.info.clone(), self.allocated(), ponder,
+                                               self.events.clone()));
+    }
+
+    /// Stops any search without playing its move
+    fn cancel_search(&mut self) {
+        if let Some(mut search) = self.search.take() {
+            search.stop();
+        }
+        self.guess = None;
+    }
+
+    /// Tells the GUI the result when the game is over
+    ///
+    /// returns: bool Whether the game is over
+    fn report_game_end(&mut self) -> bool {
+        match outcome(&mut self.board) {
+            Some(outcome) => {
+                println!("{} {{{:?}}}", outcome.result.pgn(), outcome.termination);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Runs the CECP loop until the GUI quits, after the `xboard` command that started it
+pub fn run() {
+    let (sender, receiver) = channel();
+    spawn_input_reader(sender.clone());
+    let mut cecp = Cecp::new(sender);
+    for event in receiver {
+        match event {
+            Event::Command(line) => {
+                if !cecp.command(&line) {
+                    break;
+                }
+            }
+            Event::Finished(id) => cecp.finished(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use crate::protocol::cecp::Cecp;
+    use crate::protocol::search_thread::Event;
+
+    /// Waits for the running search, skipping the events of searches that were stopped
+    fn finish_search(cecp: &mut Cecp, receiver: &std::sync::mpsc::Receiver<Event>) {
+        loop {
+            let Ok(Event::Finished(id)) = receiver.recv() else { panic!("Search did not finish") };
+            let running = cecp.search.as_ref().is_some_and(|search| search.id == id);
+            cecp.finished(id);
+            if running {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_fischerandom() {
+        let (sender, _receiver) = channel();
+        let mut cecp = Cecp::new(sender);
+        cecp.command("variant fischerandom");
+        assert!(cecp.board.chess960);
+        cecp.command("setboard 4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        // Castling is the king taking its own rook
+        assert!(crate::utils::io::move_from_uci(&cecp.board, "e1h1").is_some());
+
+        cecp.command("new");
+        assert!(!cecp.board.chess960);
+        assert_eq!(cecp.board.variant, crate::variants::variant::Variant::Standard);
+        cecp.command("setboard 4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(crate::utils::io::move_from_uci(&cecp.board, "e1h1").is_none());
+    }
+
+    #[test]
+    fn test_level() {
+        let (sender, _receiver) = channel();
+        let mut cecp = Cecp::new(sender);
+        cecp.command("level 40 5 0");
+        assert_eq!(cecp.allocated(), Some(7500));
+        cecp.command("level 0 2:30 2");
+        cecp.command("time 6000");
+        assert_eq!(cecp.allocated(), Some(3500));
+        cecp.command("st 3");
+        assert_eq!(cecp.allocated(), Some(3000));
+    }
+
+    #[test]
+    fn test_move_now_straight_after_go() {
+        let (sender, receiver) = channel();
+        let mut cecp = Cecp::new(sender);
+        for command in ["new", "force", "go", "?"] {
+            cecp.command(command);
+        }
+        assert_eq!(cecp.board.history_ply, 1, "Engine did not move when told to");
+
+        // A clock that has run out still gives a move
+        for command in ["time 0", "usermove e7e5"] {
+            cecp.command(command);
+        }
+        finish_search(&mut cecp, &receiver);
+        assert_eq!(cecp.board.history_ply, 3, "Engine did not move without time left");
+    }
+
+    #[test]
+    fn test_ponder_hit_uses_new_clock() {
+        let (sender, receiver) = channel();
+        let mut cecp = Cecp::new(sender);
+        for command in ["new", "hard", "st 0.2", "force", "usermove e2e4", "go"] {
+            cecp.command(command);
+        }
+        // The ponder search starts with the clock of a long game
+        cecp.command("level 0 100 0");
+        finish_search(&mut cecp, &receiver);
+        let guess = cecp.guess.expect("Not pondering after moving");
+
+        // The ponder search was given plenty of time, but the clock sent with the move is empty
+        cecp.command("time 0");
+        cecp.command(&format!("usermove {}", crate::utils::io::move_to_uci(&cecp.board, guess)));
+        let ponder_id = cecp.search.as_ref().unwrap().id;
+        loop {
+            match receiver.recv_timeout(std::time::Duration::from_secs(10)) {
+                Ok(Event::Finished(id)) if id == ponder_id => break,
+                Ok(_) => (),
+                Err(_) => panic!("Ponder hit kept the time from before the engine's move"),
+            }
+        }
+        cecp.finished(ponder_id);
+        assert_eq!(cecp.board.history_ply, 4);
+        cecp.command("quit");
+    }
+
+    #[test]
+    fn test_ponder_hit_and_miss() {
+        let (sender, receiver) = channel();
+        let mut cecp = Cecp::new(sender);
+        for command in ["new", "hard", "sd 3", "st 10", "force", "usermove e2e4", "go"] {
+            cecp.command(command);
+        }
+        finish_search(&mut cecp, &receiver);
+        assert_eq!(cecp.board.history_ply, 2, "Engine did not play its move");
+        let guess = cecp.guess.expect("Not pondering after moving");
+        assert!(cecp.search.as_ref().unwrap().is_pondering());
+
+        // The expected reply continues the ponder search as the search for the next move
+        let ponder_id = cecp.search.as_ref().unwrap().id;
+        cecp.command(&format!("usermove {}", crate::utils::io::move_to_uci(&cecp.board, guess)));
+        assert_eq!(cecp.search.as_ref().unwrap().id, ponder_id);
+        assert!(!cecp.search.as_ref().unwrap().is_pondering());
+        finish_search(&mut cecp, &receiver);
+        assert_eq!(cecp.board.history_ply, 4);
+
+        // Any other reply drops the ponder search and starts a new one
+        let guess = cecp.guess.expect("Not pondering after moving");
+        let ponder_id = cecp.search.as_ref().unwrap().id;
+        let mut moves = Vec::new();
+        crate::moves::movegen::generate_all_moves(&cecp.board, &mut moves);
+        let other = moves.into_iter().find(|&mov| mov != guess && cecp.board.clone().make_move(mov)).unwrap();
+        cecp.command(&format!("usermove {}", crate::utils::io::move_to_uci(&cecp.board, other)));
+        assert!(cecp.guess.is_none());
+        assert_ne!(cecp.search.as_ref().unwrap().id, ponder_id);
+        assert!(!cecp.search.as_ref().unwrap().is_pondering());
+        loop {
+            let Ok(Event::Finished(id)) = receiver.recv() else { panic!("Search did not finish") };
+            if id != ponder_id {
+                cecp.finished(id);
+                break;
+            }
+            cecp.finished(id);
+        }
+        assert_eq!(cecp.board.history_ply, 6);
+        assert!(!cecp.command("quit"));
+    }
+}
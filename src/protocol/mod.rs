@@ -0,0 +1,3 @@
+pub mod search_thread;
+pub mod uci;
+pub mod cecp;
@@ -0,0 +1,191 @@
+//! A search running on its own thread, so the protocol loop can keep reading commands such
+//! as `stop` and `ponderhit` while it thinks. Commands and the news that a search finished
+//! reach the loop as events on one channel, and the loop then collects the result.
+
+use std::io;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use crate::alphabeta::search::{search_position, SearhInfo};
+use crate::game_board::board::Board;
+use crate::moves::gamemove::GameMove;
+use crate::utils::time::{ponder_hit_end_time, time_ms};
+
+/// Something for the protocol loop to handle
+#[derive(Debug)]
+pub enum Event {
+    Command(String), // A line read from the GUI
+    Finished(u64), // The id of a search that finished, stale when the search was since stopped
+}
+
+/// The result of a search
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SearchResult {
+    pub best: Option<GameMove>,
+    pub ponder: Option<GameMove>, // The expected reply, the second move of the principal variation
+}
+
+#[derive(Debug)]
+pub struct SearchThread {
+    pub id: u64,
+    handle: Option<JoinHandle<SearchResult>>,
+    stop: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+    ponder_end_time: Arc<AtomicU64>,
+    start_time: u64,
+    allocated: Option<u64>,
+}
+
+impl SearchThread {
+    /// Starts searching a position, sending an event once it is done. A ponder search runs
+    /// until it is stopped or hit, and does not finish before then even if it runs out of
+    /// depth first.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: Identifies the search in its event
+    /// * `board`: The position to search
+    /// * `info`: The search options and depth limit, its stop and ponder flags are replaced
+    /// * `allocated`: The time to think in milliseconds, None for no time limit
+    /// * `ponder`: Whether the search is on the opponent's time
+    /// * `events`: Where to send the event
+    ///
+    /// returns: SearchThread
+    pub fn start(id: u64, mut board: Board, mut info: SearhInfo, allocated: Option<u64>, ponder: bool,
+                 events: Sender<Event>) -> SearchThread {
+        info.stop = Arc::new(AtomicBool::new(false));
+        info.pondering = Arc::new(AtomicBool::new(ponder));
+        info.ponder_end_time = Arc::new(AtomicU64::new(0));
+        info.start_time = time_ms();
+        info.timeset = allocated.is_some();
+        info.end_time = info.start_time + allocated.unwrap_or(0);
+
+        let stop = info.stop.clone();
+        let pondering = info.pondering.clone();
+        let ponder_end_time = info.ponder_end_time.clone();
+        let start_time = info.start_time;
+        let handle = thread::spawn(move || {
+            let best = search_position(&mut board, &mut info);
+            while info.pondering.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let _ = events.send(Event::Finished(id));
+            SearchResult { best, ponder: best.and(info.pv.get(1).copied()) }
+        });
+        SearchThread { id, handle: Some(handle), stop, pondering, ponder_end_time, start_time, allocated }
+    }
+
+    /// Whether the search is still on the opponent's time
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Relaxed)
+    }
+
+    /// Turns a ponder search into a timed search after the opponent played the expected
+    /// move, keeping everything it has searched so far. The time spent pondering counts
+    /// towards the time given to the move.
+    pub fn ponder_hit(&self) {
+        self.ponder_hit_with_time(self.allocated);
+    }
+
+    /// Same as `ponder_hit`, with the time for the move worked out from the clocks at the hit
+    /// in place of the time given when the search started
+    ///
+    /// # Arguments
+    ///
+    /// * `allocated`: The time to think in milliseconds, None for no time limit
+    ///
+    /// returns: ()
+    pub fn ponder_hit_with_time(&self, allocated: Option<u64>) {
+        if let Some(allocated) = allocated {
+            let end_time = ponder_hit_end_time(self.start_time, allocated, time_ms());
+            self.ponder_end_time.store(end_time, Ordering::Relaxed);
+        }
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    /// Stops the search, see `finish`
+    pub fn stop(&mut self) -> Option<SearchResult> {
+        self.pondering.store(false, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+        self.finish()
+    }
+
+    /// Waits for the search to finish
+    ///
+    /// returns: Option<SearchResult> The result, None if it was already collected
+    pub fn finish(&mut self) -> Option<SearchResult> {
+        self.handle.take().and_then(|handle| handle.join().ok())
+    }
+}
+
+/// Reads lines from standard input on its own thread, sending each as a command event. The
+/// end of the input is sent as `quit`.
+///
+/// # Arguments
+///
+/// * `events`: Where to send the commands
+///
+/// returns: ()
+pub fn spawn_input_reader(events: Sender<Event>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if events.send(Event::Command(line)).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(Event::Command("quit".to_string()));
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+    use crate::alphabeta::search::SearhInfo;
+    use crate::game_board::board::Board;
+    use crate::protocol::search_thread::{Event, SearchThread};
+    use crate::utils::io::move_to_uci;
+
+    #[test]
+    fn test_ponder_result_waits_for_hit() {
+        // The search reaches its depth limit at once, but a ponder search holds its result
+        // back until the ponder hit
+        let mut board = Board::new();
+        board.set_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let info = SearhInfo { depthset: 2, post_thinking: false, ..SearhInfo::default() };
+        let (sender, receiver) = channel();
+        let mut search = SearchThread::start(7, board.clone(), info, Some(1000), true, sender);
+
+        thread::sleep(Duration::from_millis(100));
+        assert!(receiver.try_recv().is_err(), "Ponder search finished before the hit");
+        assert!(search.is_pondering());
+        search.ponder_hit();
+        assert!(matches!(receiver.recv(), Ok(Event::Finished(7))));
+        let result = search.finish().unwrap();
+        assert_eq!(move_to_uci(&board, result.best.unwrap()), "d1d8");
+        assert_eq!(search.finish(), None);
+    }
+
+    #[test]
+    fn test_stop_ponder_search() {
+        let mut board = Board::new();
+        board.set_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let info = SearhInfo { post_thinking: false, ..SearhInfo::default() };
+        let (sender, receiver) = channel();
+        let mut search = SearchThread::start(1, board, info, Some(10), true, sender);
+
+        // Pondering ignores the time limit
+        thread::sleep(Duration::from_millis(100));
+        assert!(receiver.try_recv().is_err(), "Ponder search stopped on time");
+        let result = search.stop().unwrap();
+        assert!(result.best.is_some());
+        assert!(result.ponder.is_some());
+        assert!(matches!(receiver.recv(), Ok(Event::Finished(1))));
+    }
+}
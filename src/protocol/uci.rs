@@ -0,0 +1,353 @@
+//! The Universal Chess Interface, for playing through a GUI. The search runs on its own
+//! thread so `stop` and `ponderhit` are read while it thinks. Pondering is driven by the GUI:
+//! after `bestmove <move> ponder <reply>` it sends the position with both moves and
+//! `go ponder`, then `ponderhit` if the opponent played the reply, or `stop` if not.
+
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use crate::alphabeta::bench::{print_bench, BENCH_DEPTH};
+use crate::alphabeta::search::SearhInfo;
+use crate::alphabeta::transposition::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::constants::pieces::WHITE;
+use crate::evaluation::nnue::Network;
+use crate::evaluation::trace::describe_evaluation;
+use crate::game_board::board::Board;
+use crate::protocol::search_thread::{spawn_input_reader, Event, SearchResult, SearchThread};
+use crate::utils::io::{move_from_uci, move_to_uci};
+use crate::utils::time::allocate_time;
+use crate::variants::variant::Variant;
+
+pub const ENGINE_NAME: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+const MAX_HASH_MB: usize = 65536;
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 256;
+
+// Words that end the move list of `searchmoves`
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc", "movestogo", "depth", "nodes", "mate", "movetime",
+    "infinite",
+];
+
+/// The limits of a `go` command
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GoParams {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub depth: Option<u64>,
+    pub movetime: Option<u64>,
+    pub nodes: Option<u64>,
+    pub mate: Option<u64>, // Stop once a mate in this many moves is found
+    pub searchmoves: Vec<String>, // Root moves to search in UCI notation, all of them when empty
+    pub infinite: bool,
+    pub ponder: bool, // Search on the opponent's time, the clocks are for after the ponder hit
+}
+
+impl GoParams {
+    /// Reads the arguments of a `go` command, unknown or malformed ones are skipped
+    pub fn parse(args: &str) -> GoParams {
+        let mut params = GoParams::default();
+        let mut tokens = args.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            let field = match token {
+                "infinite" => { params.infinite = true; continue; }
+                "ponder" => { params.ponder = true; continue; }
+                "searchmoves" => {
+                    while let Some(mov) = tokens.next_if(|token| !GO_KEYWORDS.contains(token)) {
+                        params.searchmoves.push(mov.to_string());
+                    }
+                    continue;
+                }
+                "wtime" => &mut params.wtime,
+                "btime" => &mut params.btime,
+                "winc" => &mut params.winc,
+                "binc" => &mut params.binc,
+                "movestogo" => &mut params.movestogo,
+                "depth" => &mut params.depth,
+                "movetime" => &mut params.movetime,
+                "nodes" => &mut params.nodes,
+                "mate" => &mut params.mate,
+                _ => continue,
+            };
+            // Clocks can go negative when a GUI is late, treat that as no time left
+            *field = tokens.next().and_then(|value| value.parse::<i64>().ok()).map(|value| value.max(0) as u64);
+        }
+        params
+    }
+
+    /// Works out how long to think for the side to move
+    ///
+    /// # Arguments
+    ///
+    /// * `side`: The side to move
+    ///
+    /// returns: Option<u64> The time in milliseconds, None when the search has no time limit
+    pub fn allocated(&self, side: u8) -> Option<u64> {
+        if self.infinite {
+            return None;
+        }
+        if self.movetime.is_some() {
+            return self.movetime;
+        }
+        let (time, increment) = if side == WHITE { (self.wtime, self.winc) } else { (self.btime, self.binc) };
+        time.map(|time| allocate_time(time, increment.unwrap_or(0), self.movestogo))
+    }
+}
+
+pub struct Uci {
+    board: Board,
+    info: SearhInfo, // Options copied into each search
+    search: Option<SearchThread>,
+    searches: u64, // Searches started, each one's id
+    events: Sender<Event>,
+}
+
+impl Uci {
+    pub fn new(events: Sender<Event>) -> Uci {
+        let mut board = Board::new();
+        board.set_fen(Variant::Standard.starting_fen()).expect("Starting position is valid");
+        Uci { board, info: SearhInfo::default(), search: None, searches: 0, events }
+    }
+
+    /// Handles one line from the GUI
+    ///
+    /// returns: bool false once the GUI quits
+    pub fn command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "uci" => self.identify(),
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop();
+                self.info.hash.clear();
+            }
+            "setoption" => self.set_option(args),
+            "position" => {
+                self.stop();
+                self.set_position(args);
+            }
+            "go" => self.go(args),
+            "stop" => {
+                self.stop();
+            }
+            // Not part of UCI, prints the node count signature of the search
+            "bench" => {
+                self.stop();
+                print_bench(args.parse().unwrap_or(BENCH_DEPTH));
+            }
+            // Not part of UCI, explains the static evaluation of the current position
+            "eval" => println!("{}", describe_evaluation(&mut self.board, args == "json")),
+            "ponderhit" => {
+                if let Some(search) = &self.search {
+                    search.ponder_hit();
+                }
+            }
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            "" => (),
+            _ => println!("info string unknown command {}", line),
+        }
+        true
+    }
+
+    /// Reports the result of a search that finished by itself
+    pub fn finished(&mut self, id: u64) {
+        if self.search.as_ref().map(|search| search.id) != Some(id) {
+            return;
+        }
+        let result = self.search.take().and_then(|mut search| search.finish());
+        self.report(result);
+    }
+
+    fn identify(&self) {
+        println!("id name {}", ENGINE_NAME);
+        println!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB);
+        println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+        println!("option name Ponder type check default false");
+        println!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV);
+        println!("option name EvalFile type string default <empty>");
+        println!("option name UCI_Chess960 type check default false");
+        let variants: Vec<String> = Variant::ALL.iter().map(|variant| format!("var {}", variant.name())).collect();
+        println!("option name UCI_Variant type combo default chess {}", variants.join(" "));
+        println!("uciok");
+    }
+
+    fn set_option(&mut self, args: &str) {
+        let args = args.trim().strip_prefix("name ").unwrap_or(args);
+        let (name, value) = args.split_once(" value").unwrap_or((args, ""));
+        let (name, value) = (name.trim(), value.trim());
+        match name.to_ascii_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(megabytes) => self.info.hash = Arc::new(TranspositionTable::new(megabytes.clamp(1, MAX_HASH_MB))),
+                Err(_) => println!("info string invalid Hash value {}", value),
+            },
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) => self.info.threads = threads.clamp(1, MAX_THREADS),
+                Err(_) => println!("info string invalid Threads value {}", value),
+            },
+            "multipv" => match value.parse::<usize>() {
+                Ok(lines) => self.info.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+                Err(_) => println!("info string invalid MultiPV value {}", value),
+            },
+            // The GUI decides when to ponder, the engine only has to follow
+            "ponder" => (),
+            "evalfile" if value.is_empty() || value == "<empty>" => self.board.set_network(None),
+            "evalfile" => match Network::load(value) {
+                Ok(network) => self.board.set_network(Some(Arc::new(network))),
+                Err(err) => println!("info string {}", err),
+            },
+            "uci_chess960" => self.board.chess960 = value == "true",
+            "uci_variant" => match Variant::from_name(value) {
+                Some(variant) => {
+                    self.board.variant = variant;
+                    self.set_position("startpos");
+                }
+                None => println!("info string unknown variant {}", value),
+            },
+            _ => println!("info string unknown option {}", name),
+        }
+    }
+
+    fn set_position(&mut self, args: &str) {
+        let (position, moves) = args.split_once("moves").unwrap_or((args, ""));
+        let position = position.trim();
+        let fen = if position == "startpos" {
+            self.board.variant.starting_fen()
+        } else if let Some(fen) = position.strip_prefix("fen") {
+            fen.trim()
+        } else {
+            println!("info string invalid position {}", args);
+            return;
+        };
+        if let Err(err) = self.board.set_fen(fen) {
+            println!("info string invalid fen: {}", err);
+            return;
+        }
+        for text in moves.split_whitespace() {
+            match move_from_uci(&self.board, text) {
+                Some(mov) if self.board.make_move(mov) => (),
+                _ => {
+                    println!("info string illegal move {}", text);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn go(&mut self, args: &str) {
+        self.stop();
+        let params = GoParams::parse(args);
+        let mut info = self.info.clone();
+        info.depthset = params.depth.unwrap_or(info.depthset);
+        info.node_limit = params.nodes.unwrap_or(u64::MAX);
+        info.mate_limit = params.mate;
+        info.search_moves = params.searchmoves.iter().filter_map(|text| move_from_uci(&self.board, text)).collect();
+        self.searches += 1;
+        self.search = Some(SearchThread::start(self.searches, self.board.clone(), info,
+                                               params.allocated(self.board.side), params.ponder, self.events.clone()));
+    }
+
+    /// Stops the search if there is one, which still reports its move
+    ///
+    /// returns: Option<SearchResult> The reported result, None if no search was running
+    fn stop(&mut self) -> Option<SearchResult> {
+        let result = self.search.take().and_then(|mut search| search.stop());
+        if result.is_some() {
+            self.report(result);
+        }
+        result
+    }
+
+    fn report(&self, result: Option<SearchResult>) {
+        match result.and_then(|result| result.best.map(|best| (best, result.ponder))) {
+            Some((best, Some(ponder))) => {
+                println!("bestmove {} ponder {}", move_to_uci(&self.board, best), move_to_uci(&self.board, ponder));
+            }
+            Some((best, None)) => println!("bestmove {}", move_to_uci(&self.board, best)),
+            None => println!("bestmove 0000"),
+        }
+    }
+}
+
+/// Runs the UCI loop until the GUI quits, after the `uci` command that started it
+pub fn run() {
+    let (sender, receiver) = channel();
+    spawn_input_reader(sender.clone());
+    let mut uci = Uci::new(sender);
+    uci.command("uci");
+    for event in receiver {
+        match event {
+            Event::Command(line) => {
+                if !uci.command(&line) {
+                    break;
+                }
+            }
+            Event::Finished(id) => uci.finished(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use crate::constants::pieces::{BLACK, WHITE};
+    use crate::protocol::search_thread::Event;
+    use crate::protocol::uci::{GoParams, Uci};
+
+    #[test]
+    fn test_parse_go() {
+        let params = GoParams::parse("ponder wtime 60000 btime 30000 winc 1000 binc 1000 movestogo 10");
+        assert!(params.ponder);
+        assert_eq!(params.allocated(WHITE), Some(6750));
+        assert_eq!(params.allocated(BLACK), Some(3750));
+
+        assert_eq!(GoParams::parse("movetime 500 wtime 100").allocated(WHITE), Some(500));
+        assert_eq!(GoParams::parse("infinite").allocated(WHITE), None);
+        assert_eq!(GoParams::parse("depth 5").depth, Some(5));
+        assert_eq!(GoParams::parse("wtime -20 btime").wtime, Some(0));
+
+        let params = GoParams::parse("searchmoves e2e4 d2d4 nodes 5000 mate 3");
+        assert_eq!(params.searchmoves, ["e2e4", "d2d4"]);
+        assert_eq!((params.nodes, params.mate), (Some(5000), Some(3)));
+        assert_eq!(params.allocated(WHITE), None);
+    }
+
+    #[test]
+    fn test_position_and_go() {
+        let (sender, receiver) = channel();
+        let mut uci = Uci::new(sender);
+        assert!(uci.command("setoption name Threads value 2"));
+        assert_eq!(uci.info.threads, 2);
+        uci.command("setoption name MultiPV value 3");
+        assert_eq!(uci.info.multi_pv, 3);
+        uci.command("position startpos moves g1f3 g8f6 b1c3");
+        assert_eq!(uci.board.to_fen(), "rnbqkb1r/pppppppp/5n2/8/8/2N2N2/PPPPPPPP/R1BQKB1R b KQkq - 3 2");
+        uci.command("position fen 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1 moves");
+        assert!(uci.command("eval json"));
+        uci.command("go depth 3");
+        let Ok(Event::Finished(id)) = receiver.recv() else { panic!("Search did not finish") };
+        assert!(uci.search.is_some());
+        uci.finished(id);
+        assert!(uci.search.is_none());
+        assert!(!uci.command("quit"));
+    }
+
+    #[test]
+    fn test_stop_straight_after_go() {
+        let (sender, _receiver) = channel();
+        let mut uci = Uci::new(sender);
+        uci.command("position startpos");
+        for go in ["go infinite", "go wtime 5 btime 5"] {
+            uci.command(go);
+            let result = uci.stop().expect("No search to stop");
+            let best = result.best.unwrap_or_else(|| panic!("Null move after {}", go));
+            assert!(uci.board.clone().make_move(best), "Illegal move after {}", go);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! A UCI engine running as a child process, so matches can be played against other engines
+//! or other builds of this one. The engine's output is read on its own thread, so every wait
+//! for an answer can time out.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Time an engine gets to answer `uci` and `isready`
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+// Centipawn value given to a reported mate, less the moves to it
+const MATE_CENTIPAWNS: i32 = 30000;
+
+/// An engine that failed to start or stopped answering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineError {
+    pub reason: String,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "engine error: {}", self.reason)
+    }
+}
+
+impl Error for EngineError {}
+
+fn engine_error(reason: impl Into<String>) -> EngineError {
+    EngineError { reason: reason.into() }
+}
+
+/// How to start an engine
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineConfig {
+    pub command: String, // The program and its arguments, separated by spaces
+    pub name: Option<String>, // Name used in the results, the engine's own when None
+    pub options: Vec<(String, String)>, // UCI options set once the engine has started
+}
+
+/// A score reported by an engine, from its own point of view
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EngineScore {
+    Centipawns(i32),
+    Mate(i32), // Moves to mate, negative when the engine is getting mated
+}
+
+impl EngineScore {
+    /// The score in centipawns, with mates beyond any material score and nearer mates
+    /// scoring further from 0
+    pub fn centipawns(self) -> i32 {
+        match self {
+            EngineScore::Centipawns(score) => score,
+            EngineScore::Mate(moves) if moves > 0 => MATE_CENTIPAWNS - moves,
+            EngineScore::Mate(moves) => -MATE_CENTIPAWNS - moves,
+        }
+    }
+}
+
+/// An engine's answer to `go`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineReply {
+    pub best: String, // The move in UCI notation
+    pub score: Option<EngineScore>, // The last score reported before the move
+}
+
+pub struct UciEngine {
+    pub name: String,
+    config: EngineConfig,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl fmt::Debug for UciEngine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UciEngine {{ name: {}, command: {} }}", self.name, self.config.command)
+    }
+}
+
+impl UciEngine {
+    /// Starts an engine and sets its options, waiting until it is ready
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: The command to run and the options to set
+    ///
+    /// returns: Result<UciEngine, EngineError>
+    pub fn start(config: &EngineConfig) -> Result<UciEngine, EngineError> {
+        let mut words = config.command.split_whitespace();
+        let program = words.next().ok_or_else(|| engine_error("no command given"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| engine_error(format!("can't start {}: {}", config.command, err)))?;
+        let stdin = child.stdin.take().ok_or_else(|| engine_error("no input pipe"))?;
+        let stdout = child.stdout.take().ok_or_else(|| engine_error("no output pipe"))?;
+
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = UciEngine { name: config.command.clone(), config: config.clone(), child, stdin, lines };
+        engine.send("uci")?;
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            let line = engine.read_line(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        if let Some(name) = &config.name {
+            engine.name = name.clone();
+        }
+        for (name, value) in config.options.iter() {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.wait_ready()?;
+        Ok(engine)
+    }
+
+    /// Stops the engine's process and starts it again, after it stopped answering
+    pub fn restart(&mut self) -> Result<(), EngineError> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        *self = UciEngine::start(&self.config)?;
+        Ok(())
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| engine_error(format!("{} stopped reading: {}", self.name, err)))
+    }
+
+    fn read_line(&mut self, deadline: Instant) -> Result<String, EngineError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines.recv_timeout(timeout).map_err(|err| match err {
+            RecvTimeoutError::Timeout => engine_error(format!("{} did not answer in time", self.name)),
+            RecvTimeoutError::Disconnected => engine_error(format!("{} exited", self.name)),
+        })
+    }
+
+    fn wait_ready(&mut self) -> Result<(), EngineError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        while self.read_line(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    /// Tells the engine a new game starts
+    pub fn new_game(&mut self) -> Result<(), EngineError> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    /// Asks the engine for its move
+    ///
+    /// # Arguments
+    ///
+    /// * `position`: The arguments of the `position` command
+    /// * `limits`: The arguments of the `go` command
+    /// * `timeout`: How long to wait for the move
+    ///
+    /// returns: Result<EngineReply, EngineError>
+    pub fn go(&mut self, position: &str, limits: &str, timeout: Duration) -> Result<EngineReply, EngineError> {
+        self.send(&format!("position {}", position))?;
+        self.send(&format!("go {}", limits))?;
+        let deadline = Instant::now() + timeout;
+        let mut score = None;
+        loop {
+            let line = self.read_line(deadline)?;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("bestmove") => {
+                    let best = words.next().ok_or_else(|| engine_error(format!("{} sent no move", self.name)))?;
+                    return Ok(EngineReply { best: best.to_string(), score });
+                }
+                Some("info") => {
+                    while let Some(word) = words.next() {
+                        if word == "score" {
+                            let kind = words.next();
+                            let value = words.next().and_then(|value| value.parse().ok());
+                            score = match (kind, value) {
+                                (Some("cp"), Some(value)) => Some(EngineScore::Centipawns(value)),
+                                (Some("mate"), Some(value)) => Some(EngineScore::Mate(value)),
+                                _ => score,
+                            };
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // Give the engine a moment to exit by itself before stopping it
+        for _ in 0..50 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tournament::engine::EngineScore;
+
+    #[test]
+    fn test_score_centipawns() {
+        assert_eq!(EngineScore::Centipawns(-35).centipawns(), -35);
+        assert!(EngineScore::Mate(3).centipawns() > EngineScore::Mate(5).centipawns());
+        assert!(EngineScore::Mate(5).centipawns() > 10000);
+        assert!(EngineScore::Mate(-2).centipawns() < EngineScore::Mate(-4).centipawns());
+        assert!(EngineScore::Mate(-4).centipawns() < -10000);
+    }
+}
@@ -0,0 +1,409 @@
+//! Playing a single game between two UCI engines. The crate's own rules decide when the game
+//! is over, the engines' clocks are kept here, and the game can be cut short when both engines
+//! agree on the result.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::constants::pieces::{BLACK, WHITE};
+use crate::game_board::board::Board;
+use crate::game_board::outcome::{outcome, GameResult, Termination};
+use crate::moves::gamemove::GameMove;
+use crate::tournament::engine::UciEngine;
+use crate::tournament::openings::Opening;
+use crate::utils::io::{move_from_uci, move_to_san};
+use crate::variants::variant::Variant;
+
+// How long past its clock an engine may take to answer before it loses on time
+const TIME_MARGIN: u64 = 100;
+// Longest line of movetext in the PGN output
+const PGN_LINE_LENGTH: usize = 80;
+
+/// A base time and an increment added after each move, in milliseconds
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: u64,
+    pub increment: u64,
+}
+
+impl TimeControl {
+    /// Reads a time control in seconds such as `10+0.1` or `60`
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The base time, optionally followed by `+` and the increment
+    ///
+    /// returns: Option<TimeControl> None if the text isn't a time control with time on it
+    pub fn parse(text: &str) -> Option<TimeControl> {
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let millis = |seconds: &str| seconds.trim().parse::<f64>().ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .map(|seconds| (seconds * 1000.0).round() as u64);
+        let time_control = TimeControl { base: millis(base)?, increment: millis(increment)? };
+        (time_control.base > 0).then_some(time_control)
+    }
+
+    /// Writes the time control as in the PGN TimeControl tag, e.g. `10+0.1`
+    pub fn pgn(&self) -> String {
+        let seconds = |millis: u64| format!("{}", millis as f64 / 1000.0);
+        if self.increment == 0 {
+            seconds(self.base)
+        } else {
+            format!("{}+{}", seconds(self.base), seconds(self.increment))
+        }
+    }
+}
+
+/// Ends a game as won once both engines' scores have favored the same side by at least
+/// `score` centipawns for `moves` moves each
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ResignRule {
+    pub moves: usize,
+    pub score: i32,
+}
+
+/// Ends a game as drawn once both engines' scores have been within `score` centipawns of 0
+/// for `moves` moves each, from move `move_number` on
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DrawRule {
+    pub move_number: u32,
+    pub moves: usize,
+    pub score: i32,
+}
+
+/// Settings shared by all the games of a match
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GameSettings {
+    pub time_control: TimeControl,
+    pub resign: Option<ResignRule>,
+    pub draw: Option<DrawRule>,
+}
+
+/// A finished game, with everything needed to write it as PGN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub date: String, // As in the PGN Date tag
+    pub time_control: String,
+    pub fen: String, // The position the game started from, before the opening moves
+    pub moves: Vec<String>, // In SAN, including the opening moves
+    pub result: GameResult,
+    pub termination: &'static str, // The PGN Termination tag, such as `normal` or `time forfeit`
+    pub reason: String, // How the game ended, for people to read
+}
+
+impl GameRecord {
+    /// Writes the game as PGN
+    ///
+    /// # Arguments
+    ///
+    /// * `event`: The name of the match
+    /// * `round`: The game's number in the match
+    ///
+    /// returns: String
+    pub fn to_pgn(&self, event: &str, round: usize) -> String {
+        let mut pgn = String::new();
+        let mut tag = |name: &str, value: &str| pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        tag("Event", event);
+        tag("Site", "?");
+        tag("Date", &self.date);
+        tag("Round", &round.to_string());
+        tag("White", &self.white);
+        tag("Black", &self.black);
+        tag("Result", self.result.pgn());
+        if self.fen != Variant::Standard.starting_fen() {
+            tag("SetUp", "1");
+            tag("FEN", &self.fen);
+        }
+        tag("TimeControl", &self.time_control);
+        tag("PlyCount", &self.moves.len().to_string());
+        tag("Termination", self.termination);
+        pgn.push('\n');
+
+        let mut board = Board::new();
+        let (mut move_number, mut side) = match board.set_fen(&self.fen) {
+            Ok(()) => (board.full_move(), board.side),
+            Err(_) => (1, WHITE),
+        };
+        let mut tokens = Vec::new();
+        for (ply, san) in self.moves.iter().enumerate() {
+            if side == WHITE {
+                tokens.push(format!("{}. {}", move_number, san));
+            } else if ply == 0 {
+                tokens.push(format!("{}... {}", move_number, san));
+            } else {
+                tokens.push(san.clone());
+            }
+            if side == BLACK {
+                move_number += 1;
+            }
+            side ^= 1;
+        }
+        tokens.push(format!("{{{}}}", self.reason));
+        tokens.push(self.result.pgn().to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push_str("\n\n");
+        pgn
+    }
+}
+
+/// Writes today's date as in the PGN Date tag
+fn pgn_date() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() / 86400) as i64;
+    // Converts days since 1970-01-01 to a civil date, counting in 400 year eras from March 0000
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Tells whether a pseudo legal move is legal, leaving the board unchanged
+fn is_legal(board: &mut Board, mov: GameMove) -> bool {
+    let legal = board.make_move(mov);
+    if legal {
+        board.undo_move();
+    }
+    legal
+}
+
+/// The result of a game forfeited by `side`
+fn win_for_opponent(side: u8) -> GameResult {
+    if side == WHITE { GameResult::BlackWin } else { GameResult::WhiteWin }
+}
+
+/// Decides a game from the engines' scores, from White's point of view and most recent last,
+/// None where an engine gave no score
+fn adjudicate(settings: &GameSettings, scores: &[Option<i32>], full_move: u32) -> Option<(GameResult, String)> {
+    let recent = |moves: usize| -> Option<Vec<i32>> {
+        let plies = moves.max(1) * 2;
+        if scores.len() < plies {
+            return None;
+        }
+        scores[scores.len() - plies..].iter().copied().collect()
+    };
+    if let Some(rule) = settings.resign {
+        if let Some(recent) = recent(rule.moves) {
+            if recent.iter().all(|&score| score >= rule.score) {
+                return Some((GameResult::WhiteWin, String::from("Black resigns by adjudication")));
+            }
+            if recent.iter().all(|&score| score <= -rule.score) {
+                return Some((GameResult::BlackWin, String::from("White resigns by adjudication")));
+            }
+        }
+    }
+    if let Some(rule) = settings.draw {
+        if full_move >= rule.move_number {
+            if let Some(recent) = recent(rule.moves) {
+                if recent.iter().all(|&score| score.abs() <= rule.score) {
+                    return Some((GameResult::Draw, String::from("Draw by adjudication")));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Plays a game between two engines from an opening. An engine that stops answering loses
+/// the game and is restarted for the next one.
+///
+/// # Arguments
+///
+/// * `white`: The engine playing White
+/// * `black`: The engine playing Black
+/// * `opening`: The position and moves the game starts from
+/// * `settings`: The time control and adjudication rules
+///
+/// returns: GameRecord
+pub fn play_game(white: &mut UciEngine, black: &mut UciEngine, opening: &Opening, settings: &GameSettings) -> GameRecord {
+    let mut record = GameRecord {
+        white: white.name.clone(),
+        black: black.name.clone(),
+        date: pgn_date(),
+        time_control: settings.time_control.pgn(),
+        fen: opening.fen.clone(),
+        moves: Vec::new(),
+        result: GameResult::Draw,
+        termination: "normal",
+        reason: String::new(),
+    };
+    let engines = [white, black];
+    let names = [record.white.clone(), record.black.clone()];
+
+    let mut board = Board::new();
+    if board.set_fen(&opening.fen).is_err() {
+        record.termination = "unterminated";
+        record.reason = String::from("Invalid opening position");
+        return record;
+    }
+    let mut uci_moves = Vec::new();
+    for text in opening.moves.iter() {
+        match move_from_uci(&board, text) {
+            Some(mov) if is_legal(&mut board, mov) => {
+                record.moves.push(move_to_san(&mut board, mov));
+                uci_moves.push(text.clone());
+                board.make_move(mov);
+            }
+            _ => break,
+        }
+    }
+
+    for side in [WHITE, BLACK] {
+        if let Err(err) = engines[side as usize].new_game() {
+            record.result = win_for_opponent(side);
+            record.termination = "abandoned";
+            record.reason = format!("{} fails to start the game: {}", names[side as usize], err.reason);
+            let _ = engines[side as usize].restart();
+            return record;
+        }
+    }
+
+    let time_control = settings.time_control;
+    let mut clocks = [time_control.base; 2];
+    let mut scores = Vec::new();
+    loop {
+        if let Some(outcome) = outcome(&mut board) {
+            let winner = if outcome.result == GameResult::WhiteWin { "White" } else { "Black" };
+            record.result = outcome.result;
+            record.reason = match (outcome.termination, outcome.result) {
+                (Termination::Checkmate, _) => format!("{} mates", winner),
+                (Termination::Stalemate, _) => String::from("Draw by stalemate"),
+                (Termination::Repetition, _) => String::from("Draw by 3-fold repetition"),
+                (Termination::FiftyMove, _) => String::from("Draw by fifty moves rule"),
+                (Termination::InsufficientMaterial, _) => String::from("Draw by insufficient mating material"),
+                (Termination::VariantEnd, GameResult::Draw) => String::from("Draw by variant rule"),
+                (Termination::VariantEnd, _) => format!("{} wins by variant rule", winner),
+            };
+            return record;
+        }
+        if let Some((result, reason)) = adjudicate(settings, &scores, board.full_move()) {
+            record.result = result;
+            record.termination = "adjudication";
+            record.reason = reason;
+            return record;
+        }
+
+        let side = board.side;
+        let mover = side as usize;
+        let position = if opening.fen == Variant::Standard.starting_fen() {
+            format!("startpos moves {}", uci_moves.join(" "))
+        } else {
+            format!("fen {} moves {}", opening.fen, uci_moves.join(" "))
+        };
+        let limits = format!("wtime {} btime {} winc {} binc {}",
+                             clocks[0], clocks[1], time_control.increment, time_control.increment);
+        let start = Instant::now();
+        let reply = engines[mover].go(position.trim_end(), &limits, Duration::from_millis(clocks[mover] + TIME_MARGIN));
+        let elapsed = start.elapsed().as_millis() as u64;
+
+        if elapsed > clocks[mover] + TIME_MARGIN {
+            record.result = win_for_opponent(side);
+            record.termination = "time forfeit";
+            record.reason = format!("{} loses on time", if side == WHITE { "White" } else { "Black" });
+            // The engine may still be thinking, so it is started afresh for the next game
+            let _ = engines[mover].restart();
+            return record;
+        }
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(err) => {
+                record.result = win_for_opponent(side);
+                record.termination = "abandoned";
+                record.reason = format!("{} disconnects: {}", names[mover], err.reason);
+                let _ = engines[mover].restart();
+                return record;
+            }
+        };
+        clocks[mover] = clocks[mover] - elapsed.min(clocks[mover]) + time_control.increment;
+
+        let legal = move_from_uci(&board, &reply.best).filter(|&mov| is_legal(&mut board, mov));
+        let Some(mov) = legal else {
+            record.result = win_for_opponent(side);
+            record.termination = "rules infraction";
+            record.reason = format!("{} makes an illegal move: {}",
+                                    if side == WHITE { "White" } else { "Black" }, reply.best);
+            return record;
+        };
+        record.moves.push(move_to_san(&mut board, mov));
+        uci_moves.push(reply.best);
+        board.make_move(mov);
+        let score = reply.score.map(|score| score.centipawns());
+        scores.push(if side == WHITE { score } else { score.map(|score| -score) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game_board::outcome::GameResult;
+    use crate::tournament::game::{*};
+
+    fn settings(resign: Option<ResignRule>, draw: Option<DrawRule>) -> GameSettings {
+        GameSettings { time_control: TimeControl { base: 10000, increment: 100 }, resign, draw }
+    }
+
+    #[test]
+    fn test_time_control() {
+        assert_eq!(TimeControl::parse("10+0.1"), Some(TimeControl { base: 10000, increment: 100 }));
+        assert_eq!(TimeControl::parse("60"), Some(TimeControl { base: 60000, increment: 0 }));
+        assert_eq!(TimeControl::parse("0+1"), None);
+        assert_eq!(TimeControl::parse("fast"), None);
+        assert_eq!(TimeControl { base: 10000, increment: 100 }.pgn(), "10+0.1");
+        assert_eq!(TimeControl { base: 2500, increment: 0 }.pgn(), "2.5");
+    }
+
+    #[test]
+    fn test_adjudicate() {
+        let resign = settings(Some(ResignRule { moves: 2, score: 500 }), None);
+        let winning = [Some(100), Some(600), Some(700), Some(650), Some(900)];
+        assert_eq!(adjudicate(&resign, &winning[..4], 30), None);
+        assert_eq!(adjudicate(&resign, &winning, 30).map(|(result, _)| result), Some(GameResult::WhiteWin));
+        assert_eq!(adjudicate(&resign, &[Some(-600), None, Some(-800), Some(-900)], 30), None);
+
+        let draw = settings(None, Some(DrawRule { move_number: 40, moves: 2, score: 10 }));
+        let level = [Some(5), Some(-3), Some(0), Some(10)];
+        assert_eq!(adjudicate(&draw, &level, 39), None);
+        assert_eq!(adjudicate(&draw, &level, 40).map(|(result, _)| result), Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn test_pgn() {
+        let record = GameRecord {
+            white: String::from("One"),
+            black: String::from("Two"),
+            date: String::from("2024.01.02"),
+            time_control: String::from("10+0.1"),
+            fen: String::from("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12"),
+            moves: ["Kd7", "e4", "Ke6"].map(String::from).to_vec(),
+            result: GameResult::Draw,
+            termination: "adjudication",
+            reason: String::from("Draw by adjudication"),
+        };
+        let pgn = record.to_pgn("Test", 3);
+        assert!(pgn.starts_with("[Event \"Test\"]\n[Site \"?\"]\n[Date \"2024.01.02\"]\n[Round \"3\"]\n"));
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.contains("[PlyCount \"3\"]\n[Termination \"adjudication\"]\n\n"));
+        assert!(pgn.ends_with("12... Kd7 13. e4 Ke6 {Draw by adjudication} 1/2-1/2\n\n"));
+
+        let long = GameRecord { fen: String::from(Variant::Standard.starting_fen()), moves: vec![String::from("Nf3"); 60], ..record };
+        let pgn = long.to_pgn("Test", 1);
+        assert!(!pgn.contains("[FEN"));
+        assert!(pgn.lines().all(|line| line.len() <= 80));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod engine;
+pub mod openings;
+pub mod game;
+pub mod runner;
+pub mod sprt;
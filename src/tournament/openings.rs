@@ -0,0 +1,174 @@
+//! Opening positions for matches, read from EPD files or from the games of a PGN file
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::game_board::board::Board;
+use crate::utils::io::{move_from_san, move_to_uci};
+use crate::variants::variant::Variant;
+
+/// A position to start a game from, and the moves that lead to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<String>, // In UCI notation, played from the FEN before the engines take over
+}
+
+impl Default for Opening {
+    fn default() -> Self {
+        Opening { fen: Variant::Standard.starting_fen().to_string(), moves: Vec::new() }
+    }
+}
+
+/// Reads openings from EPD lines, each a FEN without the move counters followed by any
+/// operations, which are ignored
+///
+/// # Arguments
+///
+/// * `text`: The contents of an EPD file
+///
+/// returns: (Vec<Opening>, usize) The openings and the number of lines that weren't positions
+pub fn parse_epd(text: &str) -> (Vec<Opening>, usize) {
+    let mut board = Board::new();
+    let mut skipped = 0;
+    let mut openings = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+        let fen = format!("{} 0 1", fields.join(" "));
+        if fields.len() == 4 && board.set_fen(&fen).is_ok() {
+            openings.push(Opening { fen, moves: Vec::new() });
+        } else {
+            skipped += 1;
+        }
+    }
+    (openings, skipped)
+}
+
+/// Reads the moves of each game in a PGN file as an opening, starting from the game's FEN
+/// tag if it has one. Comments, variations, annotations and results are ignored.
+///
+/// # Arguments
+///
+/// * `text`: The contents of a PGN file
+///
+/// returns: (Vec<Opening>, usize) The openings and the number of games with an illegal move
+/// or position
+pub fn parse_pgn(text: &str) -> (Vec<Opening>, usize) {
+    let mut games = Vec::new();
+    let (mut fen, mut movetext) = (None, String::new());
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            // Tags after movetext start the next game
+            if !movetext.trim().is_empty() {
+                games.push((fen.take(), std::mem::take(&mut movetext)));
+            }
+            if let Some(value) = line.strip_prefix("[FEN \"").and_then(|rest| rest.strip_suffix("\"]")) {
+                fen = Some(value.to_string());
+            }
+        } else {
+            movetext.push_str(line.split(';').next().unwrap_or(""));
+            movetext.push(' ');
+        }
+    }
+    if !movetext.trim().is_empty() {
+        games.push((fen, movetext));
+    }
+
+    let mut board = Board::new();
+    let mut skipped = 0;
+    let mut openings = Vec::new();
+    for (fen, movetext) in games {
+        let fen = fen.unwrap_or_else(|| Variant::Standard.starting_fen().to_string());
+        match pgn_moves(&mut board, &fen, &movetext) {
+            Some(moves) => openings.push(Opening { fen, moves }),
+            None => skipped += 1,
+        }
+    }
+    (openings, skipped)
+}
+
+/// Plays out the SAN moves of a game's movetext, giving them back in UCI notation
+fn pgn_moves(board: &mut Board, fen: &str, movetext: &str) -> Option<Vec<String>> {
+    board.set_fen(fen).ok()?;
+    let mut moves = Vec::new();
+    let mut depth = 0; // Nesting of comments and variations
+    let spaced = movetext.replace('{', " { ").replace('}', " } ").replace('(', " ( ").replace(')', " ) ");
+    for token in spaced.split_whitespace() {
+        match token {
+            "{" | "(" => depth += 1,
+            "}" | ")" => depth -= 1,
+            _ if depth > 0 => (),
+            "1-0" | "0-1" | "1/2-1/2" | "*" => break,
+            _ if token.starts_with('$') => (),
+            _ => {
+                // Move numbers may be joined to the move, as in `1.e4`
+                let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if san.is_empty() {
+                    continue;
+                }
+                let mov = move_from_san(board, san)?;
+                moves.push(move_to_uci(board, mov));
+                board.make_move(mov);
+            }
+        }
+    }
+    Some(moves)
+}
+
+/// Loads openings from a file, read as PGN when its name ends in `.pgn` and as EPD otherwise
+///
+/// # Arguments
+///
+/// * `path`: The file to read
+///
+/// returns: Result<(Vec<Opening>, usize), io::Error> The openings and the number of entries skipped
+pub fn load_openings(path: impl AsRef<Path>) -> Result<(Vec<Opening>, usize), io::Error> {
+    let text = fs::read_to_string(path.as_ref())?;
+    let is_pgn = path.as_ref().extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pgn"));
+    Ok(if is_pgn { parse_pgn(&text) } else { parse_epd(&text) })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tournament::openings::{parse_epd, parse_pgn, Opening};
+
+    #[test]
+    fn test_parse_epd() {
+        let text = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"king pawn\";\n\
+                    # comment\n\
+                    not a position\n\
+                    8/8/4k3/3p4/3P4/4K3/8/8 w - -\n";
+        let (openings, skipped) = parse_epd(text);
+        assert_eq!(skipped, 1);
+        assert_eq!(openings.len(), 2);
+        assert_eq!(openings[0].fen, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        assert!(openings[1].moves.is_empty());
+    }
+
+    #[test]
+    fn test_parse_pgn() {
+        let text = r#"[Event "Opening one"]
+[White "?"]
+
+1. e4 e5 {main line} 2. Nf3 (2. f4 exf4) Nc6 3.Bb5 $1 a6 *
+
+[Event "From a position"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4 Kd7 2. Kf2 ; a comment
+1-0
+
+[Event "Illegal"]
+
+1. e4 e4 *
+"#;
+        let (openings, skipped) = parse_pgn(text);
+        assert_eq!(skipped, 1);
+        assert_eq!(openings[0], Opening {
+            fen: Opening::default().fen,
+            moves: ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"].map(String::from).to_vec(),
+        });
+        assert_eq!(openings[1].fen, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(openings[1].moves, ["e2e4", "e8d7", "e1f2"]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Running a match between two engines over several threads, each with its own pair of engine
+//! processes. Each opening is played twice with the colors swapped, and the games are handed
+//! back in order so the results don't depend on which thread finished first.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use crate::game_board::outcome::GameResult;
+use crate::tournament::engine::{EngineConfig, EngineError, UciEngine};
+use crate::tournament::game::{play_game, GameRecord, GameSettings};
+use crate::tournament::openings::Opening;
+use crate::utils::in_order::InOrder;
+
+// Standard normal quantile of the 95% confidence interval
+const CONFIDENCE_95: f64 = 1.959964;
+
+/// Settings for a match
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    pub engines: [EngineConfig; 2],
+    pub games: usize,
+    pub concurrency: usize, // Games played at the same time
+    pub openings: Vec<Opening>, // Used in turn, the starting position if empty
+    pub settings: GameSettings,
+}
+
+/// Wins, draws and losses from the first engine's point of view
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchScore {
+    /// Counts the result of a game
+    ///
+    /// # Arguments
+    ///
+    /// * `result`: The game's result
+    /// * `first_is_white`: Whether the first engine played White
+    ///
+    /// returns: ()
+    pub fn add(&mut self, result: GameResult, first_is_white: bool) {
+        match half_points(result, first_is_white) {
+            2 => self.wins += 1,
+            1 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The first engine's points per game, between 0 and 1
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// The first engine's Elo advantage and the half width of its 95% confidence interval
+    ///
+    /// returns: (f64, f64) The error is infinite when every game has the same result
+    pub fn elo(&self) -> (f64, f64) {
+        let games = self.games() as f64;
+        let score = self.score();
+        if games == 0.0 {
+            return (0.0, f64::INFINITY);
+        }
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2)) / games;
+        elo_interval(score, (variance / games).sqrt())
+    }
+}
+
+/// The first engine's half points from a game: 2 for a win, 1 for a draw and 0 for a loss
+///
+/// # Arguments
+///
+/// * `result`: The game's result
+/// * `first_is_white`: Whether the first engine played White
+///
+/// returns: usize
+pub fn half_points(result: GameResult, first_is_white: bool) -> usize {
+    match (result, first_is_white) {
+        (GameResult::Draw, _) => 1,
+        (GameResult::WhiteWin, true) | (GameResult::BlackWin, false) => 2,
+        _ => 0,
+    }
+}
+
+/// The Elo difference that gives a player the expected score
+///
+/// # Arguments
+///
+/// * `score`: Points per game, between 0 and 1
+///
+/// returns: f64
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// The expected score of a player with an Elo advantage, the reverse of `elo_from_score`
+///
+/// # Arguments
+///
+/// * `elo`: The Elo difference
+///
+/// returns: f64
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The Elo difference for a mean score and the half width of its 95% confidence interval
+///
+/// # Arguments
+///
+/// * `score`: The mean score per game
+/// * `std_error`: The standard error of the mean score
+///
+/// returns: (f64, f64) The error is infinite when the interval reaches a score of 0 or 1, or
+/// when the results vary too little to measure
+pub(crate) fn elo_interval(score: f64, std_error: f64) -> (f64, f64) {
+    if std_error <= 0.0 || std_error.is_nan() {
+        return (elo_from_score(score), f64::INFINITY);
+    }
+    let margin = CONFIDENCE_95 * std_error;
+    let low = elo_from_score((score - margin).max(0.0));
+    let high = elo_from_score((score + margin).min(1.0));
+    let error = if low.is_finite() && high.is_finite() { (high - low) / 2.0 } else { f64::INFINITY };
+    (elo_from_score(score), error)
+}
+
+/// Plays a match across threads, handing each game to `sink` in game order as they finish.
+/// The games with an even index have the first engine as White, and each pair of games plays
+/// the next opening.
+///
+/// # Arguments
+///
+/// * `config`: The engines, openings and game settings
+/// * `sink`: Called with each game's index and record, returns false to end the match early
+///
+/// returns: Result<(), EngineError> An error if an engine can't be started
+pub fn run_match(config: &MatchConfig, mut sink: impl FnMut(usize, &GameRecord) -> bool) -> Result<(), EngineError> {
+    let next_pair = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..config.concurrency.clamp(1, config.games.max(1)) {
+            let sender = sender.clone();
+            let (next_pair, stopped) = (&next_pair, &stopped);
+            scope.spawn(move || {
+                let engines = UciEngine::start(&config.engines[0])
+                    .and_then(|first| Ok([first, UciEngine::start(&config.engines[1])?]));
+                let mut engines = match engines {
+                    Ok(engines) => engines,
+                    Err(err) => {
+                        let _ = sender.send(Err(err));
+                        return;
+                    }
+                };
+                let default_opening = Opening::default();
+                loop {
+                    let pair = next_pair.fetch_add(1, Ordering::Relaxed);
+                    if stopped.load(Ordering::Relaxed) || pair * 2 >= config.games {
+                        break;
+                    }
+                    let opening = match config.openings.len() {
+                        0 => &default_opening,
+                        count => &config.openings[pair % count],
+                    };
+                    for game in pair * 2..(pair * 2 + 2).min(config.games) {
+                        let [first, second] = &mut engines;
+                        let record = if game % 2 == 0 {
+                            play_game(first, second, opening, &config.settings)
+                        } else {
+                            play_game(second, first, opening, &config.settings)
+                        };
+                        if sender.send(Ok((game, record))).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut finished = InOrder::default();
+        for message in receiver {
+            let (game, record) = match message {
+                Ok(game) => game,
+                Err(err) => {
+                    stopped.store(true, Ordering::Relaxed);
+                    return Err(err);
+                }
+            };
+            finished.insert(game, record, |game, record| {
+                if !stopped.load(Ordering::Relaxed) && !sink(game, &record) {
+                    stopped.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game_board::outcome::GameResult;
+    use crate::tournament::runner::{elo_from_score, score_from_elo, MatchScore};
+
+    #[test]
+    fn test_match_score() {
+        let mut score = MatchScore::default();
+        score.add(GameResult::WhiteWin, true);
+        score.add(GameResult::WhiteWin, false);
+        score.add(GameResult::BlackWin, false);
+        score.add(GameResult::Draw, true);
+        assert_eq!(score, MatchScore { wins: 2, draws: 1, losses: 1 });
+        assert_eq!(score.score(), 0.625);
+
+        assert!(elo_from_score(0.5).abs() < 1e-9);
+        assert!((elo_from_score(0.75) - 190.85).abs() < 0.01);
+        assert!((elo_from_score(0.25) + 190.85).abs() < 0.01);
+        assert!((score_from_elo(elo_from_score(0.6)) - 0.6).abs() < 1e-9);
+
+        let (elo, error) = MatchScore { wins: 30, draws: 40, losses: 30 }.elo();
+        assert!(elo.abs() < 1e-9);
+        assert!(error > 40.0 && error < 60.0);
+        assert_eq!(MatchScore { wins: 5, draws: 0, losses: 0 }.elo(), (f64::INFINITY, f64::INFINITY));
+        assert_eq!(MatchScore { wins: 1, draws: 0, losses: 3 }.elo().1, f64::INFINITY);
+        assert_eq!(MatchScore { wins: 0, draws: 4, losses: 0 }.elo(), (0.0, f64::INFINITY));
+    }
+}
@@ -0,0 +1,186 @@
+//! Sequential probability ratio test, for deciding with as few games as needed whether a
+//! change makes the engine stronger. Results are counted in pairs of games played from the
+//! same opening with the colors swapped, which cancels most of the opening's bias.
+
+use crate::tournament::runner::{elo_interval, score_from_elo};
+
+// Count used in place of an empty pentanomial bucket when computing the LLR, so a handful of
+// identical pairs doesn't give a variance of 0
+const EMPTY_BUCKET: f64 = 1e-3;
+
+/// The first engine's results counted by game pair, indexed by the half points it scored
+/// across both games, from 0 for two losses up to 4 for two wins
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Pentanomial {
+    pub counts: [usize; 5],
+}
+
+impl Pentanomial {
+    /// Counts a game pair
+    ///
+    /// # Arguments
+    ///
+    /// * `first`: The first engine's half points in the first game of the pair
+    /// * `second`: The first engine's half points in the second game of the pair
+    ///
+    /// returns: ()
+    pub fn add(&mut self, first: usize, second: usize) {
+        self.counts[first + second] += 1;
+    }
+
+    pub fn pairs(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// The mean score per game and the variance of a pair's score per game, from the counts
+    fn mean_variance(counts: [f64; 5]) -> (f64, f64) {
+        let pairs: f64 = counts.iter().sum();
+        let score = |bucket: usize| bucket as f64 / 4.0;
+        let mean = counts.iter().enumerate().map(|(bucket, count)| count * score(bucket)).sum::<f64>() / pairs;
+        let variance = counts.iter().enumerate()
+            .map(|(bucket, count)| count * (score(bucket) - mean).powi(2))
+            .sum::<f64>() / pairs;
+        (mean, variance)
+    }
+
+    /// The first engine's Elo advantage and the half width of its 95% confidence interval
+    ///
+    /// returns: (f64, f64) The error is infinite when every pair has the same result
+    pub fn elo(&self) -> (f64, f64) {
+        if self.pairs() == 0 {
+            return (0.0, f64::INFINITY);
+        }
+        let (mean, variance) = Pentanomial::mean_variance(self.counts.map(|count| count as f64));
+        elo_interval(mean, (variance / self.pairs() as f64).sqrt())
+    }
+}
+
+/// The outcome of a test so far
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SprtStatus {
+    Continue,
+    AcceptH0, // The first engine isn't stronger by elo1
+    AcceptH1, // The first engine is stronger by more than elo0
+}
+
+/// A test of the hypothesis H0, that the first engine is elo0 stronger, against H1, that it
+/// is elo1 stronger, with alpha the chance of accepting H1 when H0 holds and beta the chance
+/// of accepting H0 when H1 holds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SprtConfig {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for SprtConfig {
+    fn default() -> Self {
+        SprtConfig { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 }
+    }
+}
+
+impl SprtConfig {
+    /// The log likelihood ratios at which H0 and H1 are accepted
+    ///
+    /// returns: (f64, f64) The lower bound, accepting H0, and the upper bound, accepting H1
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// The log likelihood ratio of H1 against H0 for the results so far, using the normal
+    /// approximation of the generalized SPRT with the pentanomial variance
+    ///
+    /// # Arguments
+    ///
+    /// * `results`: The game pairs played
+    ///
+    /// returns: f64 0 before any pair has been played
+    pub fn llr(&self, results: &Pentanomial) -> f64 {
+        if results.pairs() == 0 {
+            return 0.0;
+        }
+        let counts = results.counts.map(|count| if count == 0 { EMPTY_BUCKET } else { count as f64 });
+        let pairs: f64 = counts.iter().sum();
+        let (mean, variance) = Pentanomial::mean_variance(counts);
+        let (score0, score1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        pairs * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+
+    /// Decides whether the test is over
+    ///
+    /// # Arguments
+    ///
+    /// * `results`: The game pairs played
+    ///
+    /// returns: SprtStatus
+    pub fn status(&self, results: &Pentanomial) -> SprtStatus {
+        let llr = self.llr(results);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtStatus::AcceptH1
+        } else if llr <= lower {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tournament::sprt::{Pentanomial, SprtConfig, SprtStatus};
+
+    #[test]
+    fn test_bounds() {
+        let (lower, upper) = SprtConfig::default().bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+        let (lower, upper) = SprtConfig { alpha: 0.01, beta: 0.1, ..SprtConfig::default() }.bounds();
+        assert!((lower + 2.293).abs() < 0.001);
+        assert!((upper - 4.500).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_pentanomial() {
+        let mut results = Pentanomial::default();
+        results.add(2, 0);
+        results.add(1, 2);
+        results.add(2, 2);
+        assert_eq!(results.counts, [0, 0, 1, 1, 1]);
+        assert_eq!(results.pairs(), 3);
+        assert!((results.elo().0 - 190.85).abs() < 0.01);
+        assert_eq!(Pentanomial { counts: [0, 0, 5, 0, 0] }.elo(), (0.0, f64::INFINITY));
+
+        let even = Pentanomial { counts: [10, 20, 40, 20, 10] };
+        let (elo, error) = even.elo();
+        assert!(elo.abs() < 1e-9);
+        assert!(error > 30.0 && error < 60.0);
+    }
+
+    #[test]
+    fn test_llr() {
+        let sprt = SprtConfig { elo0: 0.0, elo1: 10.0, ..SprtConfig::default() };
+        assert_eq!(sprt.llr(&Pentanomial::default()), 0.0);
+        assert_eq!(sprt.status(&Pentanomial::default()), SprtStatus::Continue);
+
+        // Scoring halfway between the hypotheses favors neither
+        let halfway = Pentanomial { counts: [100, 200, 400, 200, 100] };
+        let halfway_elo = SprtConfig { elo0: -5.0, elo1: 5.0, ..SprtConfig::default() };
+        assert!(halfway_elo.llr(&halfway).abs() < 1e-9);
+
+        let weaker = Pentanomial { counts: [40, 300, 1000, 250, 30] };
+        assert!(sprt.llr(&weaker) < 0.0);
+        assert_eq!(sprt.status(&weaker), SprtStatus::AcceptH0);
+
+        let stronger = Pentanomial { counts: [30, 250, 1000, 300, 40] };
+        assert!(sprt.llr(&stronger) > 0.0);
+        assert_eq!(sprt.status(&stronger), SprtStatus::Continue);
+        let much_stronger = Pentanomial { counts: [20, 200, 1000, 350, 60] };
+        assert_eq!(sprt.status(&much_stronger), SprtStatus::AcceptH1);
+
+        // More pairs with the same spread of results give more evidence
+        let twice = Pentanomial { counts: stronger.counts.map(|count| count * 2) };
+        assert!(sprt.llr(&twice) > sprt.llr(&stronger) * 1.99);
+    }
+}
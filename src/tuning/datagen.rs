@@ -0,0 +1,410 @@
+//! Training data from self-play. The engine plays itself from random openings at a fixed
+//! depth, and the quiet positions of each game are kept with their search score and the
+//! game's result, ready for tuning. Every game has its own seed, so the data only depends on
+//! the seed given and not on how many threads play the games.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::alphabeta::search::{search_position, SearhInfo, MATE, MAX_DEPTH};
+use crate::constants::pieces::{*};
+use crate::constants::squares::NO_SQ;
+use crate::game_board::board::Board;
+use crate::game_board::outcome::{outcome, GameResult};
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::generate_all_moves;
+use crate::moves::see::hanging_pieces;
+use crate::utils::in_order::InOrder;
+use crate::variants::variant::Variant;
+
+/// Bytes taken by a position in the binary format
+pub const RECORD_SIZE: usize = 33;
+// A game is adjudicated once the score stays this far from zero for RESIGN_PLIES plies
+const RESIGN_SCORE: i32 = 2000;
+const RESIGN_PLIES: usize = 6;
+
+/// Settings for generating self-play data
+#[derive(Debug, Clone)]
+pub struct DatagenConfig {
+    pub games: usize,
+    pub depth: u64,
+    pub nodes: Option<u64>, // Nodes each move's search may use, on top of the depth limit
+    pub threads: usize,
+    pub seed: u64,
+    pub random_plies: usize, // Random moves played from the opening before the engine takes over
+    pub max_plies: usize, // Games still going after this many plies are scored as draws
+    pub openings: Vec<String>, // Valid FENs to start from, the starting position if empty
+}
+
+impl Default for DatagenConfig {
+    fn default() -> Self {
+        DatagenConfig {
+            games: 100,
+            depth: 6,
+            nodes: None,
+            threads: 1,
+            seed: 0,
+            random_plies: 8,
+            max_plies: 400,
+            openings: Vec::new(),
+        }
+    }
+}
+
+/// A position from a self-play game packed into a few bytes: the occupied squares, then a
+/// piece code for each of them in square order, two to a byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataPosition {
+    pub occupied: u64,
+    pub pieces: [u8; 16],
+    pub side: u8,
+    pub castle_perm: u8,
+    pub en_passant: u8, // 64 square number, 64 for none
+    pub fifty_move: u8,
+    pub full_move: u16,
+    pub score: i16, // Search score from white's point of view
+    pub result: GameResult,
+}
+
+impl DataPosition {
+    /// Packs a standard chess position with its score and the game's result
+    ///
+    /// # Arguments
+    ///
+    /// * `pos`: The board state
+    /// * `score`: The search score from white's point of view
+    /// * `result`: How the game ended
+    ///
+    /// returns: DataPosition
+    pub fn from_board(pos: &Board, score: i32, result: GameResult) -> DataPosition {
+        let mut occupied = 0;
+        let mut pieces = [0; 16];
+        let mut count = 0;
+        for sq64 in 0..64 {
+            let pce = pos.pieces[pos.sq120(sq64) as usize];
+            if pce != EMPTY && count < 32 {
+                occupied |= 1 << sq64;
+                pieces[count / 2] |= pce << (4 * (count % 2));
+                count += 1;
+            }
+        }
+        DataPosition {
+            occupied,
+            pieces,
+            side: pos.side,
+            castle_perm: pos.castle_perm,
+            en_passant: if pos.en_passant == NO_SQ { 64 } else { pos.sq64(pos.en_passant) },
+            fifty_move: pos.fifty_move.min(255) as u8,
+            full_move: pos.full_move().min(u16::MAX as u32) as u16,
+            score: score.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            result,
+        }
+    }
+
+    /// Writes the position as a FEN
+    pub fn to_fen(&self) -> String {
+        let mut board = [EMPTY; 64];
+        let mut count = 0;
+        for (sq64, square) in board.iter_mut().enumerate() {
+            if self.occupied & 1 << sq64 != 0 {
+                *square = self.pieces[count / 2] >> (4 * (count % 2)) & 0xF;
+                count += 1;
+            }
+        }
+
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let pce = board[rank * 8 + file];
+                if pce == EMPTY {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(PIECE_CHARS[pce as usize]);
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.side == WHITE { " w " } else { " b " });
+        let castle: String = CASTLE_RIGHTS.iter().zip("KQkq".chars())
+            .filter(|(right, _)| self.castle_perm & **right != 0)
+            .map(|(_, ch)| ch)
+            .collect();
+        fen.push_str(if castle.is_empty() { "-" } else { &castle });
+        if self.en_passant < 64 {
+            fen.push_str(&format!(" {}{}", (b'a' + self.en_passant % 8) as char, (b'1' + self.en_passant / 8) as char));
+        } else {
+            fen.push_str(" -");
+        }
+        fen.push_str(&format!(" {} {}", self.fifty_move, self.full_move));
+        fen
+    }
+
+    /// Writes the position as a line of text, the FEN, score and result. The tuner reads
+    /// these lines as they are.
+    pub fn to_text(&self) -> String {
+        let result = match self.result {
+            GameResult::WhiteWin => "1.0",
+            GameResult::Draw => "0.5",
+            GameResult::BlackWin => "0.0",
+        };
+        format!("{} {} [{}]", self.to_fen(), self.score, result)
+    }
+
+    /// Writes the position in the binary format, all numbers little endian
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.occupied.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces);
+        bytes[24] = self.side;
+        bytes[25] = self.castle_perm;
+        bytes[26] = self.en_passant;
+        bytes[27] = self.fifty_move;
+        bytes[28..30].copy_from_slice(&self.full_move.to_le_bytes());
+        bytes[30..32].copy_from_slice(&self.score.to_le_bytes());
+        bytes[32] = match self.result {
+            GameResult::BlackWin => 0,
+            GameResult::Draw => 1,
+            GameResult::WhiteWin => 2,
+        };
+        bytes
+    }
+
+    /// Reads a position written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Option<DataPosition> {
+        let result = match bytes[32] {
+            0 => GameResult::BlackWin,
+            1 => GameResult::Draw,
+            2 => GameResult::WhiteWin,
+            _ => return None,
+        };
+        Some(DataPosition {
+            occupied: u64::from_le_bytes(bytes[0..8].try_into().ok()?),
+            pieces: bytes[8..24].try_into().ok()?,
+            side: bytes[24],
+            castle_perm: bytes[25],
+            en_passant: bytes[26],
+            fifty_move: bytes[27],
+            full_move: u16::from_le_bytes([bytes[28], bytes[29]]),
+            score: i16::from_le_bytes([bytes[30], bytes[31]]),
+            result,
+        })
+    }
+}
+
+/// Whether a position is quiet enough to learn a static evaluation from: the side to move
+/// isn't in check, neither side has a piece hanging by static exchange evaluation, and the
+/// best move found is neither a capture nor a promotion
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `best`: The best move the search found
+///
+/// returns: bool
+pub fn is_quiet(pos: &Board, best: GameMove) -> bool {
+    best.capture() == EMPTY && !best.is_en_passant() && best.promoted_piece() == EMPTY
+        && pos.checkers().is_empty()
+        && hanging_pieces(pos, WHITE).is_empty() && hanging_pieces(pos, BLACK).is_empty()
+}
+
+fn legal_moves(pos: &mut Board) -> Vec<GameMove> {
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    move_list.retain(|mov| {
+        let legal = pos.make_move(*mov);
+        if legal {
+            pos.undo_move();
+        }
+        legal
+    });
+    move_list
+}
+
+/// Sets up the opening of a game, playing random moves from one of the openings until the
+/// game can go on without being over already
+fn play_opening(pos: &mut Board, config: &DatagenConfig, rng: &mut StdRng) {
+    loop {
+        let fen = match config.openings.len() {
+            0 => Variant::Standard.starting_fen(),
+            len => &config.openings[rng.gen_range(0..len)],
+        };
+        pos.set_fen(fen).expect("Opening did not parse");
+        for _ in 0..config.random_plies {
+            let moves = legal_moves(pos);
+            if moves.is_empty() {
+                break;
+            }
+            pos.make_move(moves[rng.gen_range(0..moves.len())]);
+        }
+        if outcome(pos).is_none() {
+            return;
+        }
+    }
+}
+
+/// Plays one game of self-play and returns its quiet positions
+///
+/// # Arguments
+///
+/// * `config`: The generation settings
+/// * `game`: The number of the game, which with the seed decides its opening
+///
+/// returns: Vec<DataPosition>
+pub fn play_game(config: &DatagenConfig, game: usize) -> Vec<DataPosition> {
+    let mut rng = StdRng::seed_from_u64(config.seed ^ (game as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    let mut pos = Board::with_seed(config.seed);
+    play_opening(&mut pos, config, &mut rng);
+
+    // Positions are kept as a draw until the result is known
+    let mut recorded: Vec<DataPosition> = Vec::new();
+    // One transposition table for the whole game
+    let template = SearhInfo {
+        depthset: config.depth,
+        node_limit: config.nodes.unwrap_or(u64::MAX),
+        post_thinking: false,
+        ..SearhInfo::default()
+    };
+    let mut decisive_plies = 0;
+    let mut last_score: i32 = 0;
+    let result = loop {
+        if let Some(outcome) = outcome(&mut pos) {
+            break outcome.result;
+        }
+        if pos.history.len() >= config.max_plies {
+            break GameResult::Draw;
+        }
+
+        let mut info = template.clone();
+        let best = match search_position(&mut pos, &mut info) {
+            Some(best) => best,
+            None => break GameResult::Draw,
+        };
+        let score = if pos.side == WHITE { info.score } else { -info.score };
+
+        decisive_plies = if score.abs() >= RESIGN_SCORE && score.signum() == last_score.signum() { decisive_plies + 1 } else { 0 };
+        last_score = score;
+        if decisive_plies >= RESIGN_PLIES {
+            break if score > 0 { GameResult::WhiteWin } else { GameResult::BlackWin };
+        }
+
+        if score.abs() < MATE - MAX_DEPTH as i32 && is_quiet(&pos, best) {
+            recorded.push(DataPosition::from_board(&pos, score, GameResult::Draw));
+        }
+        pos.make_move(best);
+    };
+
+    recorded.into_iter().map(|position| DataPosition { result, ..position }).collect()
+}
+
+/// Plays the games of self-play across threads, handing each game's positions to `sink` in
+/// game order as they finish
+///
+/// # Arguments
+///
+/// * `config`: The generation settings
+/// * `sink`: Called with each game's number and positions
+///
+/// returns: ()
+pub fn generate(config: &DatagenConfig, mut sink: impl FnMut(usize, Vec<DataPosition>)) {
+    let next_game = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            scope.spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= config.games || sender.send((game, play_game(config, game))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut finished = InOrder::default();
+        for (game, positions) in receiver {
+            finished.insert(game, positions, &mut sink);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game_board::board::Board;
+    use crate::game_board::outcome::GameResult;
+    use crate::moves::gamemove::GameMove;
+    use crate::tuning::datagen::{*};
+    use crate::tuning::texel::parse_line;
+    use crate::utils::io::{parse_move, validate_move};
+
+    fn config(threads: usize) -> DatagenConfig {
+        DatagenConfig { games: 3, depth: 2, threads, seed: 42, random_plies: 6, max_plies: 40, ..DatagenConfig::default() }
+    }
+
+    fn find_move(pos: &Board, text: &str) -> GameMove {
+        let (from, to, prom) = validate_move(pos, text).unwrap();
+        parse_move(pos, from, to, prom).unwrap()
+    }
+
+    #[test]
+    fn test_packing() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 0 3",
+            "8/8/4k3/8/8/8/8/4K3 b - - 57 102",
+        ] {
+            let position = DataPosition::from_board(&Board::from_fen(fen).unwrap(), -35, GameResult::BlackWin);
+            assert_eq!(position.to_fen(), fen);
+            assert_eq!(DataPosition::from_bytes(&position.to_bytes()), Some(position));
+            assert_eq!(parse_line(&position.to_text()), Some((fen.to_string(), 0.0)), "Tuner can't read the text");
+        }
+    }
+
+    #[test]
+    fn test_is_quiet() {
+        let pos = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        assert!(is_quiet(&pos, find_move(&pos, "f1b5")));
+        let hanging = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert!(!is_quiet(&hanging, find_move(&hanging, "g1f3")), "Pawns can be taken");
+        let capture = Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert!(!is_quiet(&capture, find_move(&capture, "e4d5")));
+        let check = Board::from_fen("rnbqkbnr/ppp2ppp/3p4/1B2p3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 1 3").unwrap();
+        assert!(!is_quiet(&check, find_move(&check, "c7c6")));
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        let collect = |threads| {
+            let mut games = Vec::new();
+            generate(&config(threads), |game, positions| games.push((game, positions)));
+            games
+        };
+        let single = collect(1);
+        assert_eq!(single.iter().map(|(game, _)| *game).collect::<Vec<usize>>(), vec![0, 1, 2]);
+        assert!(single.iter().any(|(_, positions)| !positions.is_empty()));
+        assert_eq!(collect(2), single, "Threads changed the data");
+        assert_ne!(play_game(&DatagenConfig { seed: 43, ..config(1) }, 0), single[0].1, "Seed had no effect");
+    }
+
+    #[test]
+    fn test_node_budget() {
+        let budget = DatagenConfig { depth: u64::MAX, nodes: Some(300), ..config(1) };
+        let positions = play_game(&budget, 0);
+        assert!(!positions.is_empty());
+        assert_eq!(play_game(&budget, 0), positions, "Node limited games are not repeatable");
+        assert_ne!(play_game(&DatagenConfig { nodes: Some(3000), ..budget }, 0), positions, "Node budget had no effect");
+    }
+}
@@ -0,0 +1,237 @@
+//! Texel tuning: fits the evaluation parameters to the results of games. Each position's
+//! evaluation is turned into an expected score with a logistic curve, and the parameters are
+//! moved by gradient descent to lower the mean squared error against the game's result.
+
+use crate::evaluation::params::{coefficients, Coefficients, MATERIAL_START, PARAM_COUNT};
+use crate::evaluation::tapered::Score;
+use crate::game_board::board::Board;
+
+/// A quiet position and the result of the game it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningPosition {
+    pub coefficients: Coefficients,
+    pub result: f64, // 1 for a white win, 0.5 for a draw and 0 for a black win
+}
+
+/// Reads a game result in any of the usual notations: 1-0, 1/2-1/2 or 0-1, or 1.0, 0.5
+/// or 0.0, possibly quoted or in brackets
+pub fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|c| matches!(c, '"' | '[' | ']' | ';')) {
+        "1-0" | "1.0" => Some(1.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        "0-1" | "0.0" => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Splits a line of a positions file into its FEN and result. The result is the last field
+/// of the line, and anything after the FEN's counters, like an EPD `c9` opcode, is ignored.
+///
+/// # Arguments
+///
+/// * `line`: The line, e.g. `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 [0.5]`
+///
+/// returns: Option<(String, f64)> The FEN and the result from white's point of view
+pub fn parse_line(line: &str) -> Option<(String, f64)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (last, rest) = fields.split_last()?;
+    let result = parse_result(last)?;
+    let counters = rest.iter().skip(4).take(2).take_while(|field| field.parse::<u64>().is_ok()).count();
+    Some((rest[..rest.len().min(4 + counters)].join(" "), result))
+}
+
+/// Reads the positions to tune with, skipping lines that don't parse and positions with the
+/// side to move in check, which aren't quiet
+///
+/// # Arguments
+///
+/// * `text`: The positions file, one FEN and result on each line
+///
+/// returns: (Vec<TuningPosition>, usize) The positions and the number of lines skipped
+pub fn load_positions(text: &str) -> (Vec<TuningPosition>, usize) {
+    let mut board = Board::new();
+    let mut positions = Vec::new();
+    let mut skipped = 0;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_line(line) {
+            Some((fen, result)) if board.set_fen(&fen).is_ok() && board.checkers().is_empty() => {
+                positions.push(TuningPosition { coefficients: coefficients(&board), result });
+            }
+            _ => skipped += 1,
+        }
+    }
+    (positions, skipped)
+}
+
+/// Turns parameters into the flat vector the tuner works on, midgame then endgame value
+pub fn to_scalars(params: &[Score]) -> Vec<f64> {
+    params.iter().flat_map(|score| [score.mg as f64, score.eg as f64]).collect()
+}
+
+/// Rounds the tuner's flat vector back into parameters
+pub fn from_scalars(scalars: &[f64]) -> Vec<Score> {
+    scalars.chunks(2).map(|pair| Score::new(pair[0].round() as i32, pair[1].round() as i32)).collect()
+}
+
+/// The expected score for white of a position evaluated at a number of centipawns
+#[inline]
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+#[inline]
+fn linear_evaluation(position: &TuningPosition, scalars: &[f64]) -> f64 {
+    let (mg, eg) = position.coefficients.phase_factors();
+    position.coefficients.entries.iter()
+        .map(|(index, count)| *count as f64 * (scalars[2 * *index as usize] * mg + scalars[2 * *index as usize + 1] * eg))
+        .sum()
+}
+
+/// Mean squared error between the results and the expected scores of the positions
+///
+/// # Arguments
+///
+/// * `positions`: The positions to tune with
+/// * `scalars`: The parameters as a flat vector, see `to_scalars`
+/// * `k`: Scaling constant of the logistic curve
+///
+/// returns: f64
+pub fn evaluation_error(positions: &[TuningPosition], scalars: &[f64], k: f64) -> f64 {
+    let total: f64 = positions.iter()
+        .map(|position| (position.result - sigmoid(linear_evaluation(position, scalars), k)).powi(2))
+        .sum();
+    total / positions.len().max(1) as f64
+}
+
+/// Finds the scaling constant that makes the current evaluation best predict the results,
+/// narrowing in on the minimum one decimal place at a time
+pub fn fit_k(positions: &[TuningPosition], scalars: &[f64]) -> f64 {
+    let (mut best, mut step) = (1.0, 1.0);
+    for _ in 0..4 {
+        let candidates = (-10..=10).map(|i| best + i as f64 * step).filter(|k| *k > 0.0);
+        best = candidates
+            .map(|k| (k, evaluation_error(positions, scalars, k)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(best, |(k, _)| k);
+        step /= 10.0;
+    }
+    best
+}
+
+/// Gradient descent on the evaluation parameters using Adam, which adapts the step of each
+/// parameter to how consistently its gradient points one way. The pawn's midgame value is
+/// held at its starting value so scores stay in centipawns.
+///
+/// # Arguments
+///
+/// * `positions`: The positions to tune with
+/// * `scalars`: The parameters as a flat vector, updated in place
+/// * `k`: Scaling constant of the logistic curve
+/// * `epochs`: Passes over all the positions, each making one step
+/// * `learning_rate`: The largest step in centipawns a parameter takes each epoch
+/// * `report`: Called after each epoch with its number and the error
+///
+/// returns: ()
+pub fn tune(positions: &[TuningPosition], scalars: &mut [f64], k: f64, epochs: usize, learning_rate: f64,
+            mut report: impl FnMut(usize, f64)) {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+    let mut momentum = vec![0.0; scalars.len()];
+    let mut velocity = vec![0.0; scalars.len()];
+    let fixed = 2 * MATERIAL_START;
+
+    for epoch in 1..=epochs {
+        let mut gradient = vec![0.0; scalars.len()];
+        for position in positions {
+            let expected = sigmoid(linear_evaluation(position, scalars), k);
+            // Derivative of the squared error with respect to the evaluation
+            let slope = -2.0 * (position.result - expected) * expected * (1.0 - expected) * k * 10f64.ln() / 400.0;
+            let (mg, eg) = position.coefficients.phase_factors();
+            for (index, count) in position.coefficients.entries.iter() {
+                gradient[2 * *index as usize] += slope * *count as f64 * mg;
+                gradient[2 * *index as usize + 1] += slope * *count as f64 * eg;
+            }
+        }
+
+        for i in 0..scalars.len().min(2 * PARAM_COUNT) {
+            if i == fixed {
+                continue;
+            }
+            let grad = gradient[i] / positions.len().max(1) as f64;
+            momentum[i] = BETA1 * momentum[i] + (1.0 - BETA1) * grad;
+            velocity[i] = BETA2 * velocity[i] + (1.0 - BETA2) * grad * grad;
+            let corrected_momentum = momentum[i] / (1.0 - BETA1.powi(epoch as i32));
+            let corrected_velocity = velocity[i] / (1.0 - BETA2.powi(epoch as i32));
+            scalars[i] -= learning_rate * corrected_momentum / (corrected_velocity.sqrt() + EPSILON);
+        }
+        report(epoch, evaluation_error(positions, scalars, k));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::evaluation::params::{default_params, MATERIAL_START};
+    use crate::evaluation::terms::EvalWeights;
+    use crate::tuning::texel::{*};
+
+    #[test]
+    fn test_parse_line() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(parse_line(&format!("{} [0.5]", start)), Some((start.to_string(), 0.5)));
+        assert_eq!(parse_line(&format!("{} \"1-0\";", start)), Some((start.to_string(), 1.0)));
+        assert_eq!(parse_line("8/8/8/8/8/8/8/K1k5 b - - c9 \"0-1\";"), Some(("8/8/8/8/8/8/8/K1k5 b - -".to_string(), 0.0)));
+        assert_eq!(parse_line(start), None, "No result");
+    }
+
+    #[test]
+    fn test_load_positions() {
+        let text = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 [0.5]\n\
+                    not a position [1.0]\n\
+                    4k3/8/8/8/8/8/8/4RK2 b - - 0 1 [1.0]\n\n";
+        let (positions, skipped) = load_positions(text);
+        assert_eq!(positions.len(), 1);
+        assert_eq!(skipped, 2, "Skips the bad line and the position in check");
+        assert_eq!(positions[0].result, 0.5);
+    }
+
+    #[test]
+    fn test_sigmoid() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!((sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-12);
+        assert!(sigmoid(-200.0, 1.0) < 0.5);
+    }
+
+    #[test]
+    fn test_fit_k() {
+        // An extra knight that only wins half its games is worth less than the evaluation says
+        let (positions, _) = load_positions("4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1 [1.0]\n\
+                                             4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1 [0.5]\n");
+        let scalars = to_scalars(&default_params(&EvalWeights::default()));
+        let k = fit_k(&positions, &scalars);
+        assert!(k > 0.0 && k < 1.0, "K {}", k);
+        for other in [k / 2.0, k * 2.0] {
+            assert!(evaluation_error(&positions, &scalars, k) < evaluation_error(&positions, &scalars, other));
+        }
+    }
+
+    #[test]
+    fn test_tuning_lowers_the_error() {
+        // White's extra knight wins every time, so its value should go up
+        let (positions, _) = load_positions("4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1 [1.0]\n\
+                                             4k3/pppp4/8/8/8/8/PPPP4/1N2K3 b - - 0 1 [1.0]\n\
+                                             4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1 [0.5]\n");
+        let mut scalars = to_scalars(&default_params(&EvalWeights::default()));
+        let knight = scalars[2 * (MATERIAL_START + 1)];
+        let k = 1.0;
+        let before = evaluation_error(&positions, &scalars, k);
+
+        let mut errors = Vec::new();
+        tune(&positions, &mut scalars, k, 50, 5.0, |_, error| errors.push(error));
+        assert_eq!(errors.len(), 50);
+        assert!(errors[49] < before, "Tuning did not lower the error");
+        assert!(scalars[2 * (MATERIAL_START + 1)] > knight);
+        assert_eq!(scalars[2 * MATERIAL_START], 100.0, "Pawn value moved");
+        assert_eq!(from_scalars(&scalars).len(), scalars.len() / 2);
+    }
+}
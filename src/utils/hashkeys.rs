@@ -1,6 +1,5 @@
 /// Module for creating a unique key for any given board state
 
-use rand::Rng;
 use crate::constants::{pieces, squares};
 
 #[derive(Debug, Copy, Clone)]
@@ -8,35 +7,80 @@ pub struct BoardHasher {
     pub piece_keys: [[u64; 128]; 13],
     pub side_key: u64,
     pub castle_keys: [u64; 16],
+    pub check_keys: [[u64; 4]; 2], // Three-check counters, no checks given hashes to 0
+    pub pocket_keys: [[u64; 64]; 13], // Count of each piece in a drop variant pocket, an empty pocket hashes to 0
+    pub promoted_keys: [u64; 64], // Promoted pieces in drop variants, by 64 square number
+}
+
+// Seed of the keys every board uses unless given another, fixed so that searches and bench
+// node counts repeat exactly from run to run
+const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// SplitMix64, kept in the crate so the keys made from a seed can't change with the version
+/// of a dependency
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_key(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
 }
 
 impl BoardHasher {
+    /// Creates the keys from the default seed, the same ones every time
     pub fn new() -> BoardHasher {
+        BoardHasher::from_seed(DEFAULT_SEED)
+    }
+
+    /// Creates the keys from a seed, so that every board made with the same seed hashes
+    /// positions the same way and searches them identically
+    pub fn from_seed(seed: u64) -> BoardHasher {
+        let mut rng = SplitMix64(seed);
         // Need to fill arrays with random numbers for each part of the board state
 
-        let mut rng = rand::thread_rng();
         let mut piece_keys: [[u64; 128]; 13] = [[0; 128]; 13];
         for key in piece_keys.iter_mut().flat_map(|r| r.iter_mut()) {
-                *key = rng.gen();
+                *key = rng.next_key();
         }
-        let side_key: u64 = rng.gen();
+        let side_key: u64 = rng.next_key();
         let mut castle_keys: [u64; 16] = [0; 16];
         for key in castle_keys.iter_mut() {
-            *key = rng.gen();
+            *key = rng.next_key();
+        }
+
+        let mut check_keys: [[u64; 4]; 2] = [[0; 4]; 2];
+        for key in check_keys.iter_mut().flat_map(|r| r.iter_mut().skip(1)) {
+            *key = rng.next_key();
+        }
+
+        let mut pocket_keys: [[u64; 64]; 13] = [[0; 64]; 13];
+        for key in pocket_keys.iter_mut().flat_map(|r| r.iter_mut().skip(1)) {
+            *key = rng.next_key();
+        }
+
+        let mut promoted_keys: [u64; 64] = [0; 64];
+        for key in promoted_keys.iter_mut() {
+            *key = rng.next_key();
         }
 
         BoardHasher {
             piece_keys,
             side_key,
             castle_keys,
+            check_keys,
+            pocket_keys,
+            promoted_keys,
         }
     }
 
     pub fn generate_key(self, pieces: [u8; 120], side: u8, en_passant: u8, castle_perm: u8) -> u64 {
-        let mut sq = 0;
         let mut final_key: u64 = 0;
 
-        for piece in pieces {
+        for (sq, piece) in pieces.into_iter().enumerate() {
             if piece != squares::OFFBOARD && piece != pieces::EMPTY {
                 assert!((pieces::WP..=pieces::BK).contains(&piece));
                 final_key ^= self.piece_keys[piece as usize][sq];
@@ -60,11 +104,15 @@ impl BoardHasher {
     }
 
     #[cfg(test)]
-    pub fn seed(piece_keys: [[u64; 128]; 13], side_key: u64, castle_keys: [u64; 16]) -> BoardHasher {
+    pub fn seed(piece_keys: [[u64; 128]; 13], side_key: u64, castle_keys: [u64; 16], check_keys: [[u64; 4]; 2],
+                pocket_keys: [[u64; 64]; 13], promoted_keys: [u64; 64]) -> BoardHasher {
         BoardHasher {
             piece_keys,
             side_key,
             castle_keys,
+            check_keys,
+            pocket_keys,
+            promoted_keys,
         }
     }
 }
@@ -72,7 +120,7 @@ impl BoardHasher {
 
 #[cfg(test)]
 mod test {
-    use crate::utils::hashkeys::BoardHasher;
+    use crate::utils::hashkeys::{BoardHasher, SplitMix64};
 
     #[test]
     fn test_generate_key() {
@@ -126,4 +174,27 @@ mod test {
                    "Did not produce different hashes for different board states"
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_seeded_keys() {
+        let mut pieces: [u8; 120] = [0; 120];
+        pieces[45] = 2;
+        let key = |hasher: BoardHasher| hasher.generate_key(pieces, 0, 42, 3);
+        assert_eq!(key(BoardHasher::from_seed(7)), key(BoardHasher::from_seed(7)), "Same seed gave different keys");
+        assert_ne!(key(BoardHasher::from_seed(7)), key(BoardHasher::from_seed(8)), "Different seeds gave the same keys");
+        assert_eq!(key(BoardHasher::new()), key(BoardHasher::new()), "Default keys differ between hashers");
+    }
+
+    #[test]
+    fn test_default_keys_are_pinned() {
+        // The reference output of SplitMix64 from seed 0
+        let mut rng = SplitMix64(0);
+        assert_eq!(rng.next_key(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_key(), 0x6E78_9E6A_A1B9_65F4);
+
+        // Bench node counts depend on these, so they must only change on purpose
+        let hasher = BoardHasher::new();
+        assert_eq!(hasher.piece_keys[0][0], 0xC0E1_6B16_3A85_A4DC);
+        assert_eq!(hasher.side_key, 0x1453_DBC1_7B50_F205);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+
+/// Hands out numbered results in order when they arrive out of order, as the games played
+/// across threads do. Each result is held until the ones numbered before it are done.
+#[derive(Debug)]
+pub struct InOrder<T> {
+    waiting: BTreeMap<usize, T>,
+    next: usize, // The number of the next result to hand out
+}
+
+impl<T> Default for InOrder<T> {
+    fn default() -> Self {
+        InOrder { waiting: BTreeMap::new(), next: 0 }
+    }
+}
+
+impl<T> InOrder<T> {
+    /// Takes a finished result, handing out every result it completes the run up to
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The result's number, counting from 0
+    /// * `item`: The result
+    /// * `deliver`: Called with each result that is ready and its number, in order
+    ///
+    /// returns: ()
+    pub fn insert(&mut self, index: usize, item: T, mut deliver: impl FnMut(usize, T)) {
+        self.waiting.insert(index, item);
+        while let Some(item) = self.waiting.remove(&self.next) {
+            deliver(self.next, item);
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::in_order::InOrder;
+
+    #[test]
+    fn test_in_order() {
+        let mut in_order = InOrder::default();
+        let mut delivered = Vec::new();
+        for (index, item) in [(2, 'c'), (0, 'a'), (3, 'd'), (1, 'b')] {
+            in_order.insert(index, item, |index, item| delivered.push((index, item)));
+        }
+        assert_eq!(delivered, [(0, 'a'), (1, 'b'), (2, 'c'), (3, 'd')]);
+    }
+}
@@ -5,8 +5,11 @@ use crate::game_board::board::Board;
 use crate::constants::pieces::{*};
 use crate::moves::gamemove::GameMove;
 use crate::moves::movegen::generate_all_moves;
+use crate::constants::squares::{FILE_G, FILE_SQUARES, RANK_SQUARES};
 use crate::moves::validate::is_sq_on_board;
 use crate::utils::square_utils::fr2sq;
+use crate::utils::piece_utils::piece_is_pawn;
+use crate::variants::variant::side_in_check;
 
 #[derive(Debug, Clone)]
 pub struct ParseMoveError;
@@ -17,6 +20,105 @@ impl fmt::Display for ParseMoveError {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFenError {
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseFenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid FEN: {}", self.reason)
+    }
+}
+
+impl Error for ParseFenError {}
+
+#[inline(always)]
+fn fen_error(reason: &'static str) -> ParseFenError {
+    ParseFenError { reason }
+}
+
+/// Checks a rank of a FEN piece placement field describes exactly eight squares
+fn validate_rank(rank: &str) -> Result<(), ParseFenError> {
+    let mut squares = 0;
+    let mut after_piece = false;
+    for ch in rank.chars() {
+        match ch {
+            '1'..='8' => {
+                squares += ch.to_digit(10).unwrap();
+                after_piece = false;
+            }
+            '~' if after_piece => after_piece = false,
+            _ => {
+                if !PIECE_CHARS[1..].contains(&ch) { return Err(fen_error("unknown piece letter")); }
+                squares += 1;
+                after_piece = true;
+            }
+        }
+    }
+    if squares != 8 { Err(fen_error("rank does not have eight squares")) } else { Ok(()) }
+}
+
+/// Checks the holdings of a drop variant FEN only name pieces that can be dropped
+fn validate_holdings(holdings: &str) -> Result<(), ParseFenError> {
+    if holdings.len() > 64 { return Err(fen_error("too many pieces in hand")); }
+    if holdings.chars().all(|ch| "PNBRQpnbrq".contains(ch)) { Ok(()) } else { Err(fen_error("invalid holdings")) }
+}
+
+/// Checks that a string is formatted well enough for `Board::parse_fen` to read it safely:
+/// eight ranks of eight squares with optional holdings, the side to move, castling rights,
+/// an en passant square and any number of counter fields
+///
+/// # Arguments
+///
+/// * `fen`: The FEN string
+///
+/// returns: Result<(), ParseFenError>
+pub fn validate_fen(fen: &str) -> Result<(), ParseFenError> {
+    if !fen.is_ascii() { return Err(fen_error("not ascii")); }
+    let fields: Vec<&str> = fen.split(' ').collect();
+    if fields.len() < 4 { return Err(fen_error("missing fields")); }
+
+    let placement = fields[0];
+    let (board, holdings) = match placement.split_once('[') {
+        Some((board, rest)) => (board, Some(rest.strip_suffix(']').ok_or(fen_error("unclosed holdings"))?)),
+        None => (placement, None),
+    };
+    let mut ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() == 9 && holdings.is_none() {
+        validate_holdings(ranks.pop().unwrap())?;
+    }
+    if ranks.len() != 8 { return Err(fen_error("board does not have eight ranks")); }
+    for rank in ranks {
+        validate_rank(rank)?;
+    }
+    if let Some(holdings) = holdings {
+        validate_holdings(holdings)?;
+    }
+
+    if fields[1] != "w" && fields[1] != "b" { return Err(fen_error("invalid side to move")); }
+
+    let castling = fields[2];
+    let castle_chars_valid = castling.chars().all(|ch| matches!(ch, 'K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h'));
+    if castling != "-" && (castling.is_empty() || !castle_chars_valid) {
+        return Err(fen_error("invalid castling rights"));
+    }
+
+    let ep = fields[3].as_bytes();
+    let ep_valid = fields[3] == "-" || (ep.len() == 2 && (b'a'..=b'h').contains(&ep[0]) && (b'1'..=b'8').contains(&ep[1]));
+    if !ep_valid { return Err(fen_error("invalid en passant square")); }
+
+    for field in fields[4..].iter().filter(|field| !field.is_empty()) {
+        let counter_valid = match field.split_once('+') {
+            Some((white, black)) => [white, black].iter()
+                .all(|count| count.is_empty() || count.parse::<u8>().is_ok() || count.contains('+')),
+            None => field.parse::<u32>().is_ok(),
+        };
+        if !counter_valid { return Err(fen_error("invalid counter")); }
+    }
+    Ok(())
+}
+
 /// Checks if a move input string is correctly formatted
 ///
 /// # Arguments
@@ -55,6 +157,9 @@ fn get_square(itr: &mut Chars<'_>) -> Result<u8, ParseMoveError> {
 }
 
 pub fn validate_move(pos: &Board, text: &str) -> Result<(u8, u8, char), ParseMoveError> {
+    if text.chars().nth(1) == Some('@') {
+        return validate_drop(pos, text);
+    }
     if !text_is_correct_format(text) { return Err(ParseMoveError); }
     let mut itr = text.chars();
     let from =get_square(&mut itr)?;
@@ -65,20 +170,55 @@ pub fn validate_move(pos: &Board, text: &str) -> Result<(u8, u8, char), ParseMov
     Ok((from, to, prom_char))
 }
 
+/// Checks a drop move string such as `P@e4`. A drop is given back with its destination as
+/// both squares and the lowercase piece letter in place of the promotion character.
+fn validate_drop(pos: &Board, text: &str) -> Result<(u8, u8, char), ParseMoveError> {
+    if !pos.variant.has_drops() { return Err(ParseMoveError); }
+    let mut itr = text.chars();
+    let piece_char = itr.next().ok_or(ParseMoveError)?.to_ascii_lowercase();
+    if !matches!(piece_char, 'p' | 'n' | 'b' | 'r' | 'q') { return Err(ParseMoveError); }
+    itr.next();
+    let square: String = itr.collect();
+    if square.len() != 2 || !text_is_correct_format(&square.repeat(2)) { return Err(ParseMoveError); }
+    let to = get_square(&mut square.chars())?;
+    Ok((to, to, piece_char))
+}
+
+/// Finds the generated move matching the given squares. In Chess960 mode castling moves are
+/// given in king-takes-rook form, so the destination is the castling rook's square rather
+/// than the king's final square. Drops are matched by their destination and the piece letter
+/// passed as `prom_char`.
 pub fn parse_move(pos: &Board, from:u8, to:u8, prom_char:char) -> Option<GameMove> {
     let mut move_list:Vec<GameMove> = Vec::new();
     generate_all_moves(pos, &mut move_list);
     let mut prom_piece:u8;
     for mov in move_list {
-        if mov.origin() == from && mov.destination() == to {
+        if mov.is_drop() {
+            let piece_char = PIECE_CHARS[mov.dropped_piece() as usize].to_ascii_lowercase();
+            if from == to && mov.destination() == to && piece_char == prom_char {
+                return Some(mov);
+            }
+            continue;
+        }
+        let destination = if pos.chess960 && mov.is_castle_move() {
+            pos.castle_rook_square(mov)
+        } else {
+            mov.destination()
+        };
+        if mov.origin() == from && destination == to {
             prom_piece = mov.promoted_piece();
             if prom_piece != EMPTY {
-                match prom_piece {
-                    WR | BR => if prom_char == 'r' {return Some(mov);},
-                    WB | BB => if prom_char == 'b' {return Some(mov);},
-                    WN | BN => if prom_char == 'n' {return Some(mov); },
-                    WQ | BQ => if prom_char == 'q' {return Some(mov); },
-                    _ => continue
+                // A promotion without a piece letter is taken to be to a queen
+                let wanted = if matches!(prom_char, 'r' | 'b' | 'n') { prom_char } else { 'q' };
+                let matches = match prom_piece {
+                    WR | BR => wanted == 'r',
+                    WB | BB => wanted == 'b',
+                    WN | BN => wanted == 'n',
+                    WQ | BQ => wanted == 'q',
+                    _ => false,
+                };
+                if !matches {
+                    continue;
                 }
             }
             return Some(mov);
@@ -87,12 +227,159 @@ pub fn parse_move(pos: &Board, from:u8, to:u8, prom_char:char) -> Option<GameMov
     None
 }
 
+/// Writes a move in UCI long algebraic notation such as `e2e4` or `e7e8q`. When the board is in
+/// Chess960 mode (the `UCI_Chess960` option) castling is written as the king capturing its own
+/// rook, e.g. `e1h1`. Drops are written with the piece letter, e.g. `P@e4`.
+///
+/// # Arguments
+///
+/// * `pos`: The board the move is played on
+/// * `mov`: The move to write
+///
+/// returns: String
+pub fn move_to_uci(pos: &Board, mov: GameMove) -> String {
+    let destination = if pos.chess960 && mov.is_castle_move() {
+        pos.castle_rook_square(mov)
+    } else {
+        mov.destination()
+    };
+
+    let mut text = String::new();
+    if mov.is_drop() {
+        text.push(PIECE_CHARS[mov.dropped_piece() as usize].to_ascii_uppercase());
+        text.push('@');
+        text.push((b'a' + FILE_SQUARES[destination as usize]) as char);
+        text.push((b'1' + RANK_SQUARES[destination as usize]) as char);
+        return text;
+    }
+    for sq in [mov.origin(), destination] {
+        text.push((b'a' + FILE_SQUARES[sq as usize]) as char);
+        text.push((b'1' + RANK_SQUARES[sq as usize]) as char);
+    }
+
+    match mov.promoted_piece() {
+        WQ | BQ => text.push('q'),
+        WR | BR => text.push('r'),
+        WB | BB => text.push('b'),
+        WN | BN => text.push('n'),
+        _ => (),
+    }
+    text
+}
+
+/// Reads a move in UCI long algebraic notation, the reverse of `move_to_uci`. The move found
+/// is pseudo legal, making it tells whether it is legal.
+///
+/// # Arguments
+///
+/// * `pos`: The board the move is played on
+/// * `text`: The move, such as `e2e4`, `e7e8q` or `P@e4`
+///
+/// returns: Option<GameMove> None if the text is not a move in the position
+pub fn move_from_uci(pos: &Board, text: &str) -> Option<GameMove> {
+    let (from, to, prom_char) = validate_move(pos, text.trim()).ok()?;
+    parse_move(pos, from, to, prom_char)
+}
+
+/// Lists the legal moves of the position
+fn legal_moves(pos: &mut Board) -> Vec<GameMove> {
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(pos, &mut move_list);
+    move_list.retain(|&mov| {
+        let legal = pos.make_move(mov);
+        if legal {
+            pos.undo_move();
+        }
+        legal
+    });
+    move_list
+}
+
+/// Writes a legal move in Standard Algebraic Notation such as `Nbd7`, `exd5`, `e8=Q+`,
+/// `O-O` or `N@f3#`, as used in PGN
+///
+/// # Arguments
+///
+/// * `pos`: The board the move is played on, given back unchanged
+/// * `mov`: The move to write
+///
+/// returns: String
+pub fn move_to_san(pos: &mut Board, mov: GameMove) -> String {
+    let square = |sq: u8| format!("{}{}", (b'a' + FILE_SQUARES[sq as usize]) as char, (b'1' + RANK_SQUARES[sq as usize]) as char);
+    let (origin, destination) = (mov.origin(), mov.destination());
+    let piece = pos.pieces[origin as usize];
+    let captures = mov.capture() != EMPTY || mov.is_en_passant();
+
+    let mut text = if mov.is_drop() {
+        format!("{}@{}", PIECE_CHARS[mov.dropped_piece() as usize].to_ascii_uppercase(), square(destination))
+    } else if mov.is_castle_move() {
+        if FILE_SQUARES[destination as usize] == FILE_G { "O-O".to_string() } else { "O-O-O".to_string() }
+    } else if piece_is_pawn(piece) {
+        let mut text = if captures {
+            format!("{}x{}", (b'a' + FILE_SQUARES[origin as usize]) as char, square(destination))
+        } else {
+            square(destination)
+        };
+        if mov.promoted_piece() != EMPTY {
+            text.push('=');
+            text.push(PIECE_CHARS[mov.promoted_piece() as usize].to_ascii_uppercase());
+        }
+        text
+    } else {
+        // Name the origin file, else rank, else both when other pieces of the same kind
+        // could also move to the destination
+        let others: Vec<u8> = legal_moves(pos).into_iter()
+            .filter(|other| !other.is_drop() && other.destination() == destination && other.origin() != origin
+                && pos.pieces[other.origin() as usize] == piece)
+            .map(|other| other.origin())
+            .collect();
+        let same_file = others.iter().any(|&sq| FILE_SQUARES[sq as usize] == FILE_SQUARES[origin as usize]);
+        let same_rank = others.iter().any(|&sq| RANK_SQUARES[sq as usize] == RANK_SQUARES[origin as usize]);
+        let from = if others.is_empty() {
+            String::new()
+        } else if !same_file {
+            square(origin)[..1].to_string()
+        } else if !same_rank {
+            square(origin)[1..].to_string()
+        } else {
+            square(origin)
+        };
+        format!("{}{}{}{}", PIECE_CHARS[piece as usize].to_ascii_uppercase(), from, if captures { "x" } else { "" },
+                square(destination))
+    };
+
+    if pos.make_move(mov) {
+        if side_in_check(pos, pos.side) {
+            text.push(if legal_moves(pos).is_empty() { '#' } else { '+' });
+        }
+        pos.undo_move();
+    }
+    text
+}
+
+/// Reads a move in Standard Algebraic Notation, the reverse of `move_to_san`. Check marks
+/// and annotations such as `!?` are optional, and castling may be written with zeros.
+///
+/// # Arguments
+///
+/// * `pos`: The board the move is played on, given back unchanged
+/// * `text`: The move
+///
+/// returns: Option<GameMove> None if the text is not a legal move in the position
+pub fn move_from_san(pos: &mut Board, text: &str) -> Option<GameMove> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+    legal_moves(pos).into_iter().find(|&mov| move_to_san(pos, mov).trim_end_matches(['+', '#']) == text)
+}
+
 #[cfg(test)]
 mod test {
     use crate::game_board::board::Board;
     use crate::moves::gamemove::GameMove;
     use crate::constants::squares::{*};
-    use crate::utils::io::parse_move;
+    use crate::constants::pieces::{BR, WN, WQ};
+    use crate::moves::gamemove::MFLAG_CA;
+    use crate::utils::io::{move_from_san, move_from_uci, move_to_san, move_to_uci, parse_move, validate_fen, validate_move};
+    use crate::variants::variant::Variant;
     use crate::utils::square_utils::fr2sq;
 
     #[test]
@@ -114,4 +401,110 @@ mod test {
         assert_eq!(mov.destination(), expected_move.destination(), "Did not give correct destination square");
         assert_eq!(mov.is_pawn_start(), expected_move.is_pawn_start(), "Did not set pawn start flag");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_move_to_uci() {
+        let mut board = Board::new();
+        unsafe {board.parse_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")};
+        board.update_material_list();
+        let castle = GameMove::new(E1, G1, 0, 0, MFLAG_CA);
+        assert_eq!(move_to_uci(&board, castle), "e1g1", "Did not write standard castling notation");
+        board.chess960 = true;
+        assert_eq!(move_to_uci(&board, castle), "e1h1", "Did not write king takes rook castling notation");
+
+        let promotion = GameMove::new(fr2sq(FILE_G, RANK_7), fr2sq(FILE_H, RANK_8), BR, WN, 0);
+        assert_eq!(move_to_uci(&board, promotion), "g7h8n", "Did not write promotion piece");
+    }
+
+    #[test]
+    fn test_san() {
+        let mut board = Board::new();
+        let san = |board: &mut Board, uci: &str| {
+            let mov = move_from_uci(board, uci).unwrap();
+            move_to_san(board, mov)
+        };
+        board.set_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(san(&mut board, "e1g1"), "O-O");
+        assert_eq!(san(&mut board, "e1c1"), "O-O-O");
+        assert_eq!(san(&mut board, "d5e6"), "dxe6");
+        assert_eq!(san(&mut board, "e5f7"), "Nxf7");
+        assert_eq!(san(&mut board, "c3b1"), "Nb1");
+        assert_eq!(san(&mut board, "e5d3"), "Nd3");
+        assert_eq!(san(&mut board, "a1b1"), "Rb1");
+        assert_eq!(san(&mut board, "g2h3"), "gxh3");
+
+        // Disambiguation by rank, and by both file and rank
+        board.set_fen("3k4/8/8/7Q/8/Q7/8/Q1Q1K3 w - - 0 1").unwrap();
+        assert_eq!(san(&mut board, "a1a2"), "Q1a2");
+        assert_eq!(san(&mut board, "a1b2"), "Qa1b2");
+        assert_eq!(san(&mut board, "h5h8"), "Qhh8+");
+
+        board.set_fen("6k1/4Pppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(san(&mut board, "d1d8"), "Rd8#");
+        assert_eq!(san(&mut board, "e7e8q"), "e8=Q#");
+        assert_eq!(san(&mut board, "e7e8n"), "e8=N");
+
+        for text in ["Rd8#", "Rd8", "e8=Q", "e8=R+", "Kf1!?"] {
+            let mov = move_from_san(&mut board, text).expect("Did not read move");
+            assert_eq!(move_to_san(&mut board, mov).trim_end_matches(['+', '#']), text.trim_end_matches(['+', '#', '!', '?']));
+        }
+        assert_eq!(move_from_san(&mut board, "Rd9"), None);
+        assert_eq!(move_from_san(&mut board, "Kh2"), None);
+
+        board.set_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert!(move_from_san(&mut board, "0-0-0").is_some());
+    }
+
+    #[test]
+    fn test_move_from_uci() {
+        let mut board = Board::new();
+        board.set_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for text in ["e1g1", "e5f7", "d5e6", "a2a4"] {
+            let mov = move_from_uci(&board, text).expect("Did not read move");
+            assert_eq!(move_to_uci(&board, mov), text);
+        }
+        assert_eq!(move_from_uci(&board, "e2e4"), None, "Read a move of a blocked piece");
+        assert_eq!(move_from_uci(&board, "a7a6"), None, "Read a move of the side not to move");
+        assert_eq!(move_from_uci(&board, "xyz"), None);
+    }
+
+    #[test]
+    fn test_parse_chess960_castle() {
+        let mut board = Board::new();
+        unsafe {board.parse_fen("4k3/8/8/8/8/8/8/1RK5 w B - 0 1")};
+        board.update_material_list();
+        board.chess960 = true;
+
+        let castle = parse_move(&board, C1, B1, '-').expect("Did not find castling move");
+        assert!(castle.is_castle_move(), "King takes rook was not parsed as castling");
+        assert_eq!(castle.destination(), C1, "Castling king should stay on c1");
+
+        let king_move = parse_move(&board, C1, D1, '-').expect("Did not find king move");
+        assert!(!king_move.is_castle_move(), "Normal king move was parsed as castling");
+    }
+
+    #[test]
+    fn test_parse_drop() {
+        let mut board = Board::new();
+        board.variant = Variant::Crazyhouse;
+        unsafe {board.parse_fen("4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1")};
+        board.update_material_list();
+        let (from, to, piece_char) = validate_move(&board, "Q@e4").expect("Did not accept drop notation");
+        let mov = parse_move(&board, from, to, piece_char).expect("Did not find the drop");
+        assert_eq!(mov, GameMove::new_drop(WQ, E4));
+        assert_eq!(move_to_uci(&board, mov), "Q@e4");
+        assert!(validate_move(&board, "K@e4").is_err(), "Accepted a king drop");
+    }
+
+    #[test]
+    fn test_validate_fen() {
+        assert!(validate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_ok());
+        assert!(validate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").is_ok(), "Rejected missing counters");
+        assert!(validate_fen("r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[Pn] b KQkq - 0 1").is_ok());
+        assert!(validate_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err(), "Accepted a long rank");
+        assert!(validate_fen("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err(), "Accepted seven ranks");
+        assert!(validate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").is_err(), "Accepted a bad side");
+        assert!(validate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1").is_err(), "Accepted a bad square");
+        assert!(validate_fen("~nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err(), "Accepted a lone ~");
+    }
+}
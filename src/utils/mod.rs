@@ -1,4 +1,5 @@
 pub mod square_utils;
 pub mod hashkeys;
 pub mod piece_utils;
-pub mod io;
\ No newline at end of file
+pub mod io;
+pub mod time;pub mod in_order;
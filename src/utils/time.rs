@@ -0,0 +1,64 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Moves assumed left in the game when the time control does not say
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+// Kept back from the clock for the delay in sending a move
+const MOVE_OVERHEAD: u64 = 50;
+
+/// Returns the current time in milliseconds since the Unix epoch, for search time limits
+pub fn time_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis() as u64)
+}
+
+/// Works out how long to think about a move, spreading the time left over the moves to the
+/// next time control and spending most of the increment
+///
+/// # Arguments
+///
+/// * `time_left`: The time on the clock in milliseconds
+/// * `increment`: The time added after each move in milliseconds
+/// * `moves_to_go`: Moves until more time is added, None for the rest of the game
+///
+/// returns: u64 The time to think in milliseconds
+pub fn allocate_time(time_left: u64, increment: u64, moves_to_go: Option<u64>) -> u64 {
+    let moves = moves_to_go.filter(|&moves| moves > 0).unwrap_or(DEFAULT_MOVES_TO_GO);
+    let time = time_left / moves + increment * 3 / 4;
+    time.min(time_left.saturating_sub(MOVE_OVERHEAD)).max(1)
+}
+
+/// Works out when a search that started on the opponent's time should end once the
+/// opponent plays the expected move. Time spent pondering counts towards the time given to
+/// the move, so a long ponder leaves less to spend, but the search gets at least a quarter
+/// of its time after the hit to finish the iteration it is in.
+///
+/// # Arguments
+///
+/// * `ponder_start`: When pondering started in milliseconds since the epoch
+/// * `allocated`: The time given to the move
+/// * `now`: The time of the ponder hit
+///
+/// returns: u64 The end time in milliseconds since the epoch
+pub fn ponder_hit_end_time(ponder_start: u64, allocated: u64, now: u64) -> u64 {
+    (ponder_start + allocated).max(now + allocated / 4)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::time::{allocate_time, ponder_hit_end_time};
+
+    #[test]
+    fn test_allocate_time() {
+        assert_eq!(allocate_time(60000, 0, None), 2000);
+        assert_eq!(allocate_time(60000, 1000, Some(10)), 6750);
+        assert_eq!(allocate_time(1000, 0, Some(1)), 950, "Did not keep back the move overhead");
+        assert_eq!(allocate_time(20, 0, None), 1);
+    }
+
+    #[test]
+    fn test_ponder_hit_end_time() {
+        // A short ponder leaves the rest of the move's time
+        assert_eq!(ponder_hit_end_time(10000, 4000, 11000), 14000);
+        // A ponder longer than the move's time still finishes on a short budget
+        assert_eq!(ponder_hit_end_time(10000, 4000, 20000), 21000);
+    }
+}
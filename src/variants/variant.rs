@@ -0,0 +1,401 @@
+//! Rules for the chess variants the engine can play. Each rule is a small hook that move
+//! generation, make/undo move and the outcome API consult, so standard chess pays only for
+//! a match on the board's variant.
+
+use crate::constants::pieces::{*};
+use crate::constants::squares::{D4, D5, E4, E5, RANK_8, RANK_SQUARES};
+use crate::game_board::board::Board;
+use crate::game_board::outcome::{GameResult, Outcome, Termination};
+use crate::moves::gamemove::GameMove;
+use crate::moves::movegen::square_is_attacked;
+
+/// Number of checks that wins a game of three-check
+pub const CHECKS_TO_WIN: u8 = 3;
+
+const CENTER_SQUARES: [u8; 4] = [D4, E4, D5, E5];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Variant {
+    Standard,
+    KingOfTheHill,
+    ThreeCheck,
+    Atomic,
+    Antichess,
+    Horde,
+    RacingKings,
+    Crazyhouse,
+    Bughouse, // One board of a bughouse game, captured pieces go to the partner instead
+}
+
+impl Variant {
+    pub const ALL: [Variant; 9] = [
+        Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Atomic, Variant::Antichess,
+        Variant::Horde, Variant::RacingKings, Variant::Crazyhouse, Variant::Bughouse,
+    ];
+
+    /// Looks up a variant by its UCI_Variant name. Chess960 isn't a variant here but standard
+    /// chess with the board's `chess960` flag set, so its names give None.
+    pub fn from_name(name: &str) -> Option<Variant> {
+        match name.to_ascii_lowercase().as_str() {
+            "chess" | "standard" => Some(Variant::Standard),
+            "kingofthehill" | "koth" => Some(Variant::KingOfTheHill),
+            "3check" | "threecheck" => Some(Variant::ThreeCheck),
+            "atomic" => Some(Variant::Atomic),
+            "antichess" | "giveaway" => Some(Variant::Antichess),
+            "horde" => Some(Variant::Horde),
+            "racingkings" => Some(Variant::RacingKings),
+            "crazyhouse" | "zh" => Some(Variant::Crazyhouse),
+            "bughouse" => Some(Variant::Bughouse),
+            _ => None,
+        }
+    }
+
+    /// Returns the variant's UCI_Variant name
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::ThreeCheck => "3check",
+            Variant::Atomic => "atomic",
+            Variant::Antichess => "antichess",
+            Variant::Horde => "horde",
+            Variant::RacingKings => "racingkings",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::Bughouse => "bughouse",
+        }
+    }
+
+    /// Returns whether pieces can be dropped from a pocket onto the board
+    #[inline(always)]
+    pub fn has_drops(self) -> bool {
+        matches!(self, Variant::Crazyhouse | Variant::Bughouse)
+    }
+
+    /// Returns the FEN for the variant's usual starting position
+    pub fn starting_fen(self) -> &'static str {
+        match self {
+            Variant::ThreeCheck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1",
+            Variant::Antichess => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            Variant::Horde => "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1",
+            Variant::RacingKings => "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1",
+            Variant::Crazyhouse | Variant::Bughouse => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+            _ => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        }
+    }
+}
+
+#[inline(always)]
+fn has_king(pos: &Board, side: u8) -> bool {
+    pos.pieces[pos.king_sq[side as usize] as usize] == if side == WHITE { WK } else { BK }
+}
+
+#[inline(always)]
+fn kings_touching(pos: &Board) -> bool {
+    let diff = pos.king_sq[WHITE as usize] as i32 - pos.king_sq[BLACK as usize] as i32;
+    matches!(diff.abs(), 1 | 9 | 10 | 11)
+}
+
+/// Determines if a side's king is in check under the rules of the board's variant.
+/// Sides without a king are never in check, and in atomic chess touching kings can't
+/// check each other since capturing would blow up both of them.
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+/// * `side`: The side whose king might be in check
+///
+/// returns: bool
+pub fn side_in_check(pos: &Board, side: u8) -> bool {
+    match pos.variant {
+        Variant::Antichess => false,
+        Variant::Atomic => {
+            has_king(pos, side)
+                && has_king(pos, side ^ 1)
+                && !kings_touching(pos)
+                && square_is_attacked(pos.king_sq[side as usize], side ^ 1, &pos.pieces)
+        }
+        _ => has_king(pos, side) && square_is_attacked(pos.king_sq[side as usize], side ^ 1, &pos.pieces),
+    }
+}
+
+/// Determines if the side that just moved has left the board in a position its variant
+/// doesn't allow, which makes the move illegal
+///
+/// # Arguments
+///
+/// * `pos`: The board state after the move was made
+/// * `mover`: The side that made the move
+///
+/// returns: bool
+pub fn move_was_illegal(pos: &Board, mover: u8) -> bool {
+    match pos.variant {
+        // Blowing up your own king is never allowed, blowing up the opponent's always wins
+        Variant::Atomic => !has_king(pos, mover) || (has_king(pos, mover ^ 1) && side_in_check(pos, mover)),
+        // Giving check is not allowed either
+        Variant::RacingKings => side_in_check(pos, mover) || side_in_check(pos, mover ^ 1),
+        _ => side_in_check(pos, mover),
+    }
+}
+
+/// Checks for a game ending rule that is specific to the board's variant, such as a king
+/// reaching the center in king of the hill or the third check in three-check
+///
+/// # Arguments
+///
+/// * `pos`: The board state
+///
+/// returns: Option<Outcome> The outcome if the variant's rules have ended the game
+pub fn variant_outcome(pos: &Board) -> Option<Outcome> {
+    let win = |side: u8| Some(Outcome {
+        result: if side == WHITE { GameResult::WhiteWin } else { GameResult::BlackWin },
+        termination: Termination::VariantEnd,
+    });
+
+    match pos.variant {
+        Variant::Standard | Variant::Antichess | Variant::Crazyhouse | Variant::Bughouse => None,
+        Variant::KingOfTheHill => (WHITE..=BLACK)
+            .find(|side| has_king(pos, *side) && CENTER_SQUARES.contains(&pos.king_sq[*side as usize]))
+            .and_then(win),
+        Variant::ThreeCheck => (WHITE..=BLACK)
+            .find(|side| pos.checks_given[*side as usize] >= CHECKS_TO_WIN)
+            .and_then(win),
+        Variant::Atomic => (WHITE..=BLACK)
+            .find(|side| !has_king(pos, *side))
+            .and_then(|side| win(side ^ 1)),
+        Variant::Horde => {
+            let white_pieces: u8 = (WP..=WK).map(|pce| pos.num_pieces[pce as usize]).sum();
+            if white_pieces == 0 { win(BLACK) } else { None }
+        }
+        Variant::RacingKings => racing_kings_outcome(pos),
+    }
+}
+
+fn racing_kings_outcome(pos: &Board) -> Option<Outcome> {
+    let on_goal = |side: u8| RANK_SQUARES[pos.king_sq[side as usize] as usize] == RANK_8;
+    let white_goal = on_goal(WHITE);
+    let black_goal = on_goal(BLACK);
+
+    if !white_goal && !black_goal {
+        return None;
+    }
+
+    // White moves first, so black gets one more move to draw by also reaching the goal
+    if white_goal && !black_goal && pos.side == BLACK {
+        let black_king = pos.king_sq[BLACK as usize] as i32;
+        let can_reach = [9, 10, 11].iter().any(|dir| {
+            let sq = (black_king + dir) as u8;
+            RANK_SQUARES[sq as usize] == RANK_8
+                && PIECE_COLOR[pos.pieces[sq as usize] as usize] != BLACK
+                && !square_is_attacked(sq, WHITE, &pos.pieces)
+        });
+        if can_reach {
+            return None;
+        }
+    }
+
+    let result = match (white_goal, black_goal) {
+        (true, true) => GameResult::Draw,
+        (true, false) => GameResult::WhiteWin,
+        _ => GameResult::BlackWin,
+    };
+    Some(Outcome { result, termination: Termination::VariantEnd })
+}
+
+/// Returns the outcome when the side to move has no legal moves
+pub fn no_moves_outcome(pos: &Board) -> Outcome {
+    let side = pos.side;
+    if pos.variant == Variant::Antichess {
+        // Running out of moves (or pieces) wins in antichess
+        return Outcome {
+            result: if side == WHITE { GameResult::WhiteWin } else { GameResult::BlackWin },
+            termination: Termination::VariantEnd,
+        };
+    }
+
+    if side_in_check(pos, side) {
+        Outcome {
+            result: if side == WHITE { GameResult::BlackWin } else { GameResult::WhiteWin },
+            termination: Termination::Checkmate,
+        }
+    } else {
+        Outcome { result: GameResult::Draw, termination: Termination::Stalemate }
+    }
+}
+
+/// Removes moves the variant forbids from a list of generated moves: kings can't capture in
+/// atomic chess and captures are compulsory in antichess
+///
+/// # Arguments
+///
+/// * `pos`: The board state the moves were generated for
+/// * `list`: The generated moves
+///
+/// returns: ()
+pub fn filter_moves(pos: &Board, list: &mut Vec<GameMove>) {
+    let is_capture = |mov: &GameMove| mov.capture() != EMPTY || mov.is_en_passant();
+    match pos.variant {
+        Variant::Atomic => list.retain(|mov| !(is_capture(mov) && is_king(pos.pieces[mov.origin() as usize]))),
+        Variant::Antichess if list.iter().any(is_capture) => list.retain(is_capture),
+        _ => (),
+    }
+}
+
+#[inline(always)]
+fn is_king(pce: u8) -> bool {
+    pce == WK || pce == BK
+}
+
+/// Square offsets of the eight squares surrounding a square, used for atomic explosions
+pub const EXPLOSION_DIR: [i32; 8] = [-11, -10, -9, -1, 1, 9, 10, 11];
+
+#[cfg(test)]
+mod test {
+    use crate::constants::pieces::{BLACK, BN, BP, BQ, EMPTY, WHITE, WN, WP, WQ};
+    use crate::constants::squares::{*};
+    use crate::game_board::board::Board;
+    use crate::game_board::outcome::{GameResult, Termination};
+    use crate::moves::gamemove::GameMove;
+    use crate::moves::movegen::generate_all_moves;
+    use crate::variants::variant::{variant_outcome, Variant};
+
+    fn variant_board(variant: Variant, fen: &str) -> Board {
+        let mut board = Board::new();
+        board.variant = variant;
+        unsafe { board.parse_fen(fen) };
+        board.update_material_list();
+        board
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Variant::from_name("giveaway"), Some(Variant::Antichess));
+        assert_eq!(Variant::from_name("3check"), Some(Variant::ThreeCheck));
+        assert_eq!(Variant::from_name("shogi"), None);
+        assert_eq!(Variant::from_name("fischerandom"), None);
+        assert_eq!(Variant::from_name(Variant::RacingKings.name()), Some(Variant::RacingKings));
+    }
+
+    #[test]
+    fn test_king_of_the_hill_win() {
+        let mut board = variant_board(Variant::KingOfTheHill, "4k3/8/8/8/8/4K3/8/8 w - - 0 1");
+        assert!(variant_outcome(&board).is_none(), "Game ended before the king reached the center");
+        assert!(board.make_move(GameMove::new(E3, E4, 0, 0, 0)));
+        let outcome = variant_outcome(&board).expect("King reaching the center did not end the game");
+        assert_eq!(outcome.result, GameResult::WhiteWin);
+
+        let mut moves = vec![];
+        generate_all_moves(&board, &mut moves);
+        assert!(moves.is_empty(), "Generated moves after the game was over");
+    }
+
+    #[test]
+    fn test_three_check_counting() {
+        let mut board = variant_board(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1");
+        assert_eq!(board.checks_given, [2, 0], "Did not parse remaining checks");
+        let key = board.pos_key;
+        assert!(board.make_move(GameMove::new(A1, A8, 0, 0, 0)));
+        assert_eq!(board.checks_given[WHITE as usize], 3, "Did not count the check");
+        assert_eq!(variant_outcome(&board).unwrap().result, GameResult::WhiteWin);
+        assert!(board.to_fen().contains(" 0+3 "), "Did not write remaining checks to FEN");
+        board.undo_move();
+        assert_eq!(board.checks_given[WHITE as usize], 2, "Did not undo the check count");
+        assert_eq!(board.pos_key, key, "Did not restore position key");
+    }
+
+    #[test]
+    fn test_atomic_explosion() {
+        let fen = "r1bqkbnr/ppp1pppp/2n5/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+        let mut board = variant_board(Variant::Atomic, fen);
+        let before = board.clone();
+        assert!(board.make_move(GameMove::new(E4, D5, BP, 0, 0)));
+        assert_eq!(board.pieces[D5 as usize], EMPTY, "Capturing pawn did not explode");
+        assert_eq!(board.pieces[C6 as usize], EMPTY, "Neighbouring knight did not explode");
+        assert_eq!(board.pieces[C7 as usize], BP, "Exploded a piece that wasn't next to the capture");
+        assert_eq!(board.num_pieces[BN as usize], 1, "Did not update piece counts");
+        board.undo_move();
+        assert_eq!(board.pieces, before.pieces, "Did not restore exploded pieces");
+        assert_eq!(board.num_pieces, before.num_pieces, "Did not restore piece counts");
+        assert_eq!(board.pos_key, before.pos_key, "Did not restore position key");
+    }
+
+    #[test]
+    fn test_atomic_king_explosion_wins() {
+        let mut board = variant_board(Variant::Atomic, "4k3/4p3/8/8/8/8/8/4RK2 w - - 0 1");
+        assert!(board.make_move(GameMove::new(E1, E7, BP, 0, 0)));
+        let outcome = variant_outcome(&board).expect("Blowing up the king did not end the game");
+        assert_eq!(outcome.result, GameResult::WhiteWin);
+        assert_eq!(outcome.termination, Termination::VariantEnd);
+        board.undo_move();
+        assert_eq!(board.king_sq[BLACK as usize], E8, "Did not restore exploded king square");
+    }
+
+    #[test]
+    fn test_antichess_forced_capture() {
+        let board = variant_board(Variant::Antichess, "8/8/8/3p4/4P3/8/8/8 w - - 0 1");
+        let mut moves = vec![];
+        generate_all_moves(&board, &mut moves);
+        assert_eq!(moves.len(), 1, "Did not force the capture");
+        assert_eq!(moves[0].destination(), D5);
+    }
+
+    #[test]
+    fn test_racing_kings_black_can_draw() {
+        let board = variant_board(Variant::RacingKings, "4K3/1k6/8/8/8/8/8/8 b - - 0 1");
+        assert!(variant_outcome(&board).is_none(), "Black should get a move to reach the goal");
+        let board = variant_board(Variant::RacingKings, "4K3/8/1k6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(variant_outcome(&board).unwrap().result, GameResult::WhiteWin);
+    }
+
+    #[test]
+    fn test_crazyhouse_capture_goes_to_pocket() {
+        let mut board = variant_board(Variant::Crazyhouse, "4k3/8/8/3p4/4P3/8/8/4K3[] w - - 0 1");
+        let key = board.pos_key;
+        assert!(board.make_move(GameMove::new(E4, D5, BP, 0, 0)));
+        assert_eq!(board.pockets[WP as usize], 1, "Captured pawn was not put in hand");
+        assert_eq!(board.to_fen(), "4k3/8/8/3P4/8/8/8/4K3[P] b - - 0 1");
+        board.undo_move();
+        assert_eq!(board.pockets[WP as usize], 0, "Undo did not take the pawn back out of hand");
+        assert_eq!(board.pos_key, key, "Undo did not restore the position key");
+    }
+
+    #[test]
+    fn test_crazyhouse_promoted_piece_demoted() {
+        let mut board = variant_board(Variant::Crazyhouse, "4k3/8/8/8/8/8/3q~4/4K3[] w - - 0 1");
+        assert!(board.make_move(GameMove::new(E1, D2, BQ, 0, 0)));
+        assert_eq!(board.pockets[WP as usize], 1, "Promoted queen did not go back to hand as a pawn");
+        assert_eq!(board.pockets[WQ as usize], 0);
+        assert_eq!(board.promoted, 0, "Captured square kept its promotion mark");
+        board.undo_move();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/3q~4/4K3[] w - - 0 1");
+    }
+
+    #[test]
+    fn test_crazyhouse_drops() {
+        let mut board = variant_board(Variant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3/Nn w - - 0 1");
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3[Nn] w - - 0 1");
+        let mut moves = vec![];
+        generate_all_moves(&board, &mut moves);
+        assert_eq!(moves.iter().filter(|mov| mov.is_drop()).count(), 62, "Did not drop on every empty square");
+
+        let key = board.pos_key;
+        assert!(board.make_move(GameMove::new_drop(WN, D4)));
+        assert_eq!(board.pieces[D4 as usize], WN);
+        assert_eq!(board.pockets[WN as usize], 0);
+        board.undo_move();
+        assert_eq!(board.pieces[D4 as usize], EMPTY);
+        assert_eq!(board.pos_key, key, "Undo did not restore the position key");
+    }
+
+    #[test]
+    fn test_bughouse_hand_transfer() {
+        let mut board = variant_board(Variant::Bughouse, "4k3/8/8/3p4/4P3/8/8/4K3[] w - - 0 1");
+        let mut partner = variant_board(Variant::Bughouse, "4k3/8/8/8/8/8/8/4K3[] b - - 0 1");
+        assert!(board.make_move(GameMove::new(E4, D5, BP, 0, 0)));
+        assert_eq!(board.pockets[WP as usize], 0, "Bughouse capture went into the capturer's own pocket");
+
+        partner.add_to_pocket(board.last_hand_piece().expect("Capture did not give a piece for the hand"));
+        let mut moves = vec![];
+        generate_all_moves(&partner, &mut moves);
+        assert!(moves.contains(&GameMove::new_drop(BP, E5)), "Partner could not drop the handed piece");
+    }
+}
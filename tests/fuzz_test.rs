@@ -0,0 +1,84 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use chess::fuzz::targets::{fuzz_fen, fuzz_move};
+
+// Characters that keep mutated inputs close to valid FENs and moves
+const ALPHABET: &[u8] = b"pnbrqkPNBRQK12345678/ wb-KQkqABCDEFGHabcdefgh+~[]@09";
+
+fn seed_fens() -> Vec<String> {
+    ["tests/perftsuite.txt", "tests/perft960.txt", "tests/perftvariants.txt"].iter()
+        .flat_map(|file| std::fs::read_to_string(file).expect("file not found!")
+            .lines()
+            .filter_map(|line| line.split_once(';').map(|(fen, _)| fen.trim().to_string()))
+            .map(|fen| match fen.split_once(' ') {
+                // Drop the variant prefix, the first byte picks the variant instead
+                Some((name, rest)) if !name.contains('/') => rest.to_string(),
+                _ => fen,
+            })
+            .collect::<Vec<String>>())
+        .collect()
+}
+
+/// Applies a few random byte level changes: replacing, inserting, deleting or truncating
+fn mutate(rng: &mut StdRng, input: &[u8]) -> Vec<u8> {
+    let mut data = input.to_vec();
+    for _ in 0..rng.gen_range(1..=3) {
+        let pos = rng.gen_range(0..=data.len());
+        let byte = if rng.gen_bool(0.9) { ALPHABET[rng.gen_range(0..ALPHABET.len())] } else { rng.gen() };
+        match rng.gen_range(0..4) {
+            0 if pos < data.len() => data[pos] = byte,
+            1 => data.insert(pos, byte),
+            2 if pos < data.len() => { data.remove(pos); }
+            3 => data.truncate(pos),
+            _ => (),
+        }
+    }
+    data
+}
+
+/// Runs the target on every seed and then on random mutations of them. Set FUZZ_ITERATIONS
+/// for a longer run than the default.
+fn run_corpus(seeds: &[Vec<u8>], iterations: usize, seed: u64, target: fn(&[u8])) {
+    let iterations = std::env::var("FUZZ_ITERATIONS").ok().and_then(|n| n.parse().ok()).unwrap_or(iterations);
+    let mut rng = StdRng::seed_from_u64(seed);
+    for seed_input in seeds {
+        target(seed_input);
+    }
+    for _ in 0..iterations {
+        let input = if rng.gen_bool(0.05) {
+            (0..rng.gen_range(0..80)).map(|_| rng.gen()).collect()
+        } else {
+            let seed_input = &seeds[rng.gen_range(0..seeds.len())];
+            let mut input = mutate(&mut rng, seed_input);
+            if !input.is_empty() {
+                input[0] = rng.gen();
+            }
+            input
+        };
+        if std::panic::catch_unwind(|| target(&input)).is_err() {
+            panic!("Target failed on input {:?}", String::from_utf8_lossy(&input));
+        }
+    }
+}
+
+#[test]
+fn fuzz_fen_offline() {
+    let seeds: Vec<Vec<u8>> = seed_fens().iter().map(|fen| {
+        let mut data = vec![0];
+        data.extend_from_slice(fen.as_bytes());
+        data
+    }).collect();
+    run_corpus(&seeds, 3000, 0xC0FFEE, fuzz_fen);
+}
+
+#[test]
+fn fuzz_move_offline() {
+    let seeds: Vec<Vec<u8>> = ["e2a6", "e5f7", "a7b8q", "b2a1n", "e1h1", "e1g1", "P@e4", "n@d5", "g1f3"].iter()
+        .flat_map(|text| (0..4u8).map(move |selector| {
+            let mut data = vec![selector];
+            data.extend_from_slice(text.as_bytes());
+            data
+        }))
+        .collect();
+    run_corpus(&seeds, 3000, 0xBADC0DE, fuzz_move);
+}
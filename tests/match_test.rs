@@ -0,0 +1,56 @@
+use chess::game_board::outcome::GameResult;
+use chess::tournament::engine::EngineConfig;
+use chess::tournament::game::{GameSettings, TimeControl};
+use chess::tournament::openings::parse_epd;
+use chess::tournament::runner::{run_match, MatchConfig};
+
+fn config(games: usize, first_command: &str) -> MatchConfig {
+    let engine = |command: &str, name: &str| EngineConfig {
+        command: command.to_string(),
+        name: Some(name.to_string()),
+        options: vec![(String::from("Hash"), String::from("1"))],
+    };
+    // White mates in one, so each game ends on its first move
+    let (openings, _) = parse_epd("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - -");
+    MatchConfig {
+        engines: [engine(first_command, "First"), engine(env!("CARGO_BIN_EXE_chess"), "Second")],
+        games,
+        concurrency: 2,
+        openings,
+        settings: GameSettings { time_control: TimeControl { base: 2000, increment: 0 }, resign: None, draw: None },
+    }
+}
+
+#[test]
+fn match_plays_games_in_order() {
+    let mut games = Vec::new();
+    run_match(&config(3, env!("CARGO_BIN_EXE_chess")), |game, record| {
+        games.push((game, record.clone()));
+        true
+    }).unwrap();
+
+    assert_eq!(games.iter().map(|(game, _)| *game).collect::<Vec<usize>>(), [0, 1, 2]);
+    for (game, record) in games.iter() {
+        let (white, black) = if game % 2 == 0 { ("First", "Second") } else { ("Second", "First") };
+        assert_eq!((record.white.as_str(), record.black.as_str()), (white, black));
+        assert_eq!(record.moves, ["Ra8#"]);
+        assert_eq!(record.result, GameResult::WhiteWin);
+        assert!(record.to_pgn("Test", game + 1).contains("1. Ra8# {White mates} 1-0"));
+    }
+}
+
+#[test]
+fn match_stops_when_asked() {
+    let mut played = 0;
+    run_match(&config(20, env!("CARGO_BIN_EXE_chess")), |_, _| {
+        played += 1;
+        false
+    }).unwrap();
+    assert_eq!(played, 1);
+}
+
+#[test]
+fn match_reports_engine_that_fails_to_start() {
+    let err = run_match(&config(2, "./no-such-engine"), |_, _| true).unwrap_err();
+    assert!(err.reason.contains("no-such-engine"));
+}
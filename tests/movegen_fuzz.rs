@@ -0,0 +1,402 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeSet;
+use chess::game_board::board::Board;
+use chess::moves::gamemove::GameMove;
+use chess::moves::movegen::generate_all_moves;
+use chess::utils::io::move_to_uci;
+
+/// Everything make and undo move are meant to restore, with the piece lists sorted since
+/// undoing a move may leave them in a different order
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    pieces: Vec<u8>,
+    piece_list: Vec<Vec<u8>>,
+    num_pieces: [u8; 13],
+    num_big_pieces: [u8; 2],
+    num_major_pieces: [u8; 2],
+    num_minor_pieces: [u8; 2],
+    material: [u32; 2],
+    pawns: [u64; 3],
+    king_sq: [u8; 2],
+    side: u8,
+    castle_perm: u8,
+    en_passant: u8,
+    fifty_move: u64,
+    ply: u64,
+    history_ply: u64,
+    pos_key: u64,
+}
+
+impl Snapshot {
+    fn of(board: &Board) -> Snapshot {
+        Snapshot {
+            pieces: board.pieces.to_vec(),
+            piece_list: board.piece_list.iter().zip(board.num_pieces).map(|(list, count)| {
+                let mut squares = list[..count as usize].to_vec();
+                squares.sort_unstable();
+                squares
+            }).collect(),
+            num_pieces: board.num_pieces,
+            num_big_pieces: board.num_big_pieces,
+            num_major_pieces: board.num_major_pieces,
+            num_minor_pieces: board.num_minor_pieces,
+            material: board.material,
+            pawns: [board.bitboards[0].board, board.bitboards[1].board, board.bitboards[2].board],
+            king_sq: board.king_sq,
+            side: board.side,
+            castle_perm: board.castle_perm,
+            en_passant: board.en_passant,
+            fifty_move: board.fifty_move,
+            ply: board.ply,
+            history_ply: board.history_ply,
+            pos_key: board.pos_key,
+        }
+    }
+}
+
+/// A deliberately simple standard chess move generator working straight from the FEN, sharing
+/// no code with the engine. Squares are numbered 0 (a1) to 63 (h8).
+struct Reference {
+    squares: [char; 64],
+    white: bool,
+    castling: String,
+    en_passant: Option<usize>,
+}
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRS: [(i32, i32); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+fn offset(sq: usize, (file_step, rank_step): (i32, i32)) -> Option<usize> {
+    let file = (sq % 8) as i32 + file_step;
+    let rank = (sq / 8) as i32 + rank_step;
+    if (0..8).contains(&file) && (0..8).contains(&rank) { Some((rank * 8 + file) as usize) } else { None }
+}
+
+fn square_name(sq: usize) -> String {
+    format!("{}{}", (b'a' + (sq % 8) as u8) as char, sq / 8 + 1)
+}
+
+impl Reference {
+    fn from_fen(fen: &str) -> Reference {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let mut squares = [' '; 64];
+        for (row, text) in fields[0].split('/').enumerate() {
+            let mut file = 0;
+            for c in text.chars() {
+                match c.to_digit(10) {
+                    Some(n) => file += n as usize,
+                    None => {
+                        squares[(7 - row) * 8 + file] = c;
+                        file += 1;
+                    }
+                }
+            }
+        }
+        let en_passant = match fields[3].as_bytes() {
+            [file, rank] => Some(((rank - b'1') * 8 + (file - b'a')) as usize),
+            _ => None,
+        };
+        Reference { squares, white: fields[1] == "w", castling: fields[2].to_string(), en_passant }
+    }
+
+    fn is_own(&self, sq: usize, white: bool) -> bool {
+        self.squares[sq] != ' ' && self.squares[sq].is_ascii_uppercase() == white
+    }
+
+    fn attacked(&self, sq: usize, by_white: bool) -> bool {
+        let enemy = |c: char| if by_white { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() };
+        let pawn_rank = if by_white { -1 } else { 1 };
+        if [(-1, pawn_rank), (1, pawn_rank)].iter()
+            .any(|step| offset(sq, *step).is_some_and(|from| self.squares[from] == enemy('p'))) {
+            return true;
+        }
+        if KNIGHT_STEPS.iter().any(|step| offset(sq, *step).is_some_and(|from| self.squares[from] == enemy('n'))) {
+            return true;
+        }
+        if KING_STEPS.iter().any(|step| offset(sq, *step).is_some_and(|from| self.squares[from] == enemy('k'))) {
+            return true;
+        }
+        for (dirs, slider) in [(ROOK_DIRS, 'r'), (BISHOP_DIRS, 'b')] {
+            for dir in dirs {
+                let mut current = sq;
+                while let Some(next) = offset(current, dir) {
+                    let c = self.squares[next];
+                    if c == enemy(slider) || c == enemy('q') {
+                        return true;
+                    }
+                    if c != ' ' {
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+        false
+    }
+
+    fn in_check(&self, white: bool) -> bool {
+        let king = if white { 'K' } else { 'k' };
+        match self.squares.iter().position(|c| *c == king) {
+            Some(sq) => self.attacked(sq, !white),
+            None => false,
+        }
+    }
+
+    /// Moves as (from, to, promotion), without checking whether they leave the king in check
+    fn pseudo_legal(&self) -> Vec<(usize, usize, Option<char>)> {
+        let mut moves = Vec::new();
+        let white = self.white;
+        let forward = if white { 1 } else { -1 };
+        let (start_rank, last_rank) = if white { (1, 7) } else { (6, 0) };
+
+        for from in 0..64 {
+            if !self.is_own(from, white) {
+                continue;
+            }
+            let add_step = |to: usize, moves: &mut Vec<_>| {
+                if !self.is_own(to, white) {
+                    moves.push((from, to, None));
+                }
+            };
+            match self.squares[from].to_ascii_lowercase() {
+                'p' => {
+                    let mut targets = Vec::new();
+                    if let Some(to) = offset(from, (0, forward)).filter(|to| self.squares[*to] == ' ') {
+                        targets.push(to);
+                        if from / 8 == start_rank {
+                            if let Some(to) = offset(to, (0, forward)).filter(|to| self.squares[*to] == ' ') {
+                                targets.push(to);
+                            }
+                        }
+                    }
+                    for side_step in [-1, 1] {
+                        if let Some(to) = offset(from, (side_step, forward)) {
+                            if self.is_own(to, !white) || self.en_passant == Some(to) {
+                                targets.push(to);
+                            }
+                        }
+                    }
+                    for to in targets {
+                        if to / 8 == last_rank {
+                            for promotion in ['q', 'r', 'b', 'n'] {
+                                moves.push((from, to, Some(promotion)));
+                            }
+                        } else {
+                            moves.push((from, to, None));
+                        }
+                    }
+                }
+                'n' => KNIGHT_STEPS.iter().filter_map(|step| offset(from, *step)).for_each(|to| add_step(to, &mut moves)),
+                'k' => KING_STEPS.iter().filter_map(|step| offset(from, *step)).for_each(|to| add_step(to, &mut moves)),
+                piece => {
+                    let dirs: Vec<(i32, i32)> = match piece {
+                        'r' => ROOK_DIRS.to_vec(),
+                        'b' => BISHOP_DIRS.to_vec(),
+                        _ => ROOK_DIRS.iter().chain(BISHOP_DIRS.iter()).copied().collect(),
+                    };
+                    for dir in dirs {
+                        let mut current = from;
+                        while let Some(to) = offset(current, dir) {
+                            add_step(to, &mut moves);
+                            if self.squares[to] != ' ' {
+                                break;
+                            }
+                            current = to;
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn castles(&self) -> Vec<String> {
+        let (rank, king, rook) = if self.white { (0, 'K', 'R') } else { (56, 'k', 'r') };
+        let mut moves = Vec::new();
+        if self.squares[rank + 4] != king || self.in_check(self.white) {
+            return moves;
+        }
+        // Right letter, rook square, squares that must be empty, squares the king crosses
+        let sides = [
+            (if self.white { 'K' } else { 'k' }, rank + 7, vec![rank + 5, rank + 6], [rank + 5, rank + 6]),
+            (if self.white { 'Q' } else { 'q' }, rank, vec![rank + 1, rank + 2, rank + 3], [rank + 3, rank + 2]),
+        ];
+        for (right, rook_sq, empty, crossed) in sides {
+            if self.castling.contains(right) && self.squares[rook_sq] == rook
+                && empty.iter().all(|sq| self.squares[*sq] == ' ')
+                && crossed.iter().all(|sq| !self.attacked(*sq, !self.white)) {
+                moves.push(format!("{}{}", square_name(rank + 4), square_name(crossed[1])));
+            }
+        }
+        moves
+    }
+
+    fn legal_moves(&self) -> BTreeSet<String> {
+        let mut legal = BTreeSet::new();
+        for (from, to, promotion) in self.pseudo_legal() {
+            let mut after = Reference { squares: self.squares, white: self.white, castling: String::new(), en_passant: None };
+            let piece = after.squares[from];
+            if piece.eq_ignore_ascii_case(&'p') && self.en_passant == Some(to) {
+                after.squares[if self.white { to - 8 } else { to + 8 }] = ' ';
+            }
+            after.squares[from] = ' ';
+            after.squares[to] = match promotion {
+                Some(p) if self.white => p.to_ascii_uppercase(),
+                Some(p) => p,
+                None => piece,
+            };
+            if !after.in_check(self.white) {
+                let mut text = format!("{}{}", square_name(from), square_name(to));
+                text.extend(promotion);
+                legal.insert(text);
+            }
+        }
+        legal.extend(self.castles());
+        legal
+    }
+}
+
+/// Returns the engine's legal moves, checking that making and undoing each one restores the
+/// board exactly and that the incremental key is right after every move
+fn checked_legal_moves(board: &mut Board) -> Vec<GameMove> {
+    let before = Snapshot::of(board);
+    let fen = board.to_fen();
+    let mut move_list: Vec<GameMove> = Vec::new();
+    generate_all_moves(board, &mut move_list);
+
+    let mut legal = Vec::new();
+    for mov in move_list {
+        if board.make_move(mov) {
+            assert_eq!(board.pos_key, board.compute_pos_key(), "Incremental key was wrong after {} in {}", mov, fen);
+            board.undo_move();
+            legal.push(mov);
+        }
+        assert_eq!(Snapshot::of(board), before, "Undoing {} did not restore {}", mov, fen);
+    }
+    legal
+}
+
+fn check_position(board: &mut Board) -> Vec<GameMove> {
+    let fen = board.to_fen();
+    assert_eq!(board.pos_key, board.compute_pos_key(), "Incremental key was wrong in {}", fen);
+
+    let mut reparsed = Board::new();
+    reparsed.set_fen(&fen).unwrap_or_else(|error| panic!("Could not parse written FEN {}: {}", fen, error));
+    assert_eq!(reparsed.to_fen(), fen, "FEN did not round trip");
+    assert_eq!(reparsed.pieces, board.pieces, "Reparsing {} changed the pieces", fen);
+
+    let legal = checked_legal_moves(board);
+    let engine: BTreeSet<String> = legal.iter().map(|mov| move_to_uci(board, *mov)).collect();
+    let reference = Reference::from_fen(&fen).legal_moves();
+    assert_eq!(engine, reference, "Legal moves differ from the reference in {}", fen);
+    legal
+}
+
+/// Places both kings and a handful of other pieces at random. Pawns stay off the back ranks
+/// and castling rights are given whenever the king and rook are on their starting squares.
+fn random_fen(rng: &mut StdRng) -> String {
+    loop {
+        let mut squares = [' '; 64];
+        let mut place = |piece: char, rng: &mut StdRng| {
+            let range = if piece.eq_ignore_ascii_case(&'p') { 8..56 } else { 0..64 };
+            let sq = loop {
+                let sq = rng.gen_range(range.clone());
+                if squares[sq] == ' ' {
+                    break sq;
+                }
+            };
+            squares[sq] = piece;
+        };
+        place('K', rng);
+        place('k', rng);
+        for _ in 0..rng.gen_range(0..16) {
+            place(*b"PNBRQpnbrq".map(char::from).choose(rng).unwrap(), rng);
+        }
+
+        let mut board_text = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match squares[rank * 8 + file] {
+                    ' ' => empty += 1,
+                    c => {
+                        if empty > 0 {
+                            board_text.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        board_text.push(c);
+                    }
+                }
+            }
+            if empty > 0 {
+                board_text.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                board_text.push('/');
+            }
+        }
+
+        let mut castling: String = [('K', 4, 7, 'R'), ('Q', 4, 0, 'R'), ('k', 60, 63, 'r'), ('q', 60, 56, 'r')].iter()
+            .filter(|(_, king, rook, rook_char)| {
+                squares[*king] == if *rook_char == 'R' { 'K' } else { 'k' } && squares[*rook] == *rook_char
+            })
+            .map(|(right, ..)| *right)
+            .collect();
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let side = if rng.gen_bool(0.5) { "w" } else { "b" };
+        let fen = format!("{} {} {} - 0 1", board_text, side, castling);
+        // Positions where the side that just moved is in check are rejected by the parser
+        if Board::new().set_fen(&fen).is_ok() {
+            return fen;
+        }
+    }
+}
+
+fn standard_fens() -> Vec<String> {
+    std::fs::read_to_string("tests/perftsuite.txt").expect("file not found!")
+        .lines()
+        .filter_map(|line| line.split_once(';').map(|(fen, _)| fen.trim().to_string()))
+        .collect()
+}
+
+/// Plays random legal games, checking every position along the way and unwinding the whole
+/// game at the end
+fn play_random_games(start_fens: &[String], games: usize, max_plies: usize, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..games {
+        let fen = match start_fens.choose(&mut rng) {
+            Some(fen) if rng.gen_bool(0.5) => fen.clone(),
+            _ => random_fen(&mut rng),
+        };
+        let mut board = Board::new();
+        board.set_fen(&fen).unwrap_or_else(|error| panic!("Could not parse {}: {}", fen, error));
+        let start = Snapshot::of(&board);
+
+        let mut plies = 0;
+        while plies < max_plies {
+            let legal = check_position(&mut board);
+            match legal.choose(&mut rng) {
+                Some(mov) => assert!(board.make_move(*mov)),
+                None => break,
+            }
+            plies += 1;
+        }
+        for _ in 0..plies {
+            board.undo_move();
+        }
+        assert_eq!(Snapshot::of(&board), start, "Unwinding the game did not restore {}", fen);
+    }
+}
+
+#[test]
+fn random_games_match_reference() {
+    let games = std::env::var("FUZZ_GAMES").ok().and_then(|n| n.parse().ok()).unwrap_or(60);
+    play_random_games(&standard_fens(), games, 80, 0x5EED);
+}
@@ -1,93 +1,56 @@
-use chess::game_board::board::Board;
-use std::env;
-use std::fs;
-use std::num::ParseIntError;
-use std::str::FromStr;
-use std::time::{SystemTime, UNIX_EPOCH};
-use chess::constants::squares::{FILE_E, FILE_F, RANK_8};
-use chess::moves::gamemove::GameMove;
-use chess::moves::movegen::generate_all_moves;
-use chess::utils::square_utils::fr2sq;
+use chess::perft::parallel::{parallel_perft, ParallelOptions};
+use chess::perft::suite::SuitePosition;
 
-#[derive(Clone)]
-struct PositionCounts {
-    pub fen:String,
-    pub nums: Vec<u64>
-}
-
-impl FromStr for PositionCounts {
-    type Err = ParseIntError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (fen, num_positions) = match s.split_once(";") {
-            Some(a) => a,
-            None => panic!("Parsing error")
-        };
-
-        let nums = num_positions.split_whitespace().filter_map(|x| x.parse::<u64>().ok()).collect();
-
-        Ok(PositionCounts{
-            fen: fen.to_string(),
-            nums
-        })
-    }
-}
-
-fn read_all<T: FromStr>(file_name: &str) -> Vec<Result<T, <T as FromStr>::Err>> {
+/// Reads a suite file, with the line number of each position
+fn read_suite(file_name: &str) -> Vec<(usize, SuitePosition)> {
     std::fs::read_to_string(file_name)
         .expect("file not found!")
         .lines()
-        .map(|x| x.parse())
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let position = line.parse()
+                .unwrap_or_else(|_| panic!("Could not parse line {} of {}: {}", i + 1, file_name, line));
+            (i + 1, position)
+        })
         .collect()
 }
 
-fn perft(depth: u8, board: &mut Board, movs: & Vec<GameMove>) -> u64 {
-    if depth == 0 {
-        return 1;
+fn run_perft_suite(file_name: &str, max_depth: usize, chess960: bool) {
+    for (line, position) in read_suite(file_name).iter() {
+        let board = position.board(chess960)
+            .unwrap_or_else(|err| panic!("Line {} of {}: {}", line, file_name, err));
+        for j in 0..max_depth.min(position.counts.len()) {
+            let move_number = parallel_perft(&board, (j + 1) as u8, ParallelOptions::default());
+            assert_eq!(move_number, position.counts[j], "Did not find correct number of moves for line {} of {} at depth {}", line, file_name, j + 1);
+        }
     }
+}
 
-    let mut move_number = 0;
-
-    let mut movelist: Vec<GameMove> = Vec::new();
-    generate_all_moves(board, &mut movelist);
-
-    for mov in movelist {
-      if !board.make_move(mov) { continue; }
-
-        let mut mov_list = movs.clone();
-        mov_list.push(mov);
-
-
-        move_number += perft(depth - 1, board, &mov_list);
+#[test]
+#[ignore]
+fn perft_test() {
+    run_perft_suite("tests/perftsuite.txt", 7, false);
+}
 
-        board.undo_move();
-    }
-    move_number
+#[test]
+fn perft960_shallow_test() {
+    run_perft_suite("tests/perft960.txt", 3, true);
 }
 
 #[test]
 #[ignore]
-fn perft_test() {
+fn perft960_test() {
+    run_perft_suite("tests/perft960.txt", 6, true);
+}
 
-    let res = SystemTime::now().duration_since(UNIX_EPOCH);
-    let start_time;
-    match res {
-        Ok(n) => start_time = n.as_millis(),
-        _ => panic!("Did not get valid time"),
-    }
-    let positions: Vec<PositionCounts> = read_all::<PositionCounts>("tests/perftsuite.txt")
-        .iter()
-        .filter_map(|x| x.clone().ok())
-        .collect();
+#[test]
+fn perft_variants_shallow_test() {
+    run_perft_suite("tests/perftvariants.txt", 3, false);
+}
 
-    for i in 0..positions.len() {
-        let mut board = Board::new();
-        unsafe{ board.parse_fen(positions[i].fen.as_str()) };
-        board.update_material_list();
-        for j in 0..7 {
-            let move_number = perft((j + 1) as u8, &mut board, &Vec::new());
-            assert_eq!(move_number, positions[i].nums[j], "Did not find ocrrect number of moves for position {} at depth {}", i, j);
-            println!("Evaluated {} positions at depth {} taking {} ms", move_number, j, SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis());
-        }
-    }
+#[test]
+#[ignore]
+fn perft_variants_test() {
+    run_perft_suite("tests/perftvariants.txt", 5, false);
 }